@@ -0,0 +1,607 @@
+/*
+* Copyright (C) 2019-2024, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Helpers that post frequently used families of axioms and query the
+//! algebras found by the solver.
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::bitops::{BOOL_FALSE, BOOL_TRUE};
+use super::model::Model;
+use super::solver::{Domain, Predicate, Solver, Term};
+
+/// The domain and predicates created by `define_semigroup`, `define_monoid`
+/// and `define_group`. The identity and inverse are only present for
+/// monoids and groups.
+#[derive(Debug)]
+pub struct GroupHandles {
+    pub set: Rc<Domain>,
+    pub equ: Rc<Predicate>,
+    pub mul: Rc<Predicate>,
+    pub one: Option<Rc<Predicate>>,
+    pub inv: Option<Rc<Predicate>>,
+}
+
+impl Solver {
+    /// Posts the existence and uniqueness of the last coordinate of the
+    /// given predicate, which makes it the graph of an operation.
+    fn add_operation(&mut self, equ: &Rc<Predicate>, op: &Rc<Predicate>) {
+        let arity = op.arity();
+        assert!(arity >= 1);
+        let vars1: Vec<usize> = (0..arity).collect();
+        let mut vars2 = vars1.clone();
+        vars2[arity - 1] = arity;
+
+        self.add_exist(op.clone());
+        self.add_clause(vec![
+            (false, op.clone(), vars1),
+            (false, op.clone(), vars2),
+            (true, equ.clone(), vec![arity - 1, arity]),
+        ]);
+    }
+
+    /// Creates the `set` domain of the given size with the `equ` equality
+    /// relation and an associative binary operation `mul`.
+    pub fn define_semigroup(&mut self, size: usize) -> GroupHandles {
+        let set = self.add_domain("set".into(), size);
+
+        let equ = self.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        self.set_equality(&equ);
+
+        let mul = self.add_variable("mul".into(), vec![set.clone(), set.clone(), set.clone()]);
+        self.add_operation(&equ, &mul);
+        self.add_associative(&mul, &equ);
+
+        GroupHandles {
+            set,
+            equ,
+            mul,
+            one: None,
+            inv: None,
+        }
+    }
+
+    /// Creates a semigroup with a two-sided identity element `one`.
+    pub fn define_monoid(&mut self, size: usize) -> GroupHandles {
+        let mut handles = self.define_semigroup(size);
+        let set = &handles.set;
+        let equ = &handles.equ;
+        let mul = &handles.mul;
+
+        let one = self.add_variable("one".into(), vec![set.clone()]);
+        self.add_operation(equ, &one);
+        self.add_clause(vec![
+            (false, one.clone(), vec![0]),
+            (false, mul.clone(), vec![0, 1, 2]),
+            (true, equ.clone(), vec![1, 2]),
+        ]);
+        self.add_clause(vec![
+            (false, one.clone(), vec![0]),
+            (false, mul.clone(), vec![1, 0, 2]),
+            (true, equ.clone(), vec![1, 2]),
+        ]);
+
+        handles.one = Some(one);
+        handles
+    }
+
+    /// Creates a monoid with a unary inverse operation `inv`, which is
+    /// exactly a group.
+    pub fn define_group(&mut self, size: usize) -> GroupHandles {
+        let mut handles = self.define_monoid(size);
+        let set = &handles.set;
+        let equ = &handles.equ;
+        let mul = &handles.mul;
+        let one = handles.one.as_ref().unwrap();
+
+        let inv = self.add_variable("inv".into(), vec![set.clone(), set.clone()]);
+        self.add_operation(equ, &inv);
+        self.add_clause(vec![
+            (false, inv.clone(), vec![0, 1]),
+            (false, mul.clone(), vec![1, 0, 2]),
+            (false, one.clone(), vec![3]),
+            (true, equ.clone(), vec![2, 3]),
+        ]);
+
+        handles.inv = Some(inv);
+        handles
+    }
+
+    /// Posts that the binary relation `con` is an equivalence relation that
+    /// is compatible with the binary operation `mul`, that is a congruence.
+    pub fn add_congruence(&mut self, con: &Rc<Predicate>, mul: &Rc<Predicate>) {
+        assert_eq!(con.arity(), 2);
+        assert_eq!(mul.arity(), 3);
+        self.add_clause(vec![(true, con.clone(), vec![0, 0])]);
+        self.add_clause(vec![
+            (false, con.clone(), vec![0, 1]),
+            (true, con.clone(), vec![1, 0]),
+        ]);
+        self.add_clause(vec![
+            (false, con.clone(), vec![0, 1]),
+            (false, con.clone(), vec![1, 2]),
+            (true, con.clone(), vec![0, 2]),
+        ]);
+        self.add_clause(vec![
+            (false, con.clone(), vec![0, 2]),
+            (false, con.clone(), vec![1, 3]),
+            (false, mul.clone(), vec![0, 1, 4]),
+            (false, mul.clone(), vec![2, 3, 5]),
+            (true, con.clone(), vec![4, 5]),
+        ]);
+    }
+
+    /// Posts that the binary relation is symmetric by the clause
+    /// `-rel(x,y) +rel(y,x)`. Both cells of each pair are still stored, but
+    /// the clause propagates each of them to its mirror once assigned.
+    pub fn add_symmetric(&mut self, rel: &Rc<Predicate>) {
+        assert_eq!(rel.arity(), 2);
+        assert!(Rc::ptr_eq(&rel.domains()[0], &rel.domains()[1]));
+        self.add_clause(vec![
+            (false, rel.clone(), vec![0, 1]),
+            (true, rel.clone(), vec![1, 0]),
+        ]);
+    }
+
+    /// Posts that the binary operation, given by the graph `op(x,y,z)` of
+    /// `x*y = z`, is commutative.
+    pub fn add_commutative(&mut self, op: &Rc<Predicate>) {
+        assert_eq!(op.arity(), 3);
+        self.add_clause(vec![
+            (false, op.clone(), vec![0, 1, 2]),
+            (true, op.clone(), vec![1, 0, 2]),
+        ]);
+    }
+
+    /// Posts that the binary operation, given by its graph, is associative.
+    /// The intermediate products `x*y`, `y*z` and the two sides of the
+    /// equation get their own variables. The first clause derives the value
+    /// of `x*(y*z)` directly, while the second one states that the two sides
+    /// are equal by `equ`, so it also propagates from the sides.
+    pub fn add_associative(&mut self, op: &Rc<Predicate>, equ: &Rc<Predicate>) {
+        assert_eq!(op.arity(), 3);
+        assert_eq!(equ.arity(), 2);
+        self.add_clause(vec![
+            (false, op.clone(), vec![0, 1, 3]),
+            (false, op.clone(), vec![3, 2, 4]),
+            (false, op.clone(), vec![1, 2, 5]),
+            (true, op.clone(), vec![0, 5, 4]),
+        ]);
+        self.add_clause(vec![
+            (false, op.clone(), vec![0, 1, 3]),
+            (false, op.clone(), vec![3, 2, 4]),
+            (false, op.clone(), vec![1, 2, 5]),
+            (false, op.clone(), vec![0, 5, 6]),
+            (true, equ.clone(), vec![4, 6]),
+        ]);
+    }
+
+    /// Posts that the binary relation `ord` is a partial order, that is it
+    /// is reflexive, antisymmetric with respect to `equ` and transitive.
+    pub fn add_partial_order(&mut self, ord: &Rc<Predicate>, equ: &Rc<Predicate>) {
+        assert_eq!(ord.arity(), 2);
+        assert_eq!(equ.arity(), 2);
+        let dom = &ord.domains()[0];
+        assert!(Rc::ptr_eq(dom, &ord.domains()[1]));
+        assert!(equ.domains().iter().all(|other| Rc::ptr_eq(dom, other)));
+
+        self.add_clause(vec![(true, ord.clone(), vec![0, 0])]);
+        self.add_clause(vec![
+            (false, ord.clone(), vec![0, 1]),
+            (false, ord.clone(), vec![1, 0]),
+            (true, equ.clone(), vec![0, 1]),
+        ]);
+        self.add_clause(vec![
+            (false, ord.clone(), vec![0, 1]),
+            (false, ord.clone(), vec![1, 2]),
+            (true, ord.clone(), vec![0, 2]),
+        ]);
+    }
+
+    /// Counts the congruences of the binary operation whose table is fully
+    /// assigned in this solver. Congruences correspond to the quotients of
+    /// the algebra, equivalently to its surjective homomorphisms up to the
+    /// labeling of the image, so this is the number of elements of the
+    /// congruence lattice, including the equality and the full relation.
+    pub fn count_quotients(&self, mul: &Rc<Predicate>) -> usize {
+        assert_eq!(mul.arity(), 3);
+        let size = mul.domains()[0].size();
+        assert!(mul.domains().iter().all(|dom| dom.size() == size));
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), size);
+        let op = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set.clone()]);
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let val = self.get_value(mul, &[x, y, z]);
+                    assert!(val == BOOL_FALSE || val == BOOL_TRUE);
+                    sol.set_value(val == BOOL_TRUE, &op, &[x, y, z]);
+                }
+            }
+        }
+
+        let con = sol.add_variable("con".into(), vec![set.clone(), set]);
+        sol.add_congruence(&con, &op);
+        sol.count_all()
+    }
+
+    /// Returns the value of the operation, whose graph is the given predicate,
+    /// at the given arguments. Returns `None` if the cells of these arguments
+    /// are not all assigned or not exactly one of them is true.
+    pub fn get_operation(&self, op: &Rc<Predicate>, args: &[usize]) -> Option<usize> {
+        assert_eq!(op.arity(), args.len() + 1);
+        let size = op.domains()[args.len()].size();
+        let mut coordinates = args.to_vec();
+        coordinates.push(0);
+        let mut result = None;
+        for z in 0..size {
+            coordinates[args.len()] = z;
+            let val = self.get_value(op, &coordinates);
+            if val == BOOL_TRUE && result.is_none() {
+                result = Some(z);
+            } else if val != BOOL_FALSE {
+                return None;
+            }
+        }
+        result
+    }
+
+    /// Returns the order of the element `x` in the group given by the fully
+    /// assigned operation `mul` and identity `one`, which is the least `n`
+    /// with `x^n = 1`. Returns `None` if the tables are not total operations
+    /// or the powers of `x` do not reach the identity.
+    pub fn element_order(
+        &self,
+        mul: &Rc<Predicate>,
+        one: &Rc<Predicate>,
+        x: usize,
+    ) -> Option<usize> {
+        if !self.is_total(mul) || !self.is_total(one) {
+            return None;
+        }
+        let unit = self.get_operation(one, &[])?;
+        let size = mul.domains()[0].size();
+        let mut power = x;
+        for order in 1..=size {
+            if power == unit {
+                return Some(order);
+            }
+            power = self.get_operation(mul, &[power, x])?;
+        }
+        None
+    }
+
+    /// Returns the sorted list of the orders of all elements of the group,
+    /// which distinguishes many non-isomorphic groups.
+    pub fn element_orders(&self, mul: &Rc<Predicate>, one: &Rc<Predicate>) -> Option<Vec<usize>> {
+        let size = mul.domains()[0].size();
+        let mut orders = (0..size)
+            .map(|x| self.element_order(mul, one, x))
+            .collect::<Option<Vec<usize>>>()?;
+        orders.sort();
+        Some(orders)
+    }
+
+    /// Asserts that the given constants denote pairwise distinct elements.
+    /// Each constant is a witness predicate together with the fixed values
+    /// of all but its last coordinate, so `(&one, vec![])` names the element
+    /// `x` with `one(x)` and `(&mul, vec![0, 1])` the one with `mul(0,1,x)`.
+    /// The last axes of the constants must have the domain of the binary
+    /// equality predicate, and the clauses `-con1(..,x) -con2(..,y) -equ(x,y)`
+    /// are posted for all pairs of constants.
+    pub fn add_distinct(
+        &mut self,
+        equ: &Rc<Predicate>,
+        constants: &[(&Rc<Predicate>, Vec<usize>)],
+    ) {
+        assert_eq!(equ.arity(), 2);
+        let dom = &equ.domains()[0];
+        assert!(Rc::ptr_eq(dom, &equ.domains()[1]));
+        for (con, args) in constants.iter() {
+            assert_eq!(con.arity(), args.len() + 1);
+            for (&arg, domain) in args.iter().zip(con.domains()) {
+                assert!(arg < domain.size());
+            }
+            assert!(
+                Rc::ptr_eq(dom, &con.domains()[args.len()]),
+                "the constant {} is not over the domain of {}",
+                con.name(),
+                equ.name()
+            );
+        }
+
+        let literal = |con: &Rc<Predicate>, args: &[usize], var: usize| {
+            let mut terms: Vec<Term> = args.iter().map(|&arg| Term::Const(arg)).collect();
+            terms.push(Term::Var(var));
+            (false, con.clone(), terms)
+        };
+        for (idx, (con1, args1)) in constants.iter().enumerate() {
+            for (con2, args2) in constants[(idx + 1)..].iter() {
+                self.add_clause_terms(vec![
+                    literal(con1, args1, 0),
+                    literal(con2, args2, 1),
+                    (false, equ.clone(), vec![Term::Var(0), Term::Var(1)]),
+                ]);
+            }
+        }
+    }
+}
+
+/// Builds the theory for each domain size in the range with the given
+/// closure, which returns the witness predicate, and collects one model
+/// of each isomorphism class of the witness for each size. The witness is
+/// passed to `set_symmetry_witness`, so the search itself enumerates only
+/// the canonically labeled models, and the closure must not set one.
+pub fn catalog<F>(mut build: F, sizes: Range<usize>) -> Vec<(usize, Vec<Model>)>
+where
+    F: FnMut(&mut Solver, usize) -> Rc<Predicate>,
+{
+    sizes
+        .map(|size| {
+            let mut sol: Solver = Default::default();
+            let witness = build(&mut sol, size);
+            sol.set_symmetry_witness(witness);
+            let mut models = vec![];
+            sol.for_each_solution(|sol| {
+                models.push(sol.model());
+                true
+            });
+            (size, models)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distinct_constants(size: usize) -> usize {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), size);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        sol.set_equality(&equ);
+
+        let mut constants = vec![];
+        for name in ["a", "b", "c"] {
+            let con = sol.add_variable(name.into(), vec![set.clone()]);
+            sol.add_exist(con.clone());
+            sol.add_clause(vec![
+                (false, con.clone(), vec![0]),
+                (false, con.clone(), vec![1]),
+                (true, equ.clone(), vec![0, 1]),
+            ]);
+            constants.push(con);
+        }
+
+        let constants: Vec<(&Rc<Predicate>, Vec<usize>)> =
+            constants.iter().map(|con| (con, vec![])).collect();
+        sol.add_distinct(&equ, &constants);
+        sol.count_all()
+    }
+
+    #[test]
+    fn algebras() {
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        assert_eq!(sol.count_all(), 8);
+
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_monoid(2);
+        assert!(handles.one.is_some() && handles.inv.is_none());
+        assert_eq!(sol.count_all(), 4);
+
+        let mut sol: Solver = Default::default();
+        sol.define_group(3);
+        assert_eq!(sol.count_all(), 3);
+
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(3);
+        assert_eq!(sol.count_all(), 113);
+    }
+
+    // Counts the associative and optionally commutative tables by brute force.
+    fn operations(size: usize, commutative: bool) -> usize {
+        let count = size.pow((size * size) as u32);
+        (0..count)
+            .filter(|&code| {
+                let op = |x: usize, y: usize| code / size.pow((x * size + y) as u32) % size;
+                (0..size).all(|x| {
+                    (0..size).all(|y| {
+                        (!commutative || op(x, y) == op(y, x))
+                            && (0..size).all(|z| op(op(x, y), z) == op(x, op(y, z)))
+                    })
+                })
+            })
+            .count()
+    }
+
+    #[test]
+    fn symmetric() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_symmetric(&rel);
+        sol.set_value(true, &rel, &[0, 1]);
+        sol.set_value(false, &rel, &[2, 0]);
+        assert_ne!(sol.propagate_clauses(), BOOL_FALSE);
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_eq!(sol.get_value(&rel, &[i, j]), sol.get_value(&rel, &[j, i]));
+            }
+        }
+        assert_eq!(sol.get_value(&rel, &[1, 0]), BOOL_TRUE);
+        assert_eq!(sol.get_value(&rel, &[0, 2]), BOOL_FALSE);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_symmetric(&rel);
+        assert_eq!(sol.count_all(), 64);
+    }
+
+    #[test]
+    fn associative() {
+        for size in 1..4 {
+            let mut sol: Solver = Default::default();
+            sol.define_semigroup(size);
+            assert_eq!(sol.count_all(), operations(size, false));
+
+            let mut sol: Solver = Default::default();
+            let handles = sol.define_semigroup(size);
+            sol.add_commutative(&handles.mul);
+            assert_eq!(sol.count_all(), operations(size, true));
+        }
+    }
+
+    #[test]
+    fn partial_order() {
+        // the number of labeled posets
+        for (size, count) in [(1, 1), (2, 3), (3, 19), (4, 219)] {
+            let mut sol: Solver = Default::default();
+            let set = sol.add_domain("set".into(), size);
+            let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+            let ord = sol.add_variable("ord".into(), vec![set.clone(), set]);
+            sol.set_equality(&equ);
+            sol.add_partial_order(&ord, &equ);
+            assert_eq!(sol.count_all(), count);
+        }
+    }
+
+    #[test]
+    fn distinct() {
+        assert_eq!(distinct_constants(1), 0);
+        assert_eq!(distinct_constants(2), 0);
+        assert_eq!(distinct_constants(3), 6);
+        assert_eq!(distinct_constants(4), 24);
+
+        // squaring is a bijection in groups of odd order only
+        for (size, count) in [(2, 0), (3, 3)] {
+            let mut sol: Solver = Default::default();
+            let handles = sol.define_group(size);
+            let squares = [(&handles.mul, vec![0, 0]), (&handles.mul, vec![1, 1])];
+            sol.add_distinct(&handles.equ, &squares);
+            assert_eq!(sol.count_all(), count);
+        }
+
+        // the constants are distinct with respect to the given equality, in
+        // which 0 and 1 are equal, so only b(2) can be true
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let a = sol.add_variable("a".into(), vec![set.clone()]);
+        let b = sol.add_variable("b".into(), vec![set]);
+        for x in 0..3 {
+            sol.set_value(x == 0, &a, &[x]);
+            for y in 0..3 {
+                sol.set_value(x / 2 == y / 2, &equ, &[x, y]);
+            }
+        }
+        sol.add_exist(b.clone());
+        sol.add_distinct(&equ, &[(&a, vec![]), (&b, vec![])]);
+        assert_eq!(sol.count_all(), 1);
+    }
+
+    fn quotients(size: usize, op: impl Fn(usize, usize) -> usize) -> usize {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), size);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    sol.set_value(op(x, y) == z, &mul, &[x, y, z]);
+                }
+            }
+        }
+        sol.count_quotients(&mul)
+    }
+
+    #[test]
+    fn congruences() {
+        assert_eq!(quotients(2, |x, y| (x + y) % 2), 2);
+        assert_eq!(quotients(4, |x, y| (x + y) % 4), 3);
+        assert_eq!(quotients(4, |x, y| x ^ y), 5);
+        assert_eq!(quotients(3, |x, _| x), 5);
+        assert_eq!(quotients(3, |x, y| (x + y) % 3), 2);
+    }
+
+    fn element_orders_of(size: usize, op: impl Fn(usize, usize) -> usize) -> Option<Vec<usize>> {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), size);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set]);
+        for x in 0..size {
+            sol.set_value(x == 0, &one, &[x]);
+            for y in 0..size {
+                for z in 0..size {
+                    sol.set_value(op(x, y) == z, &mul, &[x, y, z]);
+                }
+            }
+        }
+        sol.element_orders(&mul, &one)
+    }
+
+    #[test]
+    fn element_orders() {
+        assert_eq!(
+            element_orders_of(4, |x, y| (x + y) % 4),
+            Some(vec![1, 2, 4, 4])
+        );
+        assert_eq!(element_orders_of(4, |x, y| x ^ y), Some(vec![1, 2, 2, 2]));
+        assert_eq!(
+            element_orders_of(3, |x, y| (x + y) % 3),
+            Some(vec![1, 3, 3])
+        );
+        assert_eq!(element_orders_of(2, |_, _| 1), None);
+
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_group(2);
+        let one = handles.one.unwrap();
+        assert_eq!(sol.element_order(&handles.mul, &one, 1), None);
+    }
+
+    #[test]
+    fn catalog() {
+        let mut witness = None;
+        let catalog = super::catalog(
+            |sol, size| {
+                let mul = sol.define_semigroup(size).mul;
+                witness = Some(mul.clone());
+                mul
+            },
+            1..4,
+        );
+        let counts: Vec<(usize, usize)> = catalog
+            .iter()
+            .map(|(size, models)| (*size, models.len()))
+            .collect();
+        assert_eq!(counts, vec![(1, 1), (2, 5), (3, 24)]);
+
+        // the models of the last size are canonical and pairwise distinct
+        let witness = witness.unwrap();
+        let models = &catalog[2].1;
+        assert!(models.iter().all(|model| model.is_canonical(&witness)));
+        let mut forms: Vec<Vec<u8>> = models
+            .iter()
+            .map(|model| model.canonical_form(&witness))
+            .collect();
+        forms.sort();
+        forms.dedup();
+        assert_eq!(forms.len(), 24);
+    }
+}
@@ -74,37 +74,71 @@ impl Op22 {
     }
 }
 
+/// A binary operation on the first `N` values of `Bit2`, where `N` is at
+/// most four. The table of all cases is packed into a single word.
 #[derive(Debug, Clone, Copy)]
-pub struct Op222(u32);
+pub struct OpN<const N: u32>(u32);
 
-impl Op222 {
+/// A binary operation on all four values of `Bit2`.
+pub type Op222 = OpN<4>;
+
+impl<const N: u32> OpN<N> {
+    /// Creates the operation from the list of its cases, which must contain
+    /// each pair of arguments below `N` exactly once.
     pub const fn new(cases: &[(Bit2, Bit2, Bit2)]) -> Self {
-        assert!(cases.len() == 16);
+        assert!(N >= 1 && N <= 4);
+        assert!(cases.len() == (N * N) as usize);
         let mut set: u32 = 0;
         let mut val: u32 = 0;
         let mut idx = 0;
         while idx < cases.len() {
             let (a, b, c) = cases[idx];
-            assert!(a.0 <= 3 && b.0 <= 3 && c.0 <= 3);
+            assert!(a.0 < N && b.0 < N && c.0 < N);
             let pos = (a.0 << 3) | (b.0 << 1);
+            assert!(set & (3 << pos) == 0);
             val |= c.0 << pos;
             set |= 3 << pos;
             idx += 1;
         }
-        assert!(set == 0xffffffff);
-        Op222(val)
+        OpN(val)
     }
 
     #[inline(always)]
     pub const fn of(self, a: Bit2, b: Bit2) -> Bit2 {
         Bit2((self.0 >> ((a.0 << 3) | (b.0 << 1))) & 3)
     }
+
+    /// Returns the operation applied to two lanes at once. The index is
+    /// the two lanes of the first arguments in the high nibble and the two
+    /// lanes of the second arguments in the low nibble, and the entry holds
+    /// the two lanes of the results.
+    pub const fn expand_byte_table(self) -> [u8; 256] {
+        let mut table = [0; 256];
+        let mut idx = 0;
+        while idx < 256 {
+            let a = idx as u32 >> 4;
+            let b = idx as u32 & 15;
+            let low = self.of(Bit2(a & 3), Bit2(b & 3)).0;
+            let high = self.of(Bit2(a >> 2), Bit2(b >> 2)).0;
+            table[idx] = (low | (high << 2)) as u8;
+            idx += 1;
+        }
+        table
+    }
 }
 
+// The two undefined values have different meanings depending on the table.
+// In the assignment of the solver `BOOL_UNDEF1` is used for unassigned cells
+// and `BOOL_UNDEF2` is reserved as `BOOL_MISSING` for the cells of partial
+// operations that are deliberately left undefined. In an evaluated clause table `BOOL_UNDEF1` marks an instance with a
+// single unassigned literal (a unit that must be propagated), `BOOL_UNDEF2`
+// one with at least two. Propagation functions return `BOOL_UNDEF1` if they
+// made some assignments and `BOOL_UNDEF2` if there was nothing to do yet.
 pub const BOOL_FALSE: Bit2 = Bit2(0);
 pub const BOOL_UNDEF1: Bit2 = Bit2(1);
 pub const BOOL_UNDEF2: Bit2 = Bit2(2);
 pub const BOOL_TRUE: Bit2 = Bit2(3);
+pub const BOOL_MISSING: Bit2 = BOOL_UNDEF2;
 
 pub const BOOL_FORMAT1: [char; 4] = ['0', '?', 'x', '1'];
 pub const BOOL_FORMAT2: [&str; 4] = ["false", "undef1", "undef2", "true"];
@@ -154,6 +188,9 @@ pub const BOOL_ORNOT: Op222 = Op222::new(&[
     (BOOL_TRUE, BOOL_FALSE, BOOL_TRUE),
 ]);
 
+pub const BOOL_OR_TABLE: [u8; 256] = BOOL_OR.expand_byte_table();
+pub const BOOL_ORNOT_TABLE: [u8; 256] = BOOL_ORNOT.expand_byte_table();
+
 pub const BOOL_AND: Op222 = Op222::new(&[
     (BOOL_FALSE, BOOL_FALSE, BOOL_FALSE),
     (BOOL_FALSE, BOOL_UNDEF1, BOOL_FALSE),
@@ -173,6 +210,25 @@ pub const BOOL_AND: Op222 = Op222::new(&[
     (BOOL_TRUE, BOOL_TRUE, BOOL_TRUE),
 ]);
 
+pub const BOOL_XOR: Op222 = Op222::new(&[
+    (BOOL_FALSE, BOOL_FALSE, BOOL_FALSE),
+    (BOOL_FALSE, BOOL_UNDEF1, BOOL_UNDEF1),
+    (BOOL_FALSE, BOOL_UNDEF2, BOOL_UNDEF2),
+    (BOOL_FALSE, BOOL_TRUE, BOOL_TRUE),
+    (BOOL_UNDEF1, BOOL_FALSE, BOOL_UNDEF1),
+    (BOOL_UNDEF1, BOOL_UNDEF1, BOOL_UNDEF2),
+    (BOOL_UNDEF1, BOOL_UNDEF2, BOOL_UNDEF2),
+    (BOOL_UNDEF1, BOOL_TRUE, BOOL_UNDEF1),
+    (BOOL_UNDEF2, BOOL_FALSE, BOOL_UNDEF2),
+    (BOOL_UNDEF2, BOOL_UNDEF1, BOOL_UNDEF2),
+    (BOOL_UNDEF2, BOOL_UNDEF2, BOOL_UNDEF2),
+    (BOOL_UNDEF2, BOOL_TRUE, BOOL_UNDEF2),
+    (BOOL_TRUE, BOOL_FALSE, BOOL_TRUE),
+    (BOOL_TRUE, BOOL_UNDEF1, BOOL_UNDEF1),
+    (BOOL_TRUE, BOOL_UNDEF2, BOOL_UNDEF2),
+    (BOOL_TRUE, BOOL_TRUE, BOOL_FALSE),
+]);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,7 +256,7 @@ mod tests {
         true
     }
 
-    fn associative(op: Op222) -> bool {
+    fn associative<const N: u32>(op: OpN<N>) -> bool {
         for a in 0..3 {
             let a = Bit2(a);
             for b in 0..3 {
@@ -245,6 +301,10 @@ mod tests {
         assert!(distributive(BOOL_OR, BOOL_AND));
         assert!(!distributive(BOOL_AND, BOOL_OR));
 
+        assert!(!idempotent(BOOL_XOR));
+        assert!(commutative(BOOL_XOR));
+        assert!(associative(BOOL_XOR));
+
         for a in 0..3 {
             let a = Bit2(a);
             for b in 0..3 {
@@ -253,4 +313,55 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn byte_table() {
+        for (op, table) in [
+            (BOOL_OR, BOOL_OR_TABLE),
+            (BOOL_AND, BOOL_AND.expand_byte_table()),
+        ] {
+            for (idx, &val) in table.iter().enumerate() {
+                let (a, b) = (idx as u32 >> 4, idx as u32 & 15);
+                let low = op.of(Bit2(a & 3), Bit2(b & 3));
+                let high = op.of(Bit2(a >> 2), Bit2(b >> 2));
+                assert_eq!(val as u32, low.0 | (high.0 << 2));
+            }
+        }
+    }
+
+    #[test]
+    fn three_valued() {
+        let kleene_and: OpN<3> = OpN::new(&[
+            (Bit2(0), Bit2(0), Bit2(0)),
+            (Bit2(0), Bit2(1), Bit2(0)),
+            (Bit2(0), Bit2(2), Bit2(0)),
+            (Bit2(1), Bit2(0), Bit2(0)),
+            (Bit2(1), Bit2(1), Bit2(1)),
+            (Bit2(1), Bit2(2), Bit2(1)),
+            (Bit2(2), Bit2(0), Bit2(0)),
+            (Bit2(2), Bit2(1), Bit2(1)),
+            (Bit2(2), Bit2(2), Bit2(2)),
+        ]);
+        let minus: OpN<3> = OpN::new(&[
+            (Bit2(0), Bit2(0), Bit2(0)),
+            (Bit2(0), Bit2(1), Bit2(2)),
+            (Bit2(0), Bit2(2), Bit2(1)),
+            (Bit2(1), Bit2(0), Bit2(1)),
+            (Bit2(1), Bit2(1), Bit2(0)),
+            (Bit2(1), Bit2(2), Bit2(2)),
+            (Bit2(2), Bit2(0), Bit2(2)),
+            (Bit2(2), Bit2(1), Bit2(1)),
+            (Bit2(2), Bit2(2), Bit2(0)),
+        ]);
+        assert!(associative(kleene_and));
+        assert!(!associative(minus));
+
+        for a in 0..3 {
+            for b in 0..3 {
+                let (x, y) = (Bit2(a), Bit2(b));
+                let z = kleene_and.of(x, y);
+                assert_eq!(z.0, a.min(b));
+            }
+        }
+    }
 }
@@ -19,7 +19,7 @@
 
 use std::ops::Range;
 
-use super::bitops::{Bit1, Bit2, Op222};
+use super::bitops::{Bit1, Bit2, Op222, BOOL_FALSE, BOOL_TRUE, BOOL_UNDEF1, BOOL_UNDEF2};
 
 /// A vector for holding single bits represented as 0 or 1.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -33,19 +33,19 @@ impl Buffer1 {
 
     pub fn new(len: usize, val: Bit1) -> Self {
         let fill = Buffer1::FILL[val.idx()];
-        let data = vec![fill; (len + 31) / 32];
+        let data = vec![fill; len.div_ceil(32)];
         Self { data, len }
     }
 
     pub fn append(&mut self, len: usize, val: Bit1) {
         let fill = Buffer1::FILL[val.idx()];
-        if self.len % 32 != 0 {
+        if !self.len.is_multiple_of(32) {
             let mask = (1 << (self.len % 32)) - 1;
             let val = self.data.last_mut().unwrap();
             *val = (*val & mask) | (fill & !mask);
         }
         self.len += len;
-        self.data.resize((self.len + 31) / 32, fill);
+        self.data.resize(self.len.div_ceil(32), fill);
     }
 
     #[inline(always)]
@@ -95,7 +95,7 @@ impl Buffer1 {
 
             self.data[(range.start / 32 + 1)..(range.end / 32)].fill(fill);
 
-            if range.end % 32 != 0 {
+            if !range.end.is_multiple_of(32) {
                 let mask = (1 << (range.end % 32)) - 1;
                 let temp = self.data[range.end / 32];
                 let temp = (temp & !mask) | (fill & mask);
@@ -117,19 +117,19 @@ impl Buffer2 {
 
     pub fn new(len: usize, val: Bit2) -> Self {
         let fill = Buffer2::FILL[val.idx()];
-        let data = vec![fill; (len + 15) / 16];
+        let data = vec![fill; len.div_ceil(16)];
         Self { data, len }
     }
 
     pub fn append(&mut self, len: usize, val: Bit2) {
         let fill = Buffer2::FILL[val.idx()];
-        if self.len % 16 != 0 {
+        if !self.len.is_multiple_of(16) {
             let mask = (1 << (2 * (self.len % 16))) - 1;
             let val = self.data.last_mut().unwrap();
             *val = (*val & mask) | (fill & !mask);
         }
         self.len += len;
-        self.data.resize((self.len + 15) / 16, fill);
+        self.data.resize(self.len.div_ceil(16), fill);
     }
 
     #[inline(always)]
@@ -180,7 +180,7 @@ impl Buffer2 {
 
             self.data[(range.start / 16 + 1)..(range.end / 16)].fill(fill);
 
-            if range.end % 16 != 0 {
+            if !range.end.is_multiple_of(16) {
                 let mask = (1 << (2 * (range.end % 16))) - 1;
                 let temp = self.data[range.end / 16];
                 let temp = (temp & !mask) | (fill & mask);
@@ -189,6 +189,80 @@ impl Buffer2 {
         }
     }
 
+    /// Returns the number of positions holding the given value. Each word
+    /// is compared with the value in all of its lanes at once.
+    pub fn count(&self, val: Bit2) -> usize {
+        let fill = Buffer2::FILL[val.idx()];
+        let mut count = 0;
+        for (idx, &word) in self.data.iter().enumerate() {
+            let diff = word ^ fill;
+            let mut other = (diff | (diff >> 1)) & 0x55555555;
+            let used = self.len - 16 * idx;
+            if used < 16 {
+                other |= !((1 << (2 * used)) - 1) & 0x55555555;
+            }
+            count += 16 - other.count_ones() as usize;
+        }
+        count
+    }
+
+    /// Returns the conjunction of all values as computed by `BOOL_AND`, which
+    /// is their minimum. All lanes of a word are checked at once, and the
+    /// unused lanes of the last word are treated as true.
+    pub fn fold_and(&self) -> Bit2 {
+        let mut zeros = 0;
+        let mut lows = 0;
+        let mut highs = 0;
+        for (idx, &word) in self.data.iter().enumerate() {
+            let mut word = word;
+            let used = self.len - 16 * idx;
+            if used < 16 {
+                word |= !((1 << (2 * used)) - 1);
+            }
+            let low = word & 0x55555555;
+            let high = (word >> 1) & 0x55555555;
+            zeros |= !high & !low & 0x55555555;
+            lows |= !high & low;
+            highs |= high & !low;
+        }
+        if zeros != 0 {
+            BOOL_FALSE
+        } else if lows != 0 {
+            BOOL_UNDEF1
+        } else if highs != 0 {
+            BOOL_UNDEF2
+        } else {
+            BOOL_TRUE
+        }
+    }
+
+    /// Returns the length of the buffer followed by its packed words, all
+    /// in little endian byte order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + 4 * self.data.len());
+        bytes.extend_from_slice(&(self.len as u64).to_le_bytes());
+        for word in self.data.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Restores a buffer from the output of `to_bytes`, or returns `None`
+    /// if the number of bytes does not match the stored length.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let len = u64::from_le_bytes(bytes.get(..8)?.try_into().unwrap());
+        let len = usize::try_from(len).ok()?;
+        let words = &bytes[8..];
+        if words.len() != 4 * len.div_ceil(16) {
+            return None;
+        }
+        let data = words
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Some(Self { data, len })
+    }
+
     /// Updates all values in this buffer by applying the given binary
     /// operation to values coming from another buffer indexed by the
     /// given iterator.
@@ -203,10 +277,63 @@ impl Buffer2 {
         }
         debug_assert!(last == self.len);
     }
+
+    /// Same as `apply`, but uses the table of `Op222::expand_byte_table` to
+    /// compute two lanes with a single lookup.
+    pub fn apply_fast<ITER>(&mut self, table: &[u8; 256], other: &Self, iter: &mut ITER)
+    where
+        ITER: Iterator<Item = usize>,
+    {
+        let mut pos1 = 0;
+        while pos1 < self.len {
+            let Some(pos2) = iter.next() else {
+                break;
+            };
+            let mut arg = other.get(pos2).idx() as u32;
+            let mut mask = 3;
+            if pos1 + 1 < self.len {
+                if let Some(pos3) = iter.next() {
+                    arg |= (other.get(pos3).idx() as u32) << 2;
+                    mask = 15;
+                }
+            }
+
+            let shift = 2 * (pos1 % 16);
+            let word = &mut self.data[pos1 / 16];
+            let lanes = (*word >> shift) & mask;
+            let val = table[((lanes << 4) | arg) as usize] as u32 & mask;
+            *word = (*word & !(mask << shift)) | (val << shift);
+            pos1 += if mask == 15 { 2 } else { 1 };
+        }
+        debug_assert!(pos1 == self.len && iter.next().is_none());
+    }
+
+    /// Same as `apply`, but only updates the positions in the given range,
+    /// so the iterator must yield exactly as many positions as the length
+    /// of the range.
+    pub fn apply_range<ITER>(
+        &mut self,
+        op: Op222,
+        other: &Self,
+        iter: &mut ITER,
+        range: Range<usize>,
+    ) where
+        ITER: Iterator<Item = usize>,
+    {
+        debug_assert!(range.start <= range.end && range.end <= self.len);
+        let mut last = range.start;
+        for (pos1, pos2) in (range.start..).zip(iter) {
+            debug_assert!(pos1 < range.end);
+            self.set(pos1, op.of(self.get(pos1), other.get(pos2)));
+            last = pos1 + 1;
+        }
+        debug_assert!(last == range.end);
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::bitops::{BOOL_AND, BOOL_OR, BOOL_ORNOT, BOOL_ORNOT_TABLE, BOOL_OR_TABLE};
     use super::*;
 
     fn random(mut seed: u32, len: usize) -> Vec<u32> {
@@ -287,4 +414,101 @@ mod tests {
             assert_eq!(buf2a, buf2b);
         }
     }
+
+    #[test]
+    fn bytes() {
+        let mut buf: Buffer2 = Default::default();
+        for i in 0..37 {
+            buf.append(1, Bit2::new(i % 4));
+        }
+        let bytes = buf.to_bytes();
+        assert_eq!(bytes.len(), 8 + 4 * 3);
+        assert_eq!(Buffer2::from_bytes(&bytes), Some(buf));
+        assert_eq!(Buffer2::from_bytes(&bytes[..12]), None);
+        assert_eq!(Buffer2::from_bytes(&bytes[..5]), None);
+    }
+
+    #[test]
+    fn count() {
+        for (round, len) in [0, 1, 15, 16, 17, 333].into_iter().enumerate() {
+            let vec = random(0x12345678 + round as u32, len);
+            let mut buf = Buffer2::new(len, Bit2::new(0));
+            for (i, a) in vec.iter().enumerate() {
+                buf.set(i, Bit2::new((a >> 7) & 3));
+            }
+            for val in 0..4 {
+                let val = Bit2::new(val);
+                let naive = (0..len).filter(|&i| buf.get(i) == val).count();
+                assert_eq!(buf.count(val), naive);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_range() {
+        let vec = random(0x12345678, 200);
+        let mut other = Buffer2::new(50, Bit2::new(0));
+        let mut buf1 = Buffer2::new(150, Bit2::new(0));
+        for (i, a) in vec.iter().enumerate() {
+            if i < 50 {
+                other.set(i, Bit2::new(a & 3));
+            } else {
+                buf1.set(i - 50, Bit2::new(a & 3));
+            }
+        }
+
+        let op = BOOL_OR;
+        let mut buf2 = buf1.clone();
+        let mut iter = (0..50).rev();
+        buf2.apply_range(op, &other, &mut iter, 30..80);
+        for pos in 0..150 {
+            let val = buf1.get(pos);
+            let val = if (30..80).contains(&pos) {
+                op.of(val, other.get(79 - pos))
+            } else {
+                val
+            };
+            assert_eq!(buf2.get(pos), val);
+        }
+    }
+
+    #[test]
+    fn fold_and() {
+        for round in 0..200 {
+            let vec = random(0x12345678 + round, 1 + round as usize);
+            let sparsity = 1 + round % 9;
+            let mut buf = Buffer2::new(vec.len(), BOOL_TRUE);
+            for (i, a) in vec.iter().enumerate() {
+                if a % sparsity == 0 {
+                    buf.set(i, Bit2::new((a >> 7) & 3));
+                }
+            }
+            let mut val = BOOL_TRUE;
+            for i in 0..buf.len() {
+                val = BOOL_AND.of(val, buf.get(i));
+            }
+            assert_eq!(buf.fold_and(), val);
+        }
+        assert_eq!(Buffer2::new(0, BOOL_FALSE).fold_and(), BOOL_TRUE);
+        assert_eq!(Buffer2::new(5, BOOL_FALSE).fold_and(), BOOL_FALSE);
+    }
+
+    #[test]
+    fn apply_fast() {
+        for len in [0, 1, 2, 15, 16, 17, 101] {
+            let vec = random(0x12345678 + len as u32, 2 * len + 1);
+            let mut other = Buffer2::new(len + 1, BOOL_FALSE);
+            let mut buf1 = Buffer2::new(len, BOOL_FALSE);
+            for i in 0..len {
+                other.set(i, Bit2::new(vec[i] & 3));
+                buf1.set(i, Bit2::new(vec[len + i] & 3));
+            }
+            for (op, table) in [(BOOL_OR, BOOL_OR_TABLE), (BOOL_ORNOT, BOOL_ORNOT_TABLE)] {
+                let mut buf2 = buf1.clone();
+                buf1.apply(op, &other, &mut (0..len).rev());
+                buf2.apply_fast(&table, &other, &mut (0..len).rev());
+                assert_eq!(buf1, buf2);
+            }
+        }
+    }
 }
@@ -0,0 +1,194 @@
+/*
+* Copyright (C) 2019-2024, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A builder for clauses whose variables are given by name.
+
+use std::rc::Rc;
+
+use super::solver::{Domain, Predicate, Solver};
+
+/// The literals of a clause in the form expected by `Solver::add_clause`.
+pub type ClauseLiterals = Vec<(bool, Rc<Predicate>, Vec<usize>)>;
+
+/// The reason why the literals of a clause builder do not form a clause.
+#[derive(PartialEq, Eq, Debug)]
+pub enum BuildError {
+    /// The number of variables does not match the arity of the predicate.
+    Arity {
+        predicate: String,
+        expected: usize,
+        found: usize,
+    },
+    /// The same variable is used at positions of different domains.
+    Domain {
+        variable: String,
+        first: String,
+        second: String,
+    },
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BuildError::Arity {
+                predicate,
+                expected,
+                found,
+            } => write!(
+                f,
+                "predicate {} has arity {} but used with {}",
+                predicate, expected, found
+            ),
+            BuildError::Domain {
+                variable,
+                first,
+                second,
+            } => write!(
+                f,
+                "variable {} is used in domains {} and {}",
+                variable, first, second
+            ),
+        }
+    }
+}
+
+/// Collects the literals of a clause with named variables, for example
+/// `ClauseBuilder::new().lit(false, &mul, &["x", "y", "xy"])`, and maps the
+/// names to the dense variable indices expected by `Solver::add_clause`, in
+/// the order of their first occurrence. The first error is kept and
+/// reported when the clause is built.
+#[derive(Debug, Default)]
+pub struct ClauseBuilder {
+    names: Vec<String>,
+    domains: Vec<Rc<Domain>>,
+    literals: ClauseLiterals,
+    error: Option<BuildError>,
+}
+
+impl ClauseBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a literal with the given sign, predicate and variable names.
+    pub fn lit(mut self, sign: bool, predicate: &Rc<Predicate>, variables: &[&str]) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        if predicate.arity() != variables.len() {
+            self.error = Some(BuildError::Arity {
+                predicate: predicate.name().into(),
+                expected: predicate.arity(),
+                found: variables.len(),
+            });
+            return self;
+        }
+
+        let mut indices = Vec::with_capacity(variables.len());
+        for (dom, &name) in predicate.domains().iter().zip(variables.iter()) {
+            let idx = match self.names.iter().position(|other| other == name) {
+                Some(idx) => {
+                    if !Rc::ptr_eq(dom, &self.domains[idx]) {
+                        self.error = Some(BuildError::Domain {
+                            variable: name.into(),
+                            first: self.domains[idx].name().into(),
+                            second: dom.name().into(),
+                        });
+                        return self;
+                    }
+                    idx
+                }
+                None => {
+                    self.names.push(name.into());
+                    self.domains.push(dom.clone());
+                    self.names.len() - 1
+                }
+            };
+            indices.push(idx);
+        }
+        self.literals.push((sign, predicate.clone(), indices));
+        self
+    }
+
+    /// Returns the literals in the form expected by `Solver::add_clause`.
+    pub fn build(self) -> Result<ClauseLiterals, BuildError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.literals),
+        }
+    }
+
+    /// Adds the clause to the solver and returns the number of its already
+    /// satisfied instances, see `Solver::add_clause`.
+    pub fn add_to(self, solver: &mut Solver) -> Result<usize, BuildError> {
+        Ok(solver.add_clause(self.build()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let two = sol.add_domain("two".into(), 2);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set.clone()]);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set]);
+        let bit = sol.add_variable("bit".into(), vec![two]);
+
+        let literals = ClauseBuilder::new()
+            .lit(false, &mul, &["x", "y", "xy"])
+            .lit(false, &mul, &["x", "y", "z"])
+            .lit(true, &equ, &["xy", "z"])
+            .build()
+            .unwrap();
+        let indices: Vec<&[usize]> = literals.iter().map(|(_, _, v)| v.as_slice()).collect();
+        assert_eq!(indices, vec![&[0, 1, 2][..], &[0, 1, 3], &[2, 3]]);
+
+        let err = ClauseBuilder::new()
+            .lit(true, &equ, &["x", "y"])
+            .lit(true, &bit, &["x"])
+            .build();
+        assert_eq!(
+            err.err(),
+            Some(BuildError::Domain {
+                variable: "x".into(),
+                first: "set".into(),
+                second: "two".into()
+            })
+        );
+
+        let err = ClauseBuilder::new()
+            .lit(true, &equ, &["x"])
+            .add_to(&mut sol);
+        assert_eq!(
+            err,
+            Err(BuildError::Arity {
+                predicate: "equ".into(),
+                expected: 2,
+                found: 1
+            })
+        );
+
+        let satisfied = ClauseBuilder::new()
+            .lit(true, &equ, &["x", "x"])
+            .add_to(&mut sol);
+        assert_eq!(satisfied, Ok(0));
+    }
+}
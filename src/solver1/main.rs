@@ -243,6 +243,7 @@ pub fn main1() {
     sol.propagate_clauses();
     sol.set_value(true, &mul.clone(), &[1, 3, 6]);
 
+    sol.set_verbosity(Verbosity::Trace);
     sol.search_all();
 }
 
@@ -474,6 +475,7 @@ pub fn main2() {
         (false, mul.clone(), vec![3, 2, 3]),
     ]);
 
+    sol.set_verbosity(Verbosity::Trace);
     sol.search_all();
 }
 
@@ -578,5 +580,6 @@ pub fn main3() {
         ]);
     }
 
+    sol.set_verbosity(Verbosity::Trace);
     sol.search_all();
 }
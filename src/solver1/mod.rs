@@ -15,9 +15,14 @@
 * along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+mod axioms;
 pub mod bitops;
 pub mod buffer;
+mod builder;
 pub mod main;
+mod model;
+mod parser;
+mod problems;
 pub mod shape;
 mod solver;
 mod theory;
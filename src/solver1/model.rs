@@ -0,0 +1,227 @@
+/*
+* Copyright (C) 2019-2024, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Snapshots of predicate tables and their canonical forms.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use super::bitops::Bit2;
+use super::buffer::Buffer2;
+use super::solver::Predicate;
+
+/// A snapshot of the values of all predicates, typically taken when the
+/// solver has found a solution.
+#[derive(Debug, Clone)]
+pub struct Model {
+    predicates: Vec<Rc<Predicate>>,
+    assignment: Buffer2,
+}
+
+impl Model {
+    /// Creates a new model from the list of predicates and the values of
+    /// all boolean variables.
+    pub fn new(predicates: Vec<Rc<Predicate>>, assignment: Buffer2) -> Self {
+        Self {
+            predicates,
+            assignment,
+        }
+    }
+
+    /// Returns the list of predicates of this model.
+    pub fn predicates(&self) -> &[Rc<Predicate>] {
+        &self.predicates
+    }
+
+    /// Returns the value of the predicate at the given coordinates.
+    pub fn get_value(&self, predicate: &Predicate, coordinates: &[usize]) -> Bit2 {
+        assert_eq!(predicate.arity(), coordinates.len());
+        let pos = predicate.position(coordinates);
+        self.assignment.get(pos)
+    }
+
+    /// Returns the table of the witness predicate after relabeling every
+    /// element `x` of its domain to `perm[x]`, one byte per cell in the
+    /// order of the relabeled coordinates. The canonical form of the model
+    /// is the smallest of these over all permutations.
+    pub fn serialize_under(&self, perm: &[usize], witness: &Rc<Predicate>) -> Vec<u8> {
+        let size = witness_size(witness);
+        assert_eq!(perm.len(), size);
+        let mut inverse = vec![usize::MAX; size];
+        for (x, &y) in perm.iter().enumerate() {
+            assert!(y < size && inverse[y] == usize::MAX);
+            inverse[y] = x;
+        }
+
+        let shape = witness.shape();
+        let mut coordinates = vec![0; shape.dimension()];
+        let mut result = Vec::with_capacity(shape.volume());
+        for pos in shape.positions() {
+            shape.coordinates(pos, &mut coordinates);
+            for c in coordinates.iter_mut() {
+                *c = inverse[*c];
+            }
+            let val = self.assignment.get(witness.position(&coordinates));
+            result.push(val.idx() as u8);
+        }
+        result
+    }
+
+    /// Returns the lexicographically smallest serialization of the witness
+    /// predicate over all relabelings of its domain. Two models have the same
+    /// canonical form exactly if their witness tables are isomorphic.
+    pub fn canonical_form(&self, witness: &Rc<Predicate>) -> Vec<u8> {
+        let mut perm: Vec<usize> = (0..witness_size(witness)).collect();
+        let mut best = self.serialize_under(&perm, witness);
+        while next_permutation(&mut perm) {
+            let form = self.serialize_under(&perm, witness);
+            if form < best {
+                best = form;
+            }
+        }
+        best
+    }
+
+    /// Returns true if the current labeling gives the canonical form, which
+    /// holds for exactly one labeling in each isomorphism class.
+    pub fn is_canonical(&self, witness: &Rc<Predicate>) -> bool {
+        let mut perm: Vec<usize> = (0..witness_size(witness)).collect();
+        let base = self.serialize_under(&perm, witness);
+        while next_permutation(&mut perm) {
+            if self.serialize_under(&perm, witness) < base {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns a hash of the canonical form of the witness predicate. The
+    /// hash is deterministic, but may change between Rust releases.
+    pub fn canonical_hash(&self, witness: &Rc<Predicate>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.canonical_form(witness).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the number of permutations of the domain of the witness that
+    /// leave its table unchanged.
+    pub fn automorphisms(&self, witness: &Rc<Predicate>) -> usize {
+        let mut perm: Vec<usize> = (0..witness_size(witness)).collect();
+        let base = self.serialize_under(&perm, witness);
+        let mut count = 1;
+        while next_permutation(&mut perm) {
+            if self.serialize_under(&perm, witness) == base {
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Returns the size of the common domain of all coordinates of the witness.
+fn witness_size(witness: &Predicate) -> usize {
+    let domains = witness.domains();
+    assert!(!domains.is_empty());
+    assert!(domains.iter().all(|dom| Rc::ptr_eq(dom, &domains[0])));
+    domains[0].size()
+}
+
+/// Advances the permutation to the next one in lexicographic order and
+/// returns false if it was the last one.
+pub fn next_permutation(perm: &mut [usize]) -> bool {
+    let mut i = perm.len();
+    while i >= 2 && perm[i - 2] >= perm[i - 1] {
+        i -= 1;
+    }
+    if i < 2 {
+        return false;
+    }
+    let mut j = perm.len() - 1;
+    while perm[j] <= perm[i - 2] {
+        j -= 1;
+    }
+    perm.swap(i - 2, j);
+    perm[(i - 1)..].reverse();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::solver::Solver;
+    use super::*;
+
+    fn cyclic(labels: &[usize]) -> (Model, Rc<Predicate>) {
+        let n = labels.len();
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), n);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let val = z == labels[(x + y) % n];
+                    sol.set_value(val, &mul, &[labels[x], labels[y], z]);
+                }
+            }
+        }
+        (sol.model(), mul)
+    }
+
+    #[test]
+    fn permutations() {
+        let mut perm = vec![0, 1, 2, 3];
+        let mut count = 1;
+        while next_permutation(&mut perm) {
+            count += 1;
+        }
+        assert_eq!(count, 24);
+        assert_eq!(perm, vec![3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn canonical() {
+        let (model1, mul1) = cyclic(&[0, 1, 2, 3]);
+        let (model2, mul2) = cyclic(&[2, 0, 3, 1]);
+        assert_ne!(
+            model1.serialize_under(&[0, 1, 2, 3], &mul1),
+            model2.serialize_under(&[0, 1, 2, 3], &mul2)
+        );
+        assert_eq!(
+            model1.serialize_under(&[2, 0, 3, 1], &mul1),
+            model2.serialize_under(&[0, 1, 2, 3], &mul2)
+        );
+        assert_eq!(model1.canonical_form(&mul1), model2.canonical_form(&mul2));
+        assert_eq!(model1.canonical_hash(&mul1), model2.canonical_hash(&mul2));
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 4);
+        let mul3 = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    sol.set_value(z == (x ^ y), &mul3, &[x, y, z]);
+                }
+            }
+        }
+        let model3 = sol.model();
+        assert_ne!(model1.canonical_form(&mul1), model3.canonical_form(&mul3));
+
+        assert_eq!(model1.automorphisms(&mul1), 2);
+        assert_eq!(model2.automorphisms(&mul2), 2);
+        assert_eq!(model3.automorphisms(&mul3), 6);
+    }
+}
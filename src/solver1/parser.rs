@@ -0,0 +1,379 @@
+/*
+* Copyright (C) 2019-2024, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! A parser that builds a solver from the textual description of a theory.
+//!
+//! The input is a sequence of statements separated by whitespace, where
+//! `#` starts a comment until the end of the line:
+//!
+//! ```text
+//! domain set = 3
+//! predicate equ(set,set)
+//! predicate mul(set,set,set)
+//! equality equ
+//! exist mul # mul is an operation
+//! -mul(x0,x1,x2) | -mul(x0,x1,x3) | +equ(x2,x3)
+//! ```
+
+use std::iter::Peekable;
+use std::rc::Rc;
+
+use super::solver::{Domain, Predicate, Solver};
+use super::tokenizer::{Spanned, Token, Tokenizer};
+
+/// The error returned when the input is not a valid theory, with the byte
+/// position of the offending token.
+#[derive(PartialEq, Eq, Debug)]
+pub struct ParseError {
+    offset: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(offset: usize, message: String) -> Self {
+        Self { offset, message }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the byte position in the input where the error was found.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Moves the position of the error, used when the parsed text is only
+    /// a part of a larger input.
+    pub(super) fn shifted(self, start: usize) -> Self {
+        Self::new(self.offset + start, self.message)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+/// Parses the given theory and returns a solver with all of its domains,
+/// predicates and clauses added.
+pub fn parse_theory(input: &str) -> Result<Solver, ParseError> {
+    let mut parser = Parser::new(input);
+    while parser.peek_token().is_some() {
+        parser.parse_statement()?;
+    }
+    Ok(parser.solver)
+}
+
+struct Parser<'a> {
+    tokens: Peekable<Spanned<'a>>,
+    // The position of the last token read and the length of the input.
+    offset: usize,
+    end: usize,
+    solver: Solver,
+    domains: Vec<Rc<Domain>>,
+    predicates: Vec<Rc<Predicate>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            tokens: Tokenizer::new(input, "()+-,|=")
+                .with_comments('#')
+                .spanned()
+                .peekable(),
+            offset: 0,
+            end: input.len(),
+            solver: Default::default(),
+            domains: Default::default(),
+            predicates: Default::default(),
+        }
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        ParseError::new(self.offset, message)
+    }
+
+    fn peek_token(&mut self) -> Option<&Token<'a>> {
+        self.tokens.peek().map(|(_, token)| token)
+    }
+
+    fn next_token(&mut self) -> Result<Token<'a>, ParseError> {
+        match self.tokens.next() {
+            Some((offset, token)) => {
+                self.offset = offset;
+                Ok(token)
+            }
+            None => {
+                self.offset = self.end;
+                Err(self.error("unexpected end of input".into()))
+            }
+        }
+    }
+
+    fn expect_operator(&mut self, oper: char) -> Result<(), ParseError> {
+        match self.next_token()? {
+            Token::Operator(c) if c == oper => Ok(()),
+            token => Err(self.error(format!("expected '{}' but found {:?}", oper, token))),
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<&'a str, ParseError> {
+        match self.next_token()? {
+            Token::Literal(name) => Ok(name),
+            token => Err(self.error(format!("expected a name but found {:?}", token))),
+        }
+    }
+
+    fn expect_integer(&mut self) -> Result<usize, ParseError> {
+        match self.next_token()? {
+            Token::Integer(num) => Ok(num),
+            token => Err(self.error(format!("expected an integer but found {:?}", token))),
+        }
+    }
+
+    fn next_is(&mut self, oper: char) -> bool {
+        self.peek_token() == Some(&Token::Operator(oper))
+    }
+
+    fn find_domain(&self, name: &str) -> Result<Rc<Domain>, ParseError> {
+        self.domains
+            .iter()
+            .find(|dom| dom.name() == name)
+            .cloned()
+            .ok_or_else(|| self.error(format!("unknown domain {}", name)))
+    }
+
+    fn find_predicate(&self, name: &str) -> Result<Rc<Predicate>, ParseError> {
+        self.predicates
+            .iter()
+            .find(|pred| pred.name() == name)
+            .cloned()
+            .ok_or_else(|| self.error(format!("unknown predicate {}", name)))
+    }
+
+    fn parse_statement(&mut self) -> Result<(), ParseError> {
+        match self.peek_token() {
+            Some(Token::Literal("domain")) => self.parse_domain(),
+            Some(Token::Literal("predicate")) => self.parse_predicate(),
+            Some(Token::Literal("exist")) => {
+                self.next_token()?;
+                let name = self.expect_name()?;
+                let pred = self.find_predicate(name)?;
+                self.solver.add_exist(pred);
+                Ok(())
+            }
+            Some(Token::Literal("equality")) => {
+                self.next_token()?;
+                let name = self.expect_name()?;
+                let pred = self.find_predicate(name)?;
+                let doms = pred.domains();
+                if doms.len() != 2 || !Rc::ptr_eq(&doms[0], &doms[1]) {
+                    return Err(self.error(format!(
+                        "equality {} is not a binary relation on a domain",
+                        pred.name()
+                    )));
+                }
+                self.solver.set_equality(&pred);
+                Ok(())
+            }
+            Some(Token::Operator('+')) | Some(Token::Operator('-')) => self.parse_clause(),
+            _ => {
+                let token = self.next_token()?;
+                Err(self.error(format!("expected a statement but found {:?}", token)))
+            }
+        }
+    }
+
+    fn parse_domain(&mut self) -> Result<(), ParseError> {
+        self.next_token()?;
+        let name = self.expect_name()?;
+        let offset = self.offset;
+        self.expect_operator('=')?;
+        let size = self.expect_integer()?;
+        if self.domains.iter().any(|dom| dom.name() == name) {
+            let message = format!("duplicate domain {}", name);
+            return Err(ParseError::new(offset, message));
+        }
+        let dom = self.solver.add_domain(name.into(), size);
+        self.domains.push(dom);
+        Ok(())
+    }
+
+    fn parse_predicate(&mut self) -> Result<(), ParseError> {
+        self.next_token()?;
+        let name = self.expect_name()?;
+        let offset = self.offset;
+        self.expect_operator('(')?;
+        let mut domains = vec![];
+        if !self.next_is(')') {
+            loop {
+                let name = self.expect_name()?;
+                domains.push(self.find_domain(name)?);
+                if !self.next_is(',') {
+                    break;
+                }
+                self.next_token()?;
+            }
+        }
+        self.expect_operator(')')?;
+        if self.predicates.iter().any(|pred| pred.name() == name) {
+            let message = format!("duplicate predicate {}", name);
+            return Err(ParseError::new(offset, message));
+        }
+        let pred = self
+            .solver
+            .try_add_variable(name.into(), domains)
+            .ok_or_else(|| ParseError::new(offset, format!("predicate {} is too large", name)))?;
+        self.predicates.push(pred);
+        Ok(())
+    }
+
+    fn parse_variable(&mut self) -> Result<usize, ParseError> {
+        let name = self.expect_name()?;
+        name.strip_prefix('x')
+            .and_then(|idx| idx.parse::<usize>().ok())
+            .ok_or_else(|| self.error(format!("invalid variable {}", name)))
+    }
+
+    fn parse_clause(&mut self) -> Result<(), ParseError> {
+        let mut domains: Vec<Option<Rc<Domain>>> = vec![];
+        let mut literals = vec![];
+        let start = self.tokens.peek().map_or(self.end, |&(offset, _)| offset);
+        loop {
+            let sign = self.next_is('+');
+            self.next_token()?;
+            let name = self.expect_name()?;
+            let offset = self.offset;
+            let pred = self.find_predicate(name)?;
+            self.expect_operator('(')?;
+            let mut variables = vec![];
+            if !self.next_is(')') {
+                loop {
+                    variables.push(self.parse_variable()?);
+                    if !self.next_is(',') {
+                        break;
+                    }
+                    self.next_token()?;
+                }
+            }
+            self.expect_operator(')')?;
+
+            if variables.len() != pred.arity() {
+                let message = format!(
+                    "predicate {} has arity {} but used with {}",
+                    pred.name(),
+                    pred.arity(),
+                    variables.len()
+                );
+                return Err(ParseError::new(offset, message));
+            }
+            for (dom, &var) in pred.domains().iter().zip(variables.iter()) {
+                if domains.len() <= var {
+                    domains.resize(var + 1, None);
+                }
+                match &domains[var] {
+                    Some(dom2) if !Rc::ptr_eq(dom, dom2) => {
+                        let message = format!(
+                            "variable x{} is used in domains {} and {}",
+                            var,
+                            dom2.name(),
+                            dom.name()
+                        );
+                        return Err(ParseError::new(offset, message));
+                    }
+                    Some(_) => {}
+                    None => domains[var] = Some(dom.clone()),
+                }
+            }
+            literals.push((sign, pred, variables));
+
+            if !self.next_is('|') {
+                break;
+            }
+            self.next_token()?;
+            if !self.next_is('+') && !self.next_is('-') {
+                let token = self.next_token()?;
+                return Err(self.error(format!("expected a literal but found {:?}", token)));
+            }
+        }
+
+        if let Some(var) = domains.iter().position(|dom| dom.is_none()) {
+            let message = format!("variable x{} is not used", var);
+            return Err(ParseError::new(start, message));
+        }
+        self.solver.add_clause(literals);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn semigroups() {
+        let input = "
+            domain set = 2
+            predicate equ(set,set)
+            predicate mul(set,set,set)
+            equality equ
+            exist mul # mul is an operation
+            -mul(x0,x1,x2) | -mul(x0,x1,x3) | +equ(x2,x3)
+            # associativity
+            -mul(x0,x1,x3) | -mul(x3,x2,x4) | -mul(x1,x2,x5) | -mul(x0,x5,x6) | +equ(x4,x6)
+        ";
+        let mut sol = parse_theory(input).unwrap();
+        assert_eq!(sol.count_all(), 8);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |input: &str| parse_theory(input).err().unwrap().message().to_string();
+        assert_eq!(err("domain set = 2 predicate p(sat)"), "unknown domain sat");
+        assert_eq!(
+            err("domain set = 2 predicate p(set) +p(x0,x1)"),
+            "predicate p has arity 1 but used with 2"
+        );
+        assert_eq!(
+            err("domain set = 2 predicate p(set) +q(x0)"),
+            "unknown predicate q"
+        );
+        assert_eq!(
+            err("domain a = 2 domain b = 2 predicate p(a) predicate q(b) +p(x0) | -q(x0)"),
+            "variable x0 is used in domains a and b"
+        );
+        assert_eq!(err("domain set ="), "unexpected end of input");
+    }
+
+    #[test]
+    fn offsets() {
+        let err = |input: &str| parse_theory(input).err().unwrap();
+        assert_eq!(err("domain set = 2 predicate p(sat)").offset(), 27);
+        assert_eq!(
+            err("domain set = 2 predicate p(set) +p(x0,x1)").offset(),
+            33
+        );
+        assert_eq!(err("domain set = 2\n+q(x0)").offset(), 16);
+        assert_eq!(err("domain set =").offset(), 12);
+        assert_eq!(
+            err("domain set = 2 domain set = 3").to_string(),
+            "duplicate domain set at byte 22"
+        );
+    }
+}
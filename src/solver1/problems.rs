@@ -0,0 +1,127 @@
+/*
+* Copyright (C) 2019-2024, Miklos Maroti
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Files holding several theories, each solved independently.
+
+use super::parser::parse_theory;
+use super::solver::Solver;
+use super::tokenizer::{Token, Tokenizer};
+
+/// A named theory loaded from a file with its own solver.
+#[derive(Debug)]
+pub struct Problem {
+    pub name: String,
+    pub solver: Solver,
+}
+
+/// Splits the input into sections at lines starting with `---` or `%%`,
+/// and parses each section as a separate theory. The markers are found by
+/// the tokenizer, so they are ignored inside strings and comments. The rest
+/// of the marker line is the name of the following problem, otherwise the
+/// problems are numbered. Sections containing only whitespace are skipped.
+/// Panics if a section cannot be parsed, where the byte position of the
+/// error is relative to the whole input.
+pub fn load_all(input: &str) -> Vec<Problem> {
+    // the name and the byte range of each section
+    let mut sections: Vec<(Option<&str>, usize, usize)> = vec![(None, 0, input.len())];
+    let mut line_end = 0;
+    for (offset, token) in Tokenizer::new(input, "()+-,|=%")
+        .with_comments('#')
+        .spanned()
+    {
+        if offset < line_end || !matches!(token, Token::Operator('-') | Token::Operator('%')) {
+            continue;
+        }
+        let line_start = input[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+        let line = &input[offset..];
+        if !input[line_start..offset].trim().is_empty()
+            || !(line.starts_with("---") || line.starts_with("%%"))
+        {
+            continue;
+        }
+        line_end = line.find('\n').map_or(input.len(), |pos| offset + pos + 1);
+        let name = input[offset..line_end]
+            .trim_start_matches(['-', '%'])
+            .trim();
+        let name = Some(name).filter(|name| !name.is_empty());
+        sections.last_mut().unwrap().2 = line_start;
+        sections.push((name, line_end, input.len()));
+    }
+
+    let mut problems = vec![];
+    for (name, start, end) in sections.into_iter() {
+        let text = &input[start..end];
+        if text.trim().is_empty() {
+            continue;
+        }
+        let name = match name {
+            Some(name) => name.into(),
+            None => format!("problem {}", problems.len() + 1),
+        };
+        let solver = parse_theory(text)
+            .unwrap_or_else(|err| panic!("invalid theory: {}", err.shifted(start)));
+        problems.push(Problem { name, solver });
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let input = "
+            domain set = 2
+            predicate rel(set,set)
+            --- symmetric
+            domain set = 2
+            predicate rel(set,set)
+            -rel(x0,x1) | +rel(x1,x0)
+            %% reflexive
+            domain set = 2
+            predicate rel(set,set)
+            +rel(x0,x0)
+            -rel(x0,x1) | +rel(x1,x0)
+            ---
+        ";
+        let mut problems = load_all(input);
+        let names: Vec<&str> = problems.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["problem 1", "symmetric", "reflexive"]);
+        let counts: Vec<usize> = problems.iter_mut().map(|p| p.solver.count_all()).collect();
+        assert_eq!(counts, vec![1, 8, 2]);
+    }
+
+    #[test]
+    fn comments() {
+        let input = "
+            domain set = 2
+            # --- not a marker
+            predicate rel(set,set) # %% neither
+            -rel(x0,x1) | +rel(x1,x0)
+        ";
+        let problems = load_all(input);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].solver.predicates().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "at byte 36")]
+    fn error_offset() {
+        load_all("domain set = 2\n---\n domain set = 2 +p(x0)");
+    }
+}
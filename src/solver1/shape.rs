@@ -44,6 +44,17 @@ impl Shape {
         }
     }
 
+    /// Same as `new`, but returns `None` if the volume or the end of the
+    /// position range does not fit into `usize`.
+    pub fn try_new(lengths: Vec<usize>, offset: usize) -> Option<Self> {
+        let mut volume: usize = 1;
+        for &d in lengths.iter() {
+            volume = volume.checked_mul(d)?;
+        }
+        offset.checked_add(volume)?;
+        Some(Self::new(lengths, offset))
+    }
+
     /// Returns the number of side lengths.
     pub fn dimension(&self) -> usize {
         self.lengths.len()
@@ -94,6 +105,20 @@ impl Shape {
         debug_assert!(position == 0);
     }
 
+    /// Returns the shape with the given side lengths and the same offset,
+    /// whose positions are the same as of this one. Returns `None` if the
+    /// volume would be different.
+    pub fn reshape(&self, new_lengths: Vec<usize>) -> Option<Shape> {
+        let mut volume: usize = 1;
+        for &d in new_lengths.iter() {
+            volume = volume.checked_mul(d)?;
+        }
+        if volume != self.volume {
+            return None;
+        }
+        Some(Self::new(new_lengths, self.offset))
+    }
+
     /// Creates the default view of this shape.
     pub fn view(&self) -> ShapeView {
         ShapeView::new(self)
@@ -190,11 +215,39 @@ impl ShapeView {
         }
     }
 
+    /// Fixes the coordinate along the given axis, which removes that axis
+    /// from the view and moves the offset accordingly.
+    pub fn fix(&self, axis: usize, coordinate: usize) -> Self {
+        debug_assert!(axis < self.strides.len());
+        let (length, stride) = self.strides[axis];
+        debug_assert!(coordinate < length);
+        let mut strides = self.strides.clone().into_vec();
+        strides.remove(axis);
+        Self {
+            strides: strides.into_boxed_slice(),
+            offset: self.offset + coordinate * stride,
+        }
+    }
+
+    /// Inserts a new axis of the given length at the given position with
+    /// stride zero, so the view repeats the same elements along that axis.
+    pub fn broadcast(&self, axis: usize, length: usize) -> Self {
+        debug_assert!(axis <= self.strides.len());
+        let mut strides = self.strides.clone().into_vec();
+        strides.insert(axis, (length, 0));
+        Self {
+            strides: strides.into_boxed_slice(),
+            offset: self.offset,
+        }
+    }
+
     /// Computes the polymer of the given view, which allows the introduction
     /// dummy variables and identification of variables. The map must be of
     /// size dimension. The old coordinate `i` will be placed at the new
     /// coordinate `map[i]`. The shapeis the shape of the new view, which is
-    /// used to obtain the lengths of dummy axes.
+    /// used to obtain the lengths of dummy axes. If several old coordinates
+    /// are mapped to the same new one, then their strides are added, so the
+    /// view walks along the diagonal of those axes.
     pub fn polymer(&self, shape: &Shape, map: &[usize]) -> Self {
         debug_assert!(map.len() == self.strides.len());
         let strides: Vec<(usize, usize)> = shape.lengths.iter().map(|&d| (d, 0)).collect();
@@ -207,6 +260,34 @@ impl ShapeView {
         Self { strides, offset }
     }
 
+    /// Same as `polymer`, but checks that the map is valid for the given
+    /// shape and returns the offending axis instead of silently computing
+    /// wrong positions.
+    pub fn try_polymer(&self, shape: &Shape, map: &[usize]) -> Result<Self, PolymerError> {
+        if map.len() != self.strides.len() {
+            return Err(PolymerError::Dimension {
+                expected: self.strides.len(),
+                found: map.len(),
+            });
+        }
+        for (axis, &target) in map.iter().enumerate() {
+            if target >= shape.dimension() {
+                return Err(PolymerError::Target { axis, target });
+            }
+            let length = self.strides[axis].0;
+            let expected = shape.length(target);
+            if length != expected {
+                return Err(PolymerError::Length {
+                    axis,
+                    length,
+                    target,
+                    expected,
+                });
+            }
+        }
+        Ok(self.polymer(shape, map))
+    }
+
     /// Returns another view whose positions are the same but might have
     /// smaller dimension because some axes could be merged.
     pub fn simplify(&self) -> Self {
@@ -239,6 +320,45 @@ impl ShapeView {
     }
 }
 
+/// The reason why a polymer map cannot be applied to a view.
+#[derive(PartialEq, Eq, Debug)]
+pub enum PolymerError {
+    /// The map does not have exactly one entry for each axis.
+    Dimension { expected: usize, found: usize },
+    /// The axis is mapped to a target axis that does not exist.
+    Target { axis: usize, target: usize },
+    /// The axis is mapped to a target axis of a different length.
+    Length {
+        axis: usize,
+        length: usize,
+        target: usize,
+        expected: usize,
+    },
+}
+
+impl std::fmt::Display for PolymerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PolymerError::Dimension { expected, found } => {
+                write!(f, "expected {} axes but found {}", expected, found)
+            }
+            PolymerError::Target { axis, target } => {
+                write!(f, "axis {} is mapped to missing axis {}", axis, target)
+            }
+            PolymerError::Length {
+                axis,
+                length,
+                target,
+                expected,
+            } => write!(
+                f,
+                "axis {} of length {} is mapped to axis {} of length {}",
+                axis, length, target, expected
+            ),
+        }
+    }
+}
+
 /// ShapeView iterator that returns all valid positions, size many in total.
 #[derive(Debug)]
 pub struct PositionIter {
@@ -269,7 +389,9 @@ impl PositionIter {
         }
     }
 
-    /// Resets the iterator to the first element.
+    /// Resets the iterator to the first element. This works at any point of
+    /// the iteration, since the last call of `next` wraps all coordinates
+    /// back to zero and the index back to the offset.
     pub fn reset(&mut self) {
         self.done = false;
         for e in self.entries.iter_mut() {
@@ -341,4 +463,159 @@ mod tests {
         ];
         assert_eq!(pos2, pos3);
     }
+
+    #[test]
+    fn try_new() {
+        let half = 1 << (usize::BITS / 2);
+        assert_eq!(Shape::try_new(vec![half, half], 0), None);
+        let shape = Shape::try_new(vec![half - 1, half + 1], 0).unwrap();
+        assert_eq!(shape.volume(), usize::MAX);
+        assert_eq!(Shape::try_new(vec![half - 1, half + 1], 1), None);
+        assert_eq!(
+            Shape::try_new(vec![half, half - 1, 0], 0).unwrap().volume(),
+            0
+        );
+        let shape = Shape::try_new(vec![half - 1, half], half - 1).unwrap();
+        assert_eq!(shape.volume(), (half - 1) * half);
+        assert_eq!(Shape::try_new(vec![half, half - 1], half), None);
+    }
+
+    #[test]
+    fn try_polymer() {
+        let shape = Shape::new(vec![4, 3], 0);
+        let target = Shape::new(vec![4, 3, 3], 0);
+        let view = shape.view();
+
+        assert_eq!(
+            view.try_polymer(&target, &[0, 2]),
+            Ok(view.polymer(&target, &[0, 2]))
+        );
+        assert_eq!(
+            view.try_polymer(&target, &[1, 2]),
+            Err(PolymerError::Length {
+                axis: 0,
+                length: 4,
+                target: 1,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            view.try_polymer(&target, &[0, 3]),
+            Err(PolymerError::Target { axis: 1, target: 3 })
+        );
+        assert_eq!(
+            view.try_polymer(&target, &[0]),
+            Err(PolymerError::Dimension {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn diagonal() {
+        let shape = Shape::new(vec![3, 3, 3], 0);
+        let view = shape.view();
+
+        let target = Shape::new(vec![3], 0);
+        let pos: Vec<usize> = view.polymer(&target, &[0, 0, 0]).positions().collect();
+        assert_eq!(pos, vec![0, 13, 26]);
+
+        let target = Shape::new(vec![3, 3], 0);
+        for map in [[0, 0, 1], [0, 1, 1], [1, 0, 1]] {
+            let view = view.polymer(&target, &map);
+            let pos1: Vec<usize> = view.positions().collect();
+            let pos2: Vec<usize> = view.simplify().positions().collect();
+            assert_eq!(pos1, pos2);
+
+            let mut pos3 = Vec::new();
+            for x in 0..3 {
+                for y in 0..3 {
+                    let c = [x, y];
+                    pos3.push(shape.position(map.iter().map(|&i| &c[i])));
+                }
+            }
+            assert_eq!(pos1, pos3);
+        }
+    }
+
+    #[test]
+    fn fix() {
+        let shape = Shape::new(vec![2, 3, 4], 5);
+        let view = shape.view().fix(1, 2);
+        assert_eq!(view.dimension(), 2);
+        let pos1: Vec<usize> = view.positions().collect();
+        let mut pos2 = vec![];
+        for i in 0..2 {
+            for k in 0..4 {
+                pos2.push(shape.position([i, 2, k].iter()));
+            }
+        }
+        assert_eq!(pos1, pos2);
+    }
+
+    #[test]
+    fn reshape() {
+        let shape = Shape::new(vec![3, 3, 3], 4);
+        assert_eq!(shape.reshape(vec![3, 8]), None);
+        assert_eq!(shape.reshape(vec![usize::MAX, 3]), None);
+
+        let flat = shape.reshape(vec![27]).unwrap();
+        assert_eq!(flat.positions(), shape.positions());
+        let table = flat.reshape(vec![9, 3]).unwrap();
+        assert_eq!(table.positions(), shape.positions());
+
+        let mut coordinates = [0; 2];
+        for pos in table.positions() {
+            table.coordinates(pos, &mut coordinates);
+            assert_eq!(table.position(coordinates.iter()), pos);
+        }
+        assert_eq!(
+            table.position([5, 1].iter()),
+            shape.position([1, 2, 1].iter())
+        );
+    }
+
+    #[test]
+    fn broadcast() {
+        let shape = Shape::new(vec![3], 2);
+        let view = shape.view();
+
+        let target = Shape::new(vec![4, 3], 0);
+        let view1 = view.broadcast(0, 4);
+        assert_eq!(view1, view.polymer(&target, &[1]));
+        let pos1: Vec<usize> = view1.positions().collect();
+        let pos2: Vec<usize> = view.polymer(&target, &[1]).positions().collect();
+        assert_eq!(pos1, pos2);
+        assert_eq!(pos1, [2, 3, 4, 2, 3, 4, 2, 3, 4, 2, 3, 4]);
+
+        let target = Shape::new(vec![3, 4], 0);
+        let pos1: Vec<usize> = view.broadcast(1, 4).positions().collect();
+        let pos2: Vec<usize> = view.polymer(&target, &[0]).positions().collect();
+        assert_eq!(pos1, pos2);
+    }
+
+    #[test]
+    fn reset() {
+        let shape = Shape::new(vec![2, 3, 4], 7);
+        let mut iter = shape.view().permute(&[2, 0, 1]).positions();
+        let pos1: Vec<usize> = (&mut iter).collect();
+        assert_eq!(pos1.len(), 24);
+
+        iter.reset();
+        let pos2: Vec<usize> = (&mut iter).collect();
+        assert_eq!(pos1, pos2);
+
+        iter.reset();
+        let pos3: Vec<usize> = (&mut iter).take(10).collect();
+        assert_eq!(pos3, pos1[..10]);
+        iter.reset();
+        let pos4: Vec<usize> = (&mut iter).collect();
+        assert_eq!(pos1, pos4);
+
+        let mut iter = shape.view().broadcast(1, 0).positions();
+        assert_eq!(iter.next(), None);
+        iter.reset();
+        assert_eq!(iter.next(), None);
+    }
 }
@@ -15,90 +15,313 @@
 * along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::ops::Range;
+use std::path::Path;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use super::bitops::*;
 use super::buffer::Buffer2;
+use super::model::Model;
 use super::shape::{PositionIter, Shape};
 
+/// Why a cell was assigned. The propagated ones list the cells that
+/// implied the value.
 #[derive(Debug)]
-enum Reason {
+pub enum Reason {
     Initial,
     Decision,
     Clause(Vec<usize>),
-    Exists,
+    Exists(Vec<usize>),
 }
 
 #[derive(Debug)]
 struct Step {
     bvar: usize,
     reason: Reason,
+    level: usize,
 }
 
-#[derive(Debug, Default)]
+/// Selects the cell of the next decision.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Heuristic {
+    /// The first unassigned cell is decided.
+    #[default]
+    FirstUndef,
+    /// The unassigned cell that took part in the most recent conflicts is
+    /// decided, where older conflicts count less and less.
+    Vsids,
+}
+
+/// The factor the activities of the cells decay by at each conflict.
+const ACTIVITY_DECAY: f64 = 0.95;
+
+#[derive(Debug)]
 struct State {
     assignment: Buffer2,
     steps: Vec<Step>,
     levels: Vec<usize>,
+    // The length of the trail when the current speculative pass started.
+    speculative: Option<usize>,
+    // The start positions of the tables and the number of changes in each.
+    tables: Vec<usize>,
+    versions: Vec<u64>,
+    // The length of the trail up to which the watched literals are updated,
+    // and the position of each assigned cell on the trail.
+    watched: usize,
+    positions: Vec<usize>,
+    heuristic: Heuristic,
+    // The activity of each cell and the amount of the next bump.
+    activity: Vec<f64>,
+    increment: f64,
+    // The number of decisions and propagated assignments made so far.
+    decisions: usize,
+    propagations: usize,
+    // The number of instances of clauses visited by propagation.
+    visits: usize,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
 }
 
 impl State {
-    fn create_table(&mut self, domains: &[Rc<Domain>]) -> Shape {
-        let shape = Shape::new(
+    /// Creates an empty state whose trail can hold the given number of
+    /// steps and decision levels without reallocation.
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            assignment: Default::default(),
+            steps: Vec::with_capacity(capacity),
+            levels: Vec::with_capacity(capacity),
+            speculative: None,
+            tables: Vec::new(),
+            watched: 0,
+            positions: Vec::new(),
+            heuristic: Heuristic::FirstUndef,
+            activity: Vec::new(),
+            increment: 1.0,
+            decisions: 0,
+            propagations: 0,
+            visits: 0,
+            versions: Vec::new(),
+        }
+    }
+
+    /// Makes sure that the trail does not need to be reallocated during
+    /// search, since it can never be longer than the number of variables.
+    fn reserve_trail(&mut self) {
+        let len = self.assignment.len();
+        self.steps.reserve(len.saturating_sub(self.steps.len()));
+        self.levels.reserve(len.saturating_sub(self.levels.len()));
+    }
+
+    fn create_table(&mut self, domains: &[Rc<Domain>]) -> Option<Shape> {
+        let shape = Shape::try_new(
             domains.iter().map(|dom| dom.size).collect(),
             self.assignment.len(),
-        );
-        self.assignment.append(shape.volume(), BOOL_UNDEF1);
-        shape
+        )?;
+        self.push_table(shape.volume());
+        Some(shape)
+    }
+
+    /// Appends a new table of unassigned cells and returns its start.
+    fn push_table(&mut self, volume: usize) -> usize {
+        let start = self.assignment.len();
+        self.tables.push(start);
+        self.versions.push(0);
+        self.assignment.append(volume, BOOL_UNDEF1);
+        self.positions.resize(self.assignment.len(), 0);
+        start
+    }
+
+    /// Sets the value of a cell and records the change of its table.
+    fn set(&mut self, pos: usize, val: Bit2) {
+        self.assignment.set(pos, val);
+        let table = self.tables.partition_point(|&start| start <= pos) - 1;
+        self.versions[table] += 1;
+    }
+
+    /// Returns the number of changes made to the table starting at the
+    /// given position.
+    fn version(&self, start: usize) -> u64 {
+        let table = self.tables.partition_point(|&other| other <= start) - 1;
+        self.versions[table]
+    }
+
+    /// Marks all tables changed, since the assignment was replaced.
+    fn touch_all(&mut self) {
+        for version in self.versions.iter_mut() {
+            *version += 1;
+        }
     }
 
-    fn print_table(&self, shape: &Shape) {
+    fn print_table(&self, predicate: &Predicate) {
+        let shape = &predicate.shape;
         let mut cor = vec![0; shape.dimension()];
         for pos in shape.positions() {
             shape.coordinates(pos, &mut cor);
-            let val = BOOL_FORMAT1[self.assignment.get(pos).idx()];
-            println!("  {:?} = {}", cor, val);
+            let val = BOOL_FORMAT1[self.assignment.get(predicate.cell(pos)).idx()];
+            let cor = Domain::format_coordinates(&predicate.domains, &cor);
+            println!("  {} = {}", cor, val);
         }
     }
 
     fn assign(&mut self, pos: usize, sign: bool, reason: Reason) {
         assert!(self.assignment.get(pos) == BOOL_UNDEF1);
-        self.assignment
-            .set(pos, if sign { BOOL_TRUE } else { BOOL_FALSE });
-        self.steps.push(Step { bvar: pos, reason });
+        if !matches!(reason, Reason::Initial) {
+            self.propagations += 1;
+        }
+        self.set(pos, if sign { BOOL_TRUE } else { BOOL_FALSE });
+        self.positions[pos] = self.steps.len();
+        self.steps.push(Step {
+            bvar: pos,
+            reason,
+            level: self.levels.len(),
+        });
+    }
+
+    /// Increases the activity of the cell that took part in a conflict.
+    fn bump(&mut self, pos: usize) {
+        if self.activity.len() < self.assignment.len() {
+            self.activity.resize(self.assignment.len(), 0.0);
+        }
+        self.activity[pos] += self.increment;
+        if self.activity[pos] > 1e100 {
+            for act in self.activity.iter_mut() {
+                *act *= 1e-100;
+            }
+            self.increment *= 1e-100;
+        }
+    }
+
+    /// Makes the earlier bumps count less than the later ones.
+    fn decay(&mut self) {
+        self.increment /= ACTIVITY_DECAY;
+    }
+
+    /// Returns the unassigned cell to decide next.
+    fn pick_decision(&self) -> Option<usize> {
+        let mut undefs =
+            (0..self.assignment.len()).filter(|&i| self.assignment.get(i) == BOOL_UNDEF1);
+        match self.heuristic {
+            Heuristic::FirstUndef => undefs.next(),
+            Heuristic::Vsids => {
+                let activity = |pos: usize| self.activity.get(pos).copied().unwrap_or(0.0);
+                undefs.fold(None, |best: Option<usize>, pos| match best {
+                    Some(best) if activity(best) >= activity(pos) => Some(best),
+                    _ => Some(pos),
+                })
+            }
+        }
     }
 
     fn make_decision(&mut self) -> bool {
-        let pos = (0..self.assignment.len()).find(|&i| self.assignment.get(i) == BOOL_UNDEF1);
+        let pos = self.pick_decision();
         if let Some(pos) = pos {
-            self.levels.push(self.steps.len());
-            self.assignment.set(pos, BOOL_TRUE);
-            self.steps.push(Step {
-                bvar: pos,
-                reason: Reason::Decision,
-            });
+            self.decide(pos);
             true
         } else {
             false
         }
     }
 
+    /// Opens a new decision level by setting the unassigned cell to true.
+    fn decide(&mut self, pos: usize) {
+        assert!(self.assignment.get(pos) == BOOL_UNDEF1);
+        self.decisions += 1;
+        self.levels.push(self.steps.len());
+        self.set(pos, BOOL_TRUE);
+        self.positions[pos] = self.steps.len();
+        self.steps.push(Step {
+            bvar: pos,
+            reason: Reason::Decision,
+            level: self.levels.len(),
+        });
+    }
+
+    /// Starts a speculative pass, whose assignments (including decisions)
+    /// can be undone together with `rollback_speculation` without touching
+    /// the earlier part of the trail.
+    fn begin_speculation(&mut self) {
+        assert!(self.speculative.is_none());
+        self.speculative = Some(self.steps.len());
+    }
+
+    /// Returns the steps made during the current speculative pass.
+    fn speculative_steps(&self) -> &[Step] {
+        match self.speculative {
+            Some(start) => &self.steps[start..],
+            None => &[],
+        }
+    }
+
+    /// Undoes all assignments of the current speculative pass.
+    fn rollback_speculation(&mut self) {
+        let start = self.speculative.take().unwrap();
+        for idx in start..self.steps.len() {
+            self.set(self.steps[idx].bvar, BOOL_UNDEF1);
+        }
+        self.steps.truncate(start);
+        self.watched = self.watched.min(start);
+        while self.levels.last().is_some_and(|&level| level >= start) {
+            self.levels.pop();
+        }
+    }
+
+    /// Ends the current speculative pass keeping its assignments.
+    fn commit_speculation(&mut self) {
+        assert!(self.speculative.take().is_some());
+    }
+
+    /// Undoes all steps above the given decision level at once, so the
+    /// trail ends with the last step of that level.
+    fn backjump(&mut self, level: usize) {
+        if level >= self.levels.len() {
+            return;
+        }
+        self.unwind(self.levels[level]);
+        self.levels.truncate(level);
+    }
+
+    // Resets the assignments of the steps from the given trail position.
+    fn unwind(&mut self, start: usize) {
+        for idx in start..self.steps.len() {
+            let bvar = self.steps[idx].bvar;
+            assert!(self.assignment.get(bvar) != BOOL_UNDEF1);
+            self.set(bvar, BOOL_UNDEF1);
+        }
+        self.steps.truncate(start);
+        self.watched = self.watched.min(start);
+    }
+
+    /// Flips the deepest decision that was not flipped yet. If there is
+    /// none, then all decisions are undone and false is returned.
     fn next_decision(&mut self) -> bool {
+        let base = self.levels.first().copied();
         while let Some(level) = self.levels.pop() {
             let val = self.assignment.get(self.steps[level].bvar);
             if val == BOOL_FALSE {
                 continue;
             }
             assert!(val == BOOL_TRUE);
-            for step in self.steps[level + 1..].iter() {
-                assert!(self.assignment.get(step.bvar) != BOOL_UNDEF1);
-                self.assignment.set(step.bvar, BOOL_UNDEF1);
+            for idx in level + 1..self.steps.len() {
+                let bvar = self.steps[idx].bvar;
+                assert!(self.assignment.get(bvar) != BOOL_UNDEF1);
+                self.set(bvar, BOOL_UNDEF1);
             }
             self.levels.push(level);
-            self.assignment.set(self.steps[level].bvar, BOOL_FALSE);
+            self.set(self.steps[level].bvar, BOOL_FALSE);
             self.steps.truncate(level + 1);
+            self.watched = self.watched.min(level);
             return true;
         }
+        if let Some(base) = base {
+            self.unwind(base);
+        }
         false
     }
 }
@@ -107,11 +330,39 @@ impl State {
 pub struct Domain {
     name: String,
     size: usize,
+    element_names: Option<Box<[String]>>,
 }
 
 impl Domain {
     fn new(name: String, size: usize) -> Self {
-        Self { name, size }
+        Self {
+            name,
+            size,
+            element_names: None,
+        }
+    }
+
+    /// Returns the name of the element, or its index if the elements of the
+    /// domain are not named.
+    pub fn element_name(&self, elem: usize) -> String {
+        match &self.element_names {
+            Some(names) => names[elem].clone(),
+            None => elem.to_string(),
+        }
+    }
+
+    /// Returns the list of the names of the elements at the coordinates.
+    fn format_coordinates(domains: &[Rc<Domain>], coordinates: &[usize]) -> String {
+        let names: Vec<String> = domains
+            .iter()
+            .zip(coordinates)
+            .map(|(dom, &elem)| dom.element_name(elem))
+            .collect();
+        format!("[{}]", names.join(", "))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     pub fn size(&self) -> usize {
@@ -130,18 +381,106 @@ pub struct Predicate {
     shape: Shape,
     name: String,
     domains: Box<[Rc<Domain>]>,
+    // The boolean variable of each position of the shape if several
+    // positions share a cell, as for symmetric relations.
+    cells: Option<Box<[usize]>>,
 }
 
 impl Predicate {
-    fn new(state: &mut State, name: String, domains: Vec<Rc<Domain>>) -> Self {
-        let shape = state.create_table(&domains);
+    fn new(state: &mut State, name: String, domains: Vec<Rc<Domain>>) -> Option<Self> {
+        let shape = state.create_table(&domains)?;
         let domains = domains.into_boxed_slice();
-        Self {
+        Some(Self {
             name,
             domains,
             shape,
+            cells: None,
+        })
+    }
+
+    /// Creates a binary relation on the domain where the positions of
+    /// `(i,j)` and `(j,i)` share the same cell, so only the `i <= j`
+    /// triangle of the table is allocated.
+    fn new_symmetric(state: &mut State, name: String, domain: Rc<Domain>) -> Self {
+        let size = domain.size;
+        let start = state.assignment.len();
+        let shape = Shape::new(vec![size, size], start);
+        let mut cells = vec![0; shape.volume()].into_boxed_slice();
+        let mut next = start;
+        for i in 0..size {
+            for j in i..size {
+                cells[shape.position([i, j].iter()) - start] = next;
+                cells[shape.position([j, i].iter()) - start] = next;
+                next += 1;
+            }
+        }
+        state.push_table(next - start);
+        Self {
+            name,
+            domains: Box::new([domain.clone(), domain]),
+            shape,
+            cells: Some(cells),
+        }
+    }
+
+    /// Returns the boolean variable of the given position of the shape.
+    fn cell(&self, pos: usize) -> usize {
+        match &self.cells {
+            None => pos,
+            Some(cells) => cells[pos - self.shape.positions().start],
+        }
+    }
+
+    /// Returns the boolean variables of the table of the predicate.
+    fn variables(&self) -> Range<usize> {
+        let start = self.shape.positions().start;
+        match &self.cells {
+            None => self.shape.positions(),
+            Some(cells) => start..(cells.iter().max().map_or(start, |&last| last + 1)),
+        }
+    }
+
+    /// Returns the boolean variable of the cell at the given coordinates.
+    pub fn position(&self, coordinates: &[usize]) -> usize {
+        self.cell(self.shape.position(coordinates.iter()))
+    }
+
+    /// Sets the coordinates of the given boolean variable of the table,
+    /// where a shared cell is reported with its smallest coordinates.
+    fn coordinates(&self, bvar: usize, coordinates: &mut [usize]) {
+        match &self.cells {
+            None => self.shape.coordinates(bvar, coordinates),
+            Some(_) => {
+                let size = self.domains[0].size;
+                let mut idx = bvar - self.shape.positions().start;
+                for i in 0..size {
+                    if idx < size - i {
+                        coordinates[0] = i;
+                        coordinates[1] = i + idx;
+                        return;
+                    }
+                    idx -= size - i;
+                }
+                unreachable!();
+            }
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    pub fn arity(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn domains(&self) -> &[Rc<Domain>] {
+        &self.domains
+    }
 }
 
 impl std::fmt::Display for Predicate {
@@ -157,26 +496,47 @@ impl std::fmt::Display for Predicate {
     }
 }
 
+/// An argument of a literal, which is either a variable of the clause or a
+/// fixed element of the domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Term {
+    Var(usize),
+    Const(usize),
+}
+
 #[derive(Debug)]
 struct Literal {
     predicate: Rc<Predicate>,
-    variables: Box<[usize]>,
+    terms: Box<[Term]>,
     positions: PositionIter,
     sign: bool,
 }
 
 impl Literal {
-    fn new(shape: &Shape, sign: bool, predicate: Rc<Predicate>, variables: Vec<usize>) -> Self {
-        let variables = variables.into_boxed_slice();
-        let positions = predicate
-            .shape
-            .view()
-            .polymer(shape, &variables)
+    fn new(shape: &Shape, sign: bool, predicate: Rc<Predicate>, terms: Vec<Term>) -> Self {
+        let terms = terms.into_boxed_slice();
+        let mut view = predicate.shape.view();
+        for (axis, term) in terms.iter().enumerate().rev() {
+            if let &Term::Const(elem) = term {
+                assert!(elem < predicate.domains[axis].size);
+                view = view.fix(axis, elem);
+            }
+        }
+        let variables: Vec<usize> = terms
+            .iter()
+            .filter_map(|term| match term {
+                &Term::Var(var) => Some(var),
+                Term::Const(_) => None,
+            })
+            .collect();
+        let positions = view
+            .try_polymer(shape, &variables)
+            .unwrap_or_else(|err| panic!("invalid literal of {}: {}", predicate.name, err))
             .simplify()
             .positions();
         Literal {
             predicate,
-            variables,
+            terms,
             positions,
             sign,
         }
@@ -184,14 +544,31 @@ impl Literal {
 
     fn evaluate(&mut self, state: &State, target: &mut Buffer2) {
         self.positions.reset();
-        let op = if self.sign { BOOL_OR } else { BOOL_ORNOT };
-        target.apply(op, &state.assignment, &mut self.positions);
+        let table = if self.sign {
+            &BOOL_OR_TABLE
+        } else {
+            &BOOL_ORNOT_TABLE
+        };
+        match &self.predicate.cells {
+            None => target.apply_fast(table, &state.assignment, &mut self.positions),
+            Some(cells) => {
+                let start = self.predicate.shape.positions().start;
+                let mut iter = (&mut self.positions).map(|pos| cells[pos - start]);
+                target.apply_fast(table, &state.assignment, &mut iter);
+            }
+        }
     }
 
     fn position(&self, coordinates: &[usize]) -> usize {
         self.predicate
-            .shape
-            .position(self.variables.iter().map(|&var| &coordinates[var]))
+            .cell(
+                self.predicate
+                    .shape
+                    .position(self.terms.iter().map(|term| match term {
+                        Term::Var(var) => &coordinates[*var],
+                        Term::Const(elem) => elem,
+                    })),
+            )
     }
 }
 
@@ -203,11 +580,14 @@ impl std::fmt::Display for Literal {
             if self.sign { '+' } else { '-' },
             self.predicate.name,
         )?;
-        for (idx, var) in self.variables.iter().enumerate() {
+        for (idx, term) in self.terms.iter().enumerate() {
             if idx != 0 {
                 write!(f, ",")?;
             }
-            write!(f, "x{}", var)?;
+            match term {
+                Term::Var(var) => write!(f, "x{}", var)?,
+                Term::Const(elem) => write!(f, "{}", elem)?,
+            }
         }
         write!(f, ")")
     }
@@ -219,31 +599,158 @@ struct Clause {
     literals: Vec<Literal>,
     shape: Shape,
     buffer: Buffer2,
+    // The positions of the literals for each cell, stored cell by cell.
+    positions: Box<[usize]>,
+    // The versions of the tables of the literals at the last evaluation.
+    stamps: Vec<u64>,
 }
 
 impl Clause {
-    fn new(shape: Shape, domains: Vec<Rc<Domain>>, literals: Vec<Literal>) -> Self {
-        let buffer = Buffer2::new(shape.volume(), BOOL_FALSE);
+    fn new(shape: Shape, domains: Vec<Rc<Domain>>, mut literals: Vec<Literal>) -> Self {
+        let volume = shape.volume();
+        let buffer = Buffer2::new(volume, BOOL_FALSE);
+        let len = literals.len();
+        let mut positions = vec![0; volume * len].into_boxed_slice();
+        for (idx, lit) in literals.iter_mut().enumerate() {
+            lit.positions.reset();
+            for (pos, bvar) in (&mut lit.positions).enumerate() {
+                positions[pos * len + idx] = lit.predicate.cell(bvar);
+            }
+        }
         Self {
             shape,
             domains,
             literals,
             buffer,
+            positions,
+            stamps: Vec::new(),
+        }
+    }
+
+    /// Returns the positions of the literals at the given cell.
+    fn cell(&self, pos: usize) -> &[usize] {
+        let len = self.literals.len();
+        &self.positions[pos * len..(pos + 1) * len]
+    }
+
+    /// Creates the clause from the list of literals, where each literal is
+    /// given by its sign, predicate and the indices of its variables.
+    fn from_literals(literals: Vec<(bool, Rc<Predicate>, Vec<usize>)>) -> Self {
+        Clause::from_terms(
+            literals
+                .into_iter()
+                .map(|(sign, pred, vars)| (sign, pred, vars.into_iter().map(Term::Var).collect()))
+                .collect(),
+        )
+    }
+
+    /// Creates the clause from the list of literals, where each argument
+    /// of a literal is a variable or a constant. The literals are sorted by
+    /// predicate name, sign and arguments, so the same clause always prints
+    /// the same way.
+    fn from_terms(mut literals: Vec<(bool, Rc<Predicate>, Vec<Term>)>) -> Self {
+        literals.sort_by(|(sign1, pred1, terms1), (sign2, pred2, terms2)| {
+            (&pred1.name, sign1, terms1).cmp(&(&pred2.name, sign2, terms2))
+        });
+        let mut domains: Vec<Option<Rc<Domain>>> = Default::default();
+        for (_, pred, terms) in literals.iter() {
+            assert_eq!(pred.domains.len(), terms.len());
+            for (pos, term) in terms.iter().enumerate() {
+                let &Term::Var(idx) = term else {
+                    continue;
+                };
+                if domains.len() <= idx {
+                    domains.resize(idx + 1, None);
+                }
+                let dom1 = &pred.domains[pos];
+                let dom2 = &mut domains[idx];
+                if dom2.is_none() {
+                    *dom2 = Some(dom1.clone());
+                } else {
+                    assert!(Rc::ptr_eq(dom1, dom2.as_ref().unwrap()));
+                }
+            }
         }
+        let domains: Vec<Rc<Domain>> = domains.into_iter().map(|dom| dom.unwrap()).collect();
+
+        let shape = Shape::new(domains.iter().map(|dom| dom.size).collect(), 0);
+        let literals: Vec<Literal> = literals
+            .into_iter()
+            .map(|(sign, pred, terms)| Literal::new(&shape, sign, pred, terms))
+            .collect();
+
+        Clause::new(shape, domains, literals)
     }
 
+    /// Evaluates the instances of the clause, unless none of the tables
+    /// of its literals has changed since the last evaluation.
     fn evaluate(&mut self, state: &State) {
+        let stamps = self
+            .literals
+            .iter()
+            .map(|lit| state.version(lit.predicate.shape.positions().start));
+        if self.stamps.len() == self.literals.len()
+            && self.stamps.iter().copied().eq(stamps.clone())
+        {
+            return;
+        }
+        self.stamps.clear();
+        self.stamps.extend(stamps);
+
         self.buffer.fill(BOOL_FALSE);
         for lit in self.literals.iter_mut() {
             lit.evaluate(state, &mut self.buffer);
         }
     }
 
+    /// Returns the distinct predicates referenced by the literals in the
+    /// order of their first occurrence.
+    fn predicates(&self) -> impl Iterator<Item = &Rc<Predicate>> + '_ {
+        self.literals
+            .iter()
+            .enumerate()
+            .filter(|(idx, lit)| {
+                self.literals[..*idx]
+                    .iter()
+                    .all(|other| !Rc::ptr_eq(&other.predicate, &lit.predicate))
+            })
+            .map(|(_, lit)| &lit.predicate)
+    }
+
+    /// Returns the literals of the clause separated by spaces.
+    fn formula(&self) -> String {
+        let literals: Vec<String> = self.literals.iter().map(|lit| lit.to_string()).collect();
+        literals.join(" ")
+    }
+
+    /// Returns the fraction of the instances that are satisfied. The clause
+    /// must be evaluated before.
+    fn density(&self) -> f64 {
+        let len = self.buffer.len();
+        if len == 0 {
+            return 1.0;
+        }
+        let count = (0..len)
+            .filter(|&pos| self.buffer.get(pos) == BOOL_TRUE)
+            .count();
+        count as f64 / len as f64
+    }
+
     fn get_status(&self) -> Bit2 {
+        self.buffer.fold_and()
+    }
+
+    /// Same as `get_status`, but computed from the assignment without
+    /// evaluating the clause.
+    fn get_current_status(&self, state: &State) -> Bit2 {
         let mut res = BOOL_TRUE;
         for pos in 0..self.buffer.len() {
-            let val = self.buffer.get(pos);
-            res = BOOL_AND.of(res, val);
+            let mut value = BOOL_FALSE;
+            for (lit, &bvar) in self.literals.iter().zip(self.cell(pos)) {
+                let op = if lit.sign { BOOL_OR } else { BOOL_ORNOT };
+                value = op.of(value, state.assignment.get(bvar));
+            }
+            res = BOOL_AND.of(res, value);
         }
         res
     }
@@ -252,7 +759,6 @@ impl Clause {
     // BOOL_UNDEF1 if some propagations were made and the status is unclear,
     // BOOL_TRUE if the clause is universally true, and BOOL_UNDEF2 otherwise.
     fn propagate(&self, state: &mut State) -> Bit2 {
-        let mut coordinates = vec![0; self.shape.dimension()];
         let mut result = BOOL_TRUE;
         for pos in 0..self.buffer.len() {
             let val = self.buffer.get(pos);
@@ -260,12 +766,10 @@ impl Clause {
             if val == BOOL_FALSE {
                 break;
             } else if val == BOOL_UNDEF1 {
-                self.shape.coordinates(pos, &mut coordinates);
                 let mut unit = 0;
                 let mut sign = None;
                 let mut reason = vec![];
-                for lit in self.literals.iter() {
-                    let bvar = lit.position(&coordinates);
+                for (lit, &bvar) in self.literals.iter().zip(self.cell(pos)) {
                     let bval = state.assignment.get(bvar);
                     if bval == BOOL_UNDEF1 {
                         assert!(sign.is_none());
@@ -282,25 +786,49 @@ impl Clause {
             }
         }
 
-        let check = self.get_status();
-        assert!(result == check || result == BOOL_UNDEF1);
+        debug_assert!(result == self.get_status() || result == BOOL_UNDEF1);
         result
     }
 
+    /// Returns the position of the first failed instance.
+    fn failure_position(&self) -> Option<usize> {
+        (0..self.buffer.len()).find(|&pos| self.buffer.get(pos) == BOOL_FALSE)
+    }
+
     fn get_failure(&self) -> Option<Vec<usize>> {
-        for pos in 0..self.buffer.len() {
-            if self.buffer.get(pos) == BOOL_FALSE {
-                let mut coordinates = vec![0; self.shape.dimension()];
-                self.shape.coordinates(pos, &mut coordinates);
-                return Some(
-                    self.literals
-                        .iter()
-                        .map(|lit| lit.position(&coordinates))
-                        .collect(),
-                );
-            }
-        }
-        None
+        self.failure_position().map(|pos| self.cell(pos).to_vec())
+    }
+
+    /// Returns the coordinates of the variables of the first failed instance.
+    fn failure_coordinates(&self) -> Option<Vec<usize>> {
+        self.failure_position().map(|pos| {
+            let mut coordinates = vec![0; self.shape.dimension()];
+            self.shape.coordinates(pos, &mut coordinates);
+            coordinates
+        })
+    }
+
+    /// Returns the literals of the instance at the given coordinates of the
+    /// variables separated by spaces, such as `-mul(1,2,3) +mul(0,5,4)`.
+    fn format_instance(&self, coordinates: &[usize]) -> String {
+        assert_eq!(coordinates.len(), self.domains.len());
+        let literals: Vec<String> = self
+            .literals
+            .iter()
+            .map(|lit| {
+                let args: Vec<String> = lit
+                    .terms
+                    .iter()
+                    .map(|term| match term {
+                        Term::Var(var) => coordinates[*var].to_string(),
+                        Term::Const(elem) => elem.to_string(),
+                    })
+                    .collect();
+                let sign = if lit.sign { '+' } else { '-' };
+                format!("{}{}({})", sign, lit.predicate.name, args.join(","))
+            })
+            .collect();
+        literals.join(" ")
     }
 
     fn print_table(&self) {
@@ -313,485 +841,5425 @@ impl Clause {
     }
 }
 
-impl std::fmt::Display for Clause {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "clause ")?;
-        for (idx, lit) in self.literals.iter().enumerate() {
-            if idx != 0 {
-                write!(f, " ")?;
-            }
-            write!(f, "{}", lit)?;
+/// The literals of an instance of a clause or of a learnt clause, as pairs
+/// of the sign and the cell.
+enum Ground<'a> {
+    Clause(&'a [Literal], &'a [usize]),
+    Learnt(&'a [(bool, usize)]),
+}
+
+impl Ground<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Ground::Clause(literals, _) => literals.len(),
+            Ground::Learnt(literals) => literals.len(),
         }
+    }
 
-        write!(f, " = {}", BOOL_FORMAT2[self.get_status().idx()])
+    fn get(&self, idx: usize) -> (bool, usize) {
+        match self {
+            Ground::Clause(literals, cell) => (literals[idx].sign, cell[idx]),
+            Ground::Learnt(literals) => literals[idx],
+        }
     }
 }
 
-#[derive(Debug)]
-struct Exist {
-    predicate: Rc<Predicate>,
+/// The constraints whose ground instances are watched.
+struct Grounds<'a> {
+    clauses: &'a [Clause],
+    lex_clauses: &'a [Learnt],
+    learnts: &'a [Learnt],
 }
 
-impl Exist {
-    fn new(predicate: Rc<Predicate>) -> Self {
-        Exist { predicate }
-    }
+/// The two watched literals of each instance of the clauses, of the
+/// lex-leader clauses and of the learnt clauses, and for each cell the
+/// instances watching it. An instance is visited only when one of its
+/// watched literals becomes false. A false literal stays watched only if
+/// the other watch is true at a level that is not higher, so the watches
+/// remain valid when the trail is shortened.
+#[derive(Debug, Default)]
+struct Watches {
+    // The first instance of each clause, followed by the first lex-leader
+    // one, which are followed by the learnt ones.
+    offsets: Vec<usize>,
+    // The number of lex-leader and learnt clauses that have instances.
+    lex_clauses: usize,
+    learnts: usize,
+    // The indices of the two watched literals of each instance.
+    pairs: Vec<(usize, usize)>,
+    // The instance and the watch of each watch of each cell.
+    lists: Vec<Vec<(usize, usize)>>,
+    // The instances whose true watch is at a higher level than the false
+    // one, with the trail position and cell of the true watch. They are
+    // checked again once that step is undone.
+    pending: Vec<(usize, usize, usize)>,
+    // The instance found without a true literal at the last check.
+    unsatisfied: usize,
+    // The instances whose watches have to be chosen again.
+    recheck: Vec<usize>,
+    // The instance that failed last.
+    conflict: Option<usize>,
+}
 
-    fn get_status(&self, state: &State) -> Bit2 {
-        let shape = &self.predicate.shape;
-        let range = shape.positions();
-        let block = shape.length(shape.dimension() - 1);
+impl Watches {
+    /// Returns true if the literal is false at the given cell.
+    fn is_false(state: &State, sign: bool, bvar: usize) -> bool {
+        state.assignment.get(bvar) == if sign { BOOL_FALSE } else { BOOL_TRUE }
+    }
 
-        let mut value1 = BOOL_TRUE;
-        let mut pos = range.start;
-        while pos < range.end {
-            let mut value2 = BOOL_FALSE;
-            for i in pos..(pos + block) {
-                value2 = BOOL_OR.of(value2, state.assignment.get(i));
-            }
-            value1 = BOOL_AND.of(value1, value2);
-            pos += block;
-        }
-        value1
+    /// Returns true if the literal is true at the given cell.
+    fn is_true(state: &State, sign: bool, bvar: usize) -> bool {
+        state.assignment.get(bvar) == if sign { BOOL_TRUE } else { BOOL_FALSE }
     }
 
-    // Returns BOOL_FALSE if the clause has failed (maybe with propagations),
-    // BOOL_UNDEF1 if some propagations were made and the status is unclear,
-    // BOOL_TRUE if the clause is universally true, and BOOL_UNDEF2 otherwise.
-    fn propagate(&self, state: &mut State) -> Bit2 {
-        let shape = &self.predicate.shape;
-        let range = shape.positions();
-        let block = shape.length(shape.dimension() - 1);
+    /// Returns the decision level of an assigned cell.
+    fn level(state: &State, bvar: usize) -> usize {
+        state.steps[state.positions[bvar]].level
+    }
 
-        let mut result = BOOL_TRUE;
-        let mut pos = range.start;
-        while pos < range.end {
-            let mut value2 = BOOL_FALSE;
-            let mut unit_pos = None;
-            for i in pos..(pos + block) {
-                let val = state.assignment.get(i);
-                value2 = BOOL_OR.of(value2, val);
-                if val == BOOL_UNDEF1 {
-                    unit_pos = Some(i);
-                }
-            }
-            result = BOOL_AND.of(result, value2);
-            if value2 == BOOL_FALSE {
-                break;
-            } else if value2 == BOOL_UNDEF1 {
-                debug_assert!(unit_pos.is_some());
-                state.assign(unit_pos.unwrap(), true, Reason::Exists);
-            }
-            pos += block;
+    fn ground<'a>(&self, grounds: &Grounds<'a>, inst: usize) -> Ground<'a> {
+        let base = self.offsets[grounds.clauses.len()];
+        let lex = grounds.lex_clauses.len();
+        if inst >= base + lex {
+            Ground::Learnt(&grounds.learnts[inst - base - lex].literals)
+        } else if inst >= base {
+            Ground::Learnt(&grounds.lex_clauses[inst - base].literals)
+        } else {
+            let idx = self.offsets.partition_point(|&start| start <= inst) - 1;
+            let cla = &grounds.clauses[idx];
+            Ground::Clause(&cla.literals, cla.cell(inst - self.offsets[idx]))
         }
-
-        let check = self.get_status(state);
-        assert!(result == check || result == BOOL_UNDEF1);
-        result
     }
 
-    fn get_failure(&self, state: &State) -> Option<usize> {
-        let shape = &self.predicate.shape;
-        let range = shape.positions();
-        let block = shape.length(shape.dimension() - 1);
+    /// Adds a new instance watching its first two literals, which are
+    /// chosen properly when it is checked again.
+    fn add_instance(&mut self, ground: &Ground) {
+        let inst = self.pairs.len();
+        let len = ground.len();
+        let second = len.min(2).saturating_sub(1);
+        self.pairs.push((0, second));
+        if len > 0 {
+            self.lists[ground.get(0).1].push((inst, 0));
+            self.lists[ground.get(second).1].push((inst, 1));
+        }
+        self.recheck.push(inst);
+    }
 
-        let mut pos = range.start;
-        while pos < range.end {
-            let mut value2 = BOOL_FALSE;
-            for i in pos..(pos + block) {
-                value2 = BOOL_OR.of(value2, state.assignment.get(i));
-            }
-            if value2 == BOOL_FALSE {
-                return Some(pos);
+    /// Creates the instances of all clauses, lex-leader and learnt clauses,
+    /// whose watches are chosen for the current assignment when they are
+    /// checked.
+    fn rebuild(&mut self, grounds: &Grounds, state: &State) {
+        *self = Watches::default();
+        self.lists.resize(state.assignment.len(), Vec::new());
+        let mut start = 0;
+        for cla in grounds.clauses.iter() {
+            self.offsets.push(start);
+            start += cla.shape.volume();
+        }
+        self.offsets.push(start);
+        for cla in grounds.clauses.iter() {
+            for pos in 0..cla.shape.volume() {
+                self.add_instance(&Ground::Clause(&cla.literals, cla.cell(pos)));
             }
-            pos += block;
         }
-        None
+        for lrn in grounds.lex_clauses.iter().chain(grounds.learnts.iter()) {
+            self.add_instance(&Ground::Learnt(&lrn.literals));
+        }
+        self.lex_clauses = grounds.lex_clauses.len();
+        self.learnts = grounds.learnts.len();
     }
-}
 
-impl std::fmt::Display for Exist {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "exist {}", self.predicate.name)
+    /// Moves the given watch of the instance to another literal.
+    fn set_watch(&mut self, ground: &Ground, inst: usize, slot: usize, idx: usize) {
+        let pair = &mut self.pairs[inst];
+        let old = if slot == 0 { pair.0 } else { pair.1 };
+        if slot == 0 {
+            pair.0 = idx;
+        } else {
+            pair.1 = idx;
+        }
+        let old = ground.get(old).1;
+        let new = ground.get(idx).1;
+        if old != new {
+            let list = &mut self.lists[old];
+            let pos = list.iter().position(|&w| w == (inst, slot)).unwrap();
+            list.swap_remove(pos);
+            self.lists[new].push((inst, slot));
+        }
     }
-}
-
-#[derive(Debug, Default)]
-pub struct Solver {
-    state: State,
-    domains: Vec<Rc<Domain>>,
-    predicates: Vec<Rc<Predicate>>,
-    clauses: Vec<Clause>,
-    exists: Vec<Exist>,
-}
 
-impl Solver {
-    pub fn add_domain(&mut self, name: String, size: usize) -> Rc<Domain> {
-        assert!(self.domains.iter().all(|dom| dom.name != name));
-        let dom = Rc::new(Domain::new(name, size));
-        self.domains.push(dom.clone());
-        dom
+    /// Records the instance as pending if its true watch at the given cell
+    /// is at a higher level than its false watch, when there is one, since
+    /// backtracking could then undo the former but not the latter.
+    fn check_pending(&mut self, state: &State, inst: usize, bvar: usize, last: Option<usize>) {
+        let level = last.map_or(0, |last| Self::level(state, last));
+        if Self::level(state, bvar) > level {
+            self.pending.push((state.positions[bvar], bvar, inst));
+        }
     }
 
-    pub fn add_variable(&mut self, name: String, domains: Vec<Rc<Domain>>) -> Rc<Predicate> {
-        assert!(self.predicates.iter().all(|pred| pred.name != name));
-        let pred = Rc::new(Predicate::new(&mut self.state, name, domains));
-        self.predicates.push(pred.clone());
-        pred
+    /// Assigns the literal of the instance at the given index, with the
+    /// other literals as the reason, where the other watch is at the given
+    /// false cell.
+    fn assign_unit(
+        &mut self,
+        ground: &Ground,
+        state: &mut State,
+        inst: usize,
+        idx: usize,
+        last: Option<usize>,
+    ) {
+        let reason = (0..ground.len())
+            .filter(|&i| i != idx)
+            .map(|i| ground.get(i).1)
+            .collect();
+        let (sign, bvar) = ground.get(idx);
+        state.assign(bvar, sign, Reason::Clause(reason));
+        self.check_pending(state, inst, bvar, last);
     }
 
-    pub fn add_clause(&mut self, literals: Vec<(bool, Rc<Predicate>, Vec<usize>)>) {
-        let mut domains: Vec<Option<Rc<Domain>>> = Default::default();
-        for (_, pred, indices) in literals.iter() {
-            assert_eq!(pred.domains.len(), indices.len());
-            for (pos, &idx) in indices.iter().enumerate() {
-                if domains.len() <= idx {
-                    domains.resize(idx + 1, None);
+    /// Chooses the watches of an instance again after all steps are
+    /// processed, propagating the last free literal if there is only one.
+    fn revisit(&mut self, grounds: &Grounds, state: &mut State, inst: usize) -> Bit2 {
+        state.visits += 1;
+        let ground = self.ground(grounds, inst);
+        let mut free = (0..ground.len()).filter(|&i| {
+            let (sign, cell) = ground.get(i);
+            !Self::is_false(state, sign, cell)
+        });
+        match (free.next(), free.next()) {
+            (Some(first), Some(second)) => {
+                self.set_watch(&ground, inst, 0, first);
+                self.set_watch(&ground, inst, 1, second);
+                BOOL_UNDEF2
+            }
+            (Some(first), None) => {
+                // the other literals are false, and the last assigned one
+                // is watched
+                let other = (0..ground.len())
+                    .filter(|&i| i != first)
+                    .max_by_key(|&i| state.positions[ground.get(i).1]);
+                self.set_watch(&ground, inst, 0, first);
+                self.set_watch(&ground, inst, 1, other.unwrap_or(first));
+                let last = other.map(|i| ground.get(i).1);
+                let (sign, bvar) = ground.get(first);
+                if Self::is_true(state, sign, bvar) {
+                    self.check_pending(state, inst, bvar, last);
+                    BOOL_UNDEF2
+                } else {
+                    self.assign_unit(&ground, state, inst, first, last);
+                    BOOL_UNDEF1
                 }
-                let dom1 = &pred.domains[pos];
-                let dom2 = &mut domains[idx];
-                if dom2.is_none() {
+            }
+            _ => {
+                self.conflict = Some(inst);
+                BOOL_FALSE
+            }
+        }
+    }
+
+    /// Visits the instances watching the cell of a newly processed step,
+    /// and moves the watches that became false.
+    fn process(&mut self, grounds: &Grounds, state: &mut State, bvar: usize) -> Bit2 {
+        let watching = std::mem::take(&mut self.lists[bvar]);
+        let mut kept = Vec::with_capacity(watching.len());
+        let mut result = BOOL_UNDEF2;
+        for (inst, slot) in watching {
+            if result == BOOL_FALSE {
+                kept.push((inst, slot));
+                self.recheck.push(inst);
+                continue;
+            }
+            state.visits += 1;
+            let ground = self.ground(grounds, inst);
+            let (first, second) = self.pairs[inst];
+            let (this, other) = if slot == 0 {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            let (sign, cell) = ground.get(this);
+            debug_assert_eq!(cell, bvar);
+            if !Self::is_false(state, sign, cell) {
+                kept.push((inst, slot));
+                continue;
+            }
+            let (other_sign, other_cell) = ground.get(other);
+            let satisfied = other != this
+                && Self::is_true(state, other_sign, other_cell)
+                && Self::level(state, other_cell) <= Self::level(state, bvar);
+            if satisfied {
+                kept.push((inst, slot));
+                continue;
+            }
+
+            let replacement = (0..ground.len()).find(|&i| {
+                let (sign, cell) = ground.get(i);
+                i != this && i != other && !Self::is_false(state, sign, cell)
+            });
+            if let Some(next) = replacement {
+                if slot == 0 {
+                    self.pairs[inst].0 = next;
+                } else {
+                    self.pairs[inst].1 = next;
+                }
+                let cell = ground.get(next).1;
+                if cell == bvar {
+                    kept.push((inst, slot));
+                } else {
+                    self.lists[cell].push((inst, slot));
+                }
+                continue;
+            }
+
+            kept.push((inst, slot));
+            if other == this || Self::is_false(state, other_sign, other_cell) {
+                self.conflict = Some(inst);
+                result = BOOL_FALSE;
+            } else if state.assignment.get(other_cell) == BOOL_UNDEF1 {
+                self.assign_unit(&ground, state, inst, other, Some(bvar));
+                result = BOOL_UNDEF1;
+            } else {
+                self.check_pending(state, inst, other_cell, Some(bvar));
+            }
+        }
+        self.lists[bvar] = kept;
+        result
+    }
+
+    /// Brings the instances up to date with the clauses, the learnt clauses
+    /// and the trail, which could have been shortened since the last call.
+    fn update(&mut self, grounds: &Grounds, state: &State) {
+        let learnts = grounds.learnts;
+        if self.offsets.len() != grounds.clauses.len() + 1
+            || self.lists.len() != state.assignment.len()
+            || self.lex_clauses != grounds.lex_clauses.len()
+            || self.learnts > learnts.len()
+        {
+            self.rebuild(grounds, state);
+        }
+        let steps = &state.steps;
+        let recheck = &mut self.recheck;
+        self.pending.retain(|&(pos, bvar, inst)| {
+            let kept = pos < steps.len() && steps[pos].bvar == bvar;
+            if !kept {
+                recheck.push(inst);
+            }
+            kept
+        });
+        for lrn in learnts[self.learnts..].iter() {
+            self.add_instance(&Ground::Learnt(&lrn.literals));
+        }
+        self.learnts = learnts.len();
+    }
+
+    /// Returns the cells of the failed instance, if it is still false.
+    fn failure(&self, grounds: &Grounds, state: &State) -> Option<Vec<usize>> {
+        let inst = self.conflict?;
+        let ground = self.ground(grounds, inst);
+        let cells: Vec<usize> = (0..ground.len()).map(|i| ground.get(i).1).collect();
+        let failed = (0..ground.len()).all(|i| {
+            let (sign, cell) = ground.get(i);
+            Self::is_false(state, sign, cell)
+        });
+        if failed {
+            Some(cells)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if all instances have a true literal, which holds at a
+    /// complete trail without conflicts. Otherwise the instances are checked
+    /// starting from the one found unsatisfied last time, which usually is
+    /// still the case.
+    fn is_satisfied(&mut self, grounds: &Grounds, state: &mut State) -> bool {
+        if state.steps.len() == state.assignment.len() {
+            return true;
+        }
+        let len = self.pairs.len();
+        for step in 0..len {
+            let inst = (self.unsatisfied + step) % len;
+            state.visits += 1;
+            let ground = self.ground(grounds, inst);
+            let satisfied = (0..ground.len()).any(|i| {
+                let (sign, cell) = ground.get(i);
+                Self::is_true(state, sign, cell)
+            });
+            if !satisfied {
+                self.unsatisfied = inst;
+                return false;
+            }
+        }
+        true
+    }
+
+    // Processes the trail from the last update. Returns BOOL_FALSE if an
+    // instance has failed, BOOL_UNDEF1 if some propagations were made,
+    // BOOL_TRUE if all instances are true, and BOOL_UNDEF2 otherwise.
+    fn propagate(&mut self, grounds: &Grounds, state: &mut State) -> Bit2 {
+        self.update(grounds, state);
+        if self.conflict.is_some() {
+            if self.failure(grounds, state).is_some() {
+                return BOOL_FALSE;
+            }
+            self.conflict = None;
+        }
+
+        let mut result = BOOL_UNDEF2;
+        loop {
+            let val = if state.watched < state.steps.len() {
+                let bvar = state.steps[state.watched].bvar;
+                state.watched += 1;
+                self.process(grounds, state, bvar)
+            } else if let Some(inst) = self.recheck.pop() {
+                self.revisit(grounds, state, inst)
+            } else {
+                break;
+            };
+            if val == BOOL_FALSE {
+                return BOOL_FALSE;
+            } else if val == BOOL_UNDEF1 {
+                result = BOOL_UNDEF1;
+            }
+        }
+        if result == BOOL_UNDEF2 && self.is_satisfied(grounds, state) {
+            BOOL_TRUE
+        } else {
+            result
+        }
+    }
+}
+
+impl std::fmt::Display for Clause {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "clause ")?;
+        for (idx, lit) in self.literals.iter().enumerate() {
+            if idx != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", lit)?;
+        }
+
+        write!(f, " = {}", BOOL_FORMAT2[self.get_status().idx()])
+    }
+}
+
+/// The positions of the cells of a predicate grouped into blocks along an
+/// axis, where each block has fixed values of the other coordinates.
+#[derive(Debug)]
+struct AxisBlocks {
+    positions: Box<[usize]>,
+    block: usize,
+}
+
+impl AxisBlocks {
+    fn new(predicate: &Predicate, axis: usize) -> Self {
+        let shape = &predicate.shape;
+        let last = shape.dimension() - 1;
+        assert!(axis <= last);
+        let block = shape.length(axis);
+        let positions = shape
+            .view()
+            .swap(axis, last)
+            .positions()
+            .map(|pos| predicate.cell(pos))
+            .collect();
+        AxisBlocks { positions, block }
+    }
+
+    fn iter(&self) -> std::slice::Chunks<'_, usize> {
+        self.positions.chunks(self.block.max(1))
+    }
+}
+
+#[derive(Debug)]
+struct Exist {
+    predicate: Rc<Predicate>,
+    axis: usize,
+    blocks: AxisBlocks,
+}
+
+impl Exist {
+    /// Requires that for all values of the other axes some cell along the
+    /// given axis is true.
+    fn new(predicate: Rc<Predicate>, axis: usize) -> Self {
+        let blocks = AxisBlocks::new(&predicate, axis);
+        Exist {
+            predicate,
+            axis,
+            blocks,
+        }
+    }
+
+    /// Returns the positions of the cells of each block, one of which must
+    /// be true.
+    fn blocks(&self) -> std::slice::Chunks<'_, usize> {
+        self.blocks.iter()
+    }
+
+    fn get_value(&self, state: &State, block: &[usize]) -> Bit2 {
+        let mut value = BOOL_FALSE;
+        for &pos in block.iter() {
+            value = BOOL_OR.of(value, state.assignment.get(pos));
+        }
+        value
+    }
+
+    fn get_status(&self, state: &State) -> Bit2 {
+        let mut value = BOOL_TRUE;
+        for block in self.blocks() {
+            value = BOOL_AND.of(value, self.get_value(state, block));
+        }
+        value
+    }
+
+    // Returns BOOL_FALSE if the clause has failed (maybe with propagations),
+    // BOOL_UNDEF1 if some propagations were made and the status is unclear,
+    // BOOL_TRUE if the clause is universally true, and BOOL_UNDEF2 otherwise.
+    fn propagate(&self, state: &mut State) -> Bit2 {
+        let mut result = BOOL_TRUE;
+        for block in self.blocks() {
+            let value = self.get_value(state, block);
+            result = BOOL_AND.of(result, value);
+            if value == BOOL_FALSE {
+                break;
+            } else if value == BOOL_UNDEF1 {
+                let mut unit = None;
+                let mut reason = vec![];
+                for &pos in block.iter() {
+                    if state.assignment.get(pos) == BOOL_UNDEF1 {
+                        unit = Some(pos);
+                    } else {
+                        reason.push(pos);
+                    }
+                }
+                state.assign(unit.unwrap(), true, Reason::Exists(reason));
+            }
+        }
+
+        debug_assert!(result == self.get_status(state) || result == BOOL_UNDEF1);
+        result
+    }
+
+    // Returns the first cell of the first failed block.
+    fn get_failure(&self, state: &State) -> Option<usize> {
+        self.blocks()
+            .find(|block| self.get_value(state, block) == BOOL_FALSE)
+            .map(|block| block[0])
+    }
+}
+
+impl std::fmt::Display for Exist {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "exist {}", self.predicate.name)?;
+        if self.axis + 1 != self.predicate.arity() {
+            write!(f, " along {}", self.axis)?;
+        }
+        Ok(())
+    }
+}
+
+/// Requires that exactly one cell is true in each block along the result
+/// axis, so the predicate is the graph of a function.
+#[derive(Debug)]
+struct Function {
+    predicate: Rc<Predicate>,
+    axis: usize,
+    blocks: AxisBlocks,
+}
+
+impl Function {
+    fn new(predicate: Rc<Predicate>, axis: usize) -> Self {
+        let blocks = AxisBlocks::new(&predicate, axis);
+        Function {
+            predicate,
+            axis,
+            blocks,
+        }
+    }
+
+    fn blocks(&self) -> std::slice::Chunks<'_, usize> {
+        self.blocks.iter()
+    }
+
+    // Returns BOOL_TRUE if the block has exactly one true cell and all
+    // others are false, BOOL_FALSE if it has no true and undefined cells or
+    // at least two true cells, BOOL_UNDEF1 if the other cells follow and
+    // BOOL_UNDEF2 otherwise.
+    fn get_value(&self, state: &State, block: &[usize]) -> Bit2 {
+        let mut trues = 0;
+        let mut undefs = 0;
+        for &pos in block.iter() {
+            let val = state.assignment.get(pos);
+            if val == BOOL_TRUE {
+                trues += 1;
+            } else if val == BOOL_UNDEF1 {
+                undefs += 1;
+            }
+        }
+        match (trues, undefs) {
+            (0, 0) => BOOL_FALSE,
+            (0, 1) => BOOL_UNDEF1,
+            (0, _) => BOOL_UNDEF2,
+            (1, 0) => BOOL_TRUE,
+            (1, _) => BOOL_UNDEF1,
+            _ => BOOL_FALSE,
+        }
+    }
+
+    fn get_status(&self, state: &State) -> Bit2 {
+        let mut value = BOOL_TRUE;
+        for block in self.blocks() {
+            value = BOOL_AND.of(value, self.get_value(state, block));
+        }
+        value
+    }
+
+    // Returns BOOL_FALSE if the function has failed (maybe with
+    // propagations), BOOL_UNDEF1 if some propagations were made and the
+    // status is unclear, BOOL_TRUE if the function is universally true, and
+    // BOOL_UNDEF2 otherwise.
+    fn propagate(&self, state: &mut State) -> Bit2 {
+        let mut result = BOOL_TRUE;
+        for block in self.blocks() {
+            let value = self.get_value(state, block);
+            result = BOOL_AND.of(result, value);
+            if value == BOOL_FALSE {
+                break;
+            } else if value == BOOL_UNDEF1 {
+                let result = block
+                    .iter()
+                    .copied()
+                    .find(|&pos| state.assignment.get(pos) == BOOL_TRUE);
+                if let Some(result) = result {
+                    for &pos in block.iter() {
+                        if state.assignment.get(pos) == BOOL_UNDEF1 {
+                            state.assign(pos, false, Reason::Clause(vec![result]));
+                        }
+                    }
+                } else {
+                    let mut unit = None;
+                    let mut reason = vec![];
+                    for &pos in block.iter() {
+                        if state.assignment.get(pos) == BOOL_UNDEF1 {
+                            unit = Some(pos);
+                        } else {
+                            reason.push(pos);
+                        }
+                    }
+                    state.assign(unit.unwrap(), true, Reason::Exists(reason));
+                }
+            }
+        }
+
+        debug_assert!(result == self.get_status(state) || result == BOOL_UNDEF1);
+        result
+    }
+
+    // Returns the two true cells of the first failed block, or all of its
+    // cells if none of them is true.
+    fn get_failure(&self, state: &State) -> Option<Vec<usize>> {
+        let block = self
+            .blocks()
+            .find(|block| self.get_value(state, block) == BOOL_FALSE)?;
+        let trues: Vec<usize> = block
+            .iter()
+            .copied()
+            .filter(|&pos| state.assignment.get(pos) == BOOL_TRUE)
+            .take(2)
+            .collect();
+        if trues.is_empty() {
+            Some(block.to_vec())
+        } else {
+            Some(trues)
+        }
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "function {} along {}", self.predicate.name, self.axis)
+    }
+}
+
+#[derive(Debug)]
+struct Parity {
+    predicate: Rc<Predicate>,
+    axis: usize,
+    even: bool,
+    blocks: AxisBlocks,
+}
+
+impl Parity {
+    fn new(predicate: Rc<Predicate>, axis: usize, even: bool) -> Self {
+        let blocks = AxisBlocks::new(&predicate, axis);
+        Parity {
+            predicate,
+            axis,
+            even,
+            blocks,
+        }
+    }
+
+    fn blocks(&self) -> std::slice::Chunks<'_, usize> {
+        self.blocks.iter()
+    }
+
+    // Returns BOOL_TRUE if the block has the right parity, BOOL_FALSE if
+    // it has the wrong one, BOOL_UNDEF1 if exactly one cell is undefined
+    // and BOOL_UNDEF2 otherwise.
+    fn get_value(&self, state: &State, block: &[usize]) -> Bit2 {
+        let mut value = if self.even { BOOL_TRUE } else { BOOL_FALSE };
+        for &pos in block.iter() {
+            value = BOOL_XOR.of(value, state.assignment.get(pos));
+        }
+        value
+    }
+
+    fn get_status(&self, state: &State) -> Bit2 {
+        let mut value = BOOL_TRUE;
+        for block in self.blocks() {
+            value = BOOL_AND.of(value, self.get_value(state, block));
+        }
+        value
+    }
+
+    // Returns BOOL_FALSE if the parity has failed (maybe with propagations),
+    // BOOL_UNDEF1 if some propagations were made and the status is unclear,
+    // BOOL_TRUE if the parity is universally true, and BOOL_UNDEF2 otherwise.
+    fn propagate(&self, state: &mut State) -> Bit2 {
+        let mut result = BOOL_TRUE;
+        for block in self.blocks() {
+            let value = self.get_value(state, block);
+            result = BOOL_AND.of(result, value);
+            if value == BOOL_FALSE {
+                break;
+            } else if value == BOOL_UNDEF1 {
+                let mut parity = !self.even;
+                let mut unit = None;
+                let mut reason = vec![];
+                for &pos in block.iter() {
+                    let val = state.assignment.get(pos);
+                    if val == BOOL_UNDEF1 {
+                        unit = Some(pos);
+                    } else {
+                        parity ^= val == BOOL_TRUE;
+                        reason.push(pos);
+                    }
+                }
+                state.assign(unit.unwrap(), parity, Reason::Clause(reason));
+            }
+        }
+
+        debug_assert!(result == self.get_status(state) || result == BOOL_UNDEF1);
+        result
+    }
+
+    fn get_failure(&self, state: &State) -> Option<usize> {
+        self.blocks()
+            .find(|block| self.get_value(state, block) == BOOL_FALSE)
+            .map(|block| block[0])
+    }
+}
+
+impl std::fmt::Display for Parity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "parity {} {} along {}",
+            if self.even { "even" } else { "odd" },
+            self.predicate.name,
+            self.axis
+        )
+    }
+}
+
+/// A failed ground instance of a clause with its literals decoded into
+/// their sign, predicate name and coordinates.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Failure {
+    pub clause: String,
+    pub literals: Vec<(bool, String, Vec<usize>)>,
+}
+
+/// A constraint of the solver returned by `unsat_core`, given by its index
+/// among the constraints of the same kind in the order they were added.
+/// The clauses satisfied when added are not kept, so the clause indices
+/// follow the order of `clause_density`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClauseRef {
+    Clause(usize),
+    Exist(usize),
+    Function(usize),
+    Parity(usize),
+}
+
+/// The reason why the theory is contradictory before any decision is made.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Conflict {
+    /// Some initial facts assign opposite values to the same cell.
+    Initial,
+    /// A ground instance of a clause is false.
+    Clause(Failure),
+    /// A block of an exist, function or parity constraint, or a lex-leader
+    /// clause of the symmetry witness, is violated.
+    Constraint(String),
+    /// A clause added by `block_model` or loaded from a checkpoint is false.
+    /// The literals are decoded as in `Failure`.
+    Learnt(Vec<(bool, String, Vec<usize>)>),
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Conflict::Initial => write!(f, "the initial facts are contradictory"),
+            Conflict::Clause(failure) => {
+                write!(f, "the clause {} fails at", failure.clause)?;
+                for (sign, name, coordinates) in failure.literals.iter() {
+                    let sign = if *sign { '+' } else { '-' };
+                    write!(f, " {}{}{:?}", sign, name, coordinates)?;
+                }
+                Ok(())
+            }
+            Conflict::Constraint(constraint) => write!(f, "the {} fails", constraint),
+            Conflict::Learnt(literals) => {
+                write!(f, "the learnt clause fails at")?;
+                for (sign, name, coordinates) in literals.iter() {
+                    let sign = if *sign { '+' } else { '-' };
+                    write!(f, " {}{}{:?}", sign, name, coordinates)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The value of a single cell of a predicate table, where `Missing` marks
+/// the cells of partial operations that are deliberately left undefined,
+/// while `Undef` ones are not assigned yet.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Cell {
+    True,
+    False,
+    Undef,
+    Missing,
+}
+
+impl From<Bit2> for Cell {
+    fn from(val: Bit2) -> Self {
+        match val {
+            BOOL_TRUE => Cell::True,
+            BOOL_FALSE => Cell::False,
+            BOOL_UNDEF1 => Cell::Undef,
+            _ => Cell::Missing,
+        }
+    }
+}
+
+impl std::fmt::Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let c = match self {
+            Cell::True => '1',
+            Cell::False => '0',
+            Cell::Undef => '?',
+            Cell::Missing => 'x',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+/// The reason why a value cannot be assigned to a predicate.
+#[derive(PartialEq, Eq, Debug)]
+pub enum AssignError {
+    /// The number of coordinates does not match the arity.
+    Arity { expected: usize, found: usize },
+    /// The coordinate is outside of the domain of the axis.
+    Range {
+        axis: usize,
+        coordinate: usize,
+        length: usize,
+    },
+    /// The cell already has the opposite value.
+    Conflict { value: bool },
+    /// The same coordinates already appear in an earlier entry.
+    Duplicate { entry: usize },
+}
+
+impl std::fmt::Display for AssignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AssignError::Arity { expected, found } => {
+                write!(f, "expected {} coordinates but found {}", expected, found)
+            }
+            AssignError::Range {
+                axis,
+                coordinate,
+                length,
+            } => write!(
+                f,
+                "coordinate {} of axis {} is not less than {}",
+                coordinate, axis, length
+            ),
+            AssignError::Conflict { value } => write!(f, "the cell is already {}", value),
+            AssignError::Duplicate { entry } => {
+                write!(f, "the cell is already given in entry {}", entry)
+            }
+        }
+    }
+}
+
+/// The reason why a model of an external SAT solver cannot be imported.
+#[derive(PartialEq, Eq, Debug)]
+pub enum ImportError {
+    /// The DIMACS variable does not correspond to a cell.
+    Range { index: i32, variables: usize },
+    /// The cell already has the opposite value.
+    Conflict { index: i32 },
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::Range { index, variables } => write!(
+                f,
+                "variable {} is not between 1 and {}",
+                index.unsigned_abs(),
+                variables
+            ),
+            ImportError::Conflict { index } => {
+                write!(f, "literal {} conflicts with the current value", index)
+            }
+        }
+    }
+}
+
+/// A ground clause learned during search or added by `block_model` and the
+/// symmetry breaking. Each literal is a boolean variable with the sign it
+/// must have to satisfy the clause.
+#[derive(Debug)]
+struct Learnt {
+    literals: Box<[(bool, usize)]>,
+}
+
+impl Learnt {
+    fn get_status(&self, state: &State) -> Bit2 {
+        let mut value = BOOL_FALSE;
+        for &(sign, bvar) in self.literals.iter() {
+            let op = if sign { BOOL_OR } else { BOOL_ORNOT };
+            value = op.of(value, state.assignment.get(bvar));
+        }
+        value
+    }
+
+    // Returns BOOL_FALSE if the clause has failed, BOOL_UNDEF1 if the last
+    // literal was propagated, BOOL_TRUE if the clause is true, and
+    // BOOL_UNDEF2 otherwise.
+    fn propagate(&self, state: &mut State) -> Bit2 {
+        let value = self.get_status(state);
+        if value == BOOL_UNDEF1 {
+            let mut unit = None;
+            let mut reason = vec![];
+            for &(sign, bvar) in self.literals.iter() {
+                if state.assignment.get(bvar) == BOOL_UNDEF1 {
+                    unit = Some((sign, bvar));
+                } else {
+                    reason.push(bvar);
+                }
+            }
+            let (sign, bvar) = unit.unwrap();
+            state.assign(bvar, sign, Reason::Clause(reason));
+        }
+        value
+    }
+
+    fn get_failure(&self, state: &State) -> Option<Vec<usize>> {
+        if self.get_status(state) == BOOL_FALSE {
+            Some(self.literals.iter().map(|&(_, bvar)| bvar).collect())
+        } else {
+            None
+        }
+    }
+}
+
+/// Controls what `search_all` prints while searching. Each level prints
+/// everything the previous ones do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Nothing is printed during the search.
+    #[default]
+    Silent,
+    /// The statistics are printed at the end of the search.
+    Summary,
+    /// Every solution is printed.
+    Solutions,
+    /// The state at every learning and exists deadend is printed as well.
+    Trace,
+}
+
+impl From<u8> for Verbosity {
+    /// Converts the levels 0 to 3, higher levels are the same as 3.
+    fn from(level: u8) -> Self {
+        match level {
+            0 => Verbosity::Silent,
+            1 => Verbosity::Summary,
+            2 => Verbosity::Solutions,
+            _ => Verbosity::Trace,
+        }
+    }
+}
+
+/// The number of steps between two checks of the cancellation flag.
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+/// Appends the string as a JSON string literal.
+fn push_json_string(out: &mut String, text: &str) {
+    out.push('"');
+    for chr in text.chars() {
+        match chr {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            chr if (chr as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => out.push(chr),
+        }
+    }
+    out.push('"');
+}
+
+const CHECKPOINT_MAGIC: &[u8] = b"relsat2\0";
+
+fn push_u64(bytes: &mut Vec<u8>, val: usize) {
+    bytes.extend_from_slice(&(val as u64).to_le_bytes());
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(invalid_data("unexpected end of checkpoint"));
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+fn read_u64(input: &mut &[u8]) -> io::Result<usize> {
+    let bytes = read_bytes(input, 8)?;
+    usize::try_from(u64::from_le_bytes(bytes.try_into().unwrap()))
+        .map_err(|_| invalid_data("value out of range"))
+}
+
+/// The iterator returned by `Solver::solutions`.
+pub struct Solutions<'a> {
+    solver: &'a mut Solver,
+}
+
+impl Iterator for Solutions<'_> {
+    type Item = Vec<(String, Vec<usize>, bool)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.solver.next_model() {
+            Some(self.solver.assigned_cells())
+        } else {
+            None
+        }
+    }
+}
+
+/// Counters collected during search.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SearchStats {
+    pub solutions: usize,
+    pub learnings: usize,
+    pub deadends: usize,
+    /// The longest trail seen during the search.
+    pub peak_trail: usize,
+    /// The deepest decision level seen during the search.
+    pub max_level: usize,
+    /// The number of restarts made by the restart policy.
+    pub restarts: u64,
+    /// The number of clauses learned by conflict analysis.
+    pub learnt_clauses: usize,
+    /// The number of conflicts where a function constraint failed.
+    pub function_conflicts: usize,
+    /// The number of decisions made by the search.
+    pub decisions: usize,
+    /// The number of values derived by propagation.
+    pub propagations: usize,
+    /// The number of times propagation looked at an instance of a clause.
+    pub clause_visits: usize,
+}
+
+impl SearchStats {
+    // Adds the counters of a later search to these ones.
+    fn merge(&mut self, other: &SearchStats) {
+        self.solutions += other.solutions;
+        self.learnings += other.learnings;
+        self.deadends += other.deadends;
+        self.peak_trail = self.peak_trail.max(other.peak_trail);
+        self.max_level = self.max_level.max(other.max_level);
+        self.restarts += other.restarts;
+        self.learnt_clauses += other.learnt_clauses;
+        self.function_conflicts += other.function_conflicts;
+        self.decisions += other.decisions;
+        self.propagations += other.propagations;
+        self.clause_visits += other.clause_visits;
+    }
+}
+
+/// Restarts the search after a number of conflicts that follows the Luby
+/// sequence 1, 1, 2, 1, 1, 2, 4, ... multiplied by the given unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    pub luby_unit: u64,
+}
+
+impl RestartPolicy {
+    /// Returns the number of conflicts allowed before the given restart,
+    /// where the first restart has index zero.
+    fn budget(&self, restart: u64) -> u64 {
+        let mut index = restart + 1;
+        loop {
+            let size = u64::BITS - index.leading_zeros();
+            if index == (1 << size) - 1 {
+                return self.luby_unit * (1 << (size - 1));
+            }
+            index -= (1 << (size - 1)) - 1;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Solver {
+    state: State,
+    domains: Vec<Rc<Domain>>,
+    predicates: Vec<Rc<Predicate>>,
+    clauses: Vec<Clause>,
+    soft_clauses: Vec<(Clause, u64)>,
+    exists: Vec<Exist>,
+    functions: Vec<Function>,
+    parities: Vec<Parity>,
+    // The lex-leader clauses of the symmetry witness, which are kept apart
+    // from the learnt clauses, and the first of their auxiliary cells.
+    lex_clauses: Vec<Learnt>,
+    lex_cells: usize,
+    learnts: Vec<Learnt>,
+    watches: Watches,
+    // The clauses are evaluated in full at each propagation.
+    dense: bool,
+    restart_policy: Option<RestartPolicy>,
+    exist_learning: bool,
+    // The enumeration stopped at a model that was already reported.
+    at_model: bool,
+    symmetry: Option<Rc<Predicate>>,
+    contradiction: bool,
+    // The reason of the contradiction found by `preprocess`.
+    conflict: Option<Conflict>,
+    // The cells of the instances that took part in the recorded conflicts,
+    // and whether `search_all` finished without models.
+    core_tracking: bool,
+    core: HashSet<Box<[usize]>>,
+    refuted: bool,
+    verbosity: Verbosity,
+    // The totals of all searches and the conflicts since the last restart.
+    stats: SearchStats,
+    conflicts: u64,
+}
+
+impl Solver {
+    pub fn add_domain(&mut self, name: String, size: usize) -> Rc<Domain> {
+        assert!(self.domains.iter().all(|dom| dom.name != name));
+        let dom = Rc::new(Domain::new(name, size));
+        self.domains.push(dom.clone());
+        dom
+    }
+
+    /// Same as `add_domain`, but the elements have the given distinct names,
+    /// which are used when printing the tables and variables.
+    pub fn add_named_domain(&mut self, name: String, elements: Vec<String>) -> Rc<Domain> {
+        assert!(self.domains.iter().all(|dom| dom.name != name));
+        for (idx, elem) in elements.iter().enumerate() {
+            assert!(elements[..idx].iter().all(|other| other != elem));
+        }
+        let mut dom = Domain::new(name, elements.len());
+        dom.element_names = Some(elements.into_boxed_slice());
+        let dom = Rc::new(dom);
+        self.domains.push(dom.clone());
+        dom
+    }
+
+    /// Returns the names and sizes of the domains in the order they were
+    /// added.
+    pub fn domains(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.domains.iter().map(|dom| (dom.name(), dom.size()))
+    }
+
+    /// Returns the names and domains of the predicates in the order they
+    /// were added.
+    pub fn predicates(&self) -> impl Iterator<Item = (&str, &[Rc<Domain>])> + '_ {
+        self.predicates
+            .iter()
+            .map(|pred| (pred.name(), pred.domains()))
+    }
+
+    /// Returns the predicate with the given name, if there is one.
+    pub fn predicate_by_name(&self, name: &str) -> Option<&Rc<Predicate>> {
+        self.predicates.iter().find(|pred| pred.name == name)
+    }
+
+    pub fn add_variable(&mut self, name: String, domains: Vec<Rc<Domain>>) -> Rc<Predicate> {
+        self.try_add_variable(name, domains)
+            .expect("predicate table is too large")
+    }
+
+    /// Same as `add_variable`, but returns `None` if the table of the
+    /// predicate would not fit into the address space.
+    pub fn try_add_variable(
+        &mut self,
+        name: String,
+        domains: Vec<Rc<Domain>>,
+    ) -> Option<Rc<Predicate>> {
+        assert!(self.predicates.iter().all(|pred| pred.name != name));
+        let pred = Rc::new(Predicate::new(&mut self.state, name, domains)?);
+        self.predicates.push(pred.clone());
+        Some(pred)
+    }
+
+    /// Adds a binary relation on the given domain that is always symmetric.
+    /// Only the `i <= j` triangle of its table is allocated, and both
+    /// `r(i,j)` and `r(j,i)` denote the same boolean variable, so no clauses
+    /// are needed to keep the two halves equal.
+    pub fn add_symmetric_relation(&mut self, name: String, domain: Rc<Domain>) -> Rc<Predicate> {
+        assert!(self.predicates.iter().all(|pred| pred.name != name));
+        let pred = Rc::new(Predicate::new_symmetric(&mut self.state, name, domain));
+        self.predicates.push(pred.clone());
+        pred
+    }
+
+    /// Adds a new universally quantified clause and returns the number of its
+    /// ground instances that are already satisfied by the level 0 facts (e.g.
+    /// by `set_equality`). If all instances are satisfied, then the clause is
+    /// not stored at all. Clauses are kept as dense tables, so a partially
+    /// satisfied clause is still stored with all of its instances.
+    pub fn add_clause(&mut self, literals: Vec<(bool, Rc<Predicate>, Vec<usize>)>) -> usize {
+        self.push_clause(Clause::from_literals(literals))
+    }
+
+    /// Same as `add_clause`, but the arguments of the literals can also be
+    /// fixed elements of the domains, as in `mul(x0,1,x1)`.
+    pub fn add_clause_terms(&mut self, literals: Vec<(bool, Rc<Predicate>, Vec<Term>)>) -> usize {
+        self.push_clause(Clause::from_terms(literals))
+    }
+
+    fn push_clause(&mut self, mut cla: Clause) -> usize {
+        if !self.state.levels.is_empty() {
+            self.clauses.push(cla);
+            return 0;
+        }
+
+        cla.evaluate(&self.state);
+        let satisfied = (0..cla.buffer.len())
+            .filter(|&pos| cla.buffer.get(pos) == BOOL_TRUE)
+            .count();
+        if satisfied < cla.buffer.len() {
+            self.clauses.push(cla);
+        }
+        satisfied
+    }
+
+    /// Adds a clause that may be violated, but every violated ground instance
+    /// costs the given weight. These are only used by `solve_maxsat`.
+    pub fn add_soft_clause(
+        &mut self,
+        literals: Vec<(bool, Rc<Predicate>, Vec<usize>)>,
+        weight: u64,
+    ) {
+        let cla = Clause::from_literals(literals);
+        self.soft_clauses.push((cla, weight));
+    }
+
+    // Returns the total weight of the violated instances of the soft clauses
+    // and whether some instances are still undecided.
+    fn get_soft_cost(&mut self) -> (u64, bool) {
+        let mut cost: u64 = 0;
+        let mut undecided = false;
+        for (cla, weight) in self.soft_clauses.iter_mut() {
+            cla.evaluate(&self.state);
+            for pos in 0..cla.buffer.len() {
+                let val = cla.buffer.get(pos);
+                if val == BOOL_FALSE {
+                    cost = cost.saturating_add(*weight);
+                } else if val != BOOL_TRUE {
+                    undecided = true;
+                }
+            }
+        }
+        (cost, undecided)
+    }
+
+    /// Finds a model of the hard clauses, exists and parities that minimizes
+    /// the total weight of the violated instances of the soft clauses, and
+    /// returns this weight with the model, or `None` if there is no model at
+    /// all. This is an exact branch and bound search that prunes every branch
+    /// whose violated weight already reaches the best one found, so it is
+    /// only practical for small instances.
+    pub fn solve_maxsat(&mut self) -> Option<(u64, Model)> {
+        let mut best: Option<(u64, Model)> = None;
+        if self.contradiction {
+            return best;
+        }
+
+        self.state.reserve_trail();
+        loop {
+            let (value, _) = self.propagate_all();
+            if value != BOOL_FALSE {
+                let (cost, undecided) = self.get_soft_cost();
+                let bound = best.as_ref().map_or(u64::MAX, |(cost, _)| *cost);
+                if cost < bound || best.is_none() {
+                    if value == BOOL_UNDEF2 || undecided {
+                        let ret = self.state.make_decision();
+                        assert!(ret);
+                        continue;
+                    }
+                    best = Some((cost, self.model()));
+                }
+            }
+            if !self.state.next_decision() {
+                break;
+            }
+        }
+
+        best
+    }
+
+    pub fn add_exist(&mut self, predicate: Rc<Predicate>) {
+        let axis = predicate.arity() - 1;
+        self.exists.push(Exist::new(predicate, axis));
+    }
+
+    /// Requires that for all values of the other coordinates the predicate
+    /// is true for some value along the given axis. So `add_exist` is the
+    /// same as quantifying the last axis.
+    pub fn add_exist_axis(&mut self, predicate: &Rc<Predicate>, axis: usize) {
+        self.exists.push(Exist::new(predicate.clone(), axis));
+    }
+
+    /// Sets what is printed during search, either as a `Verbosity` or as a
+    /// level from 0 (silent) to 3 (trace).
+    pub fn set_verbosity(&mut self, verbosity: impl Into<Verbosity>) {
+        self.verbosity = verbosity.into();
+    }
+
+    /// Makes all searches report only one model in each isomorphism class of
+    /// the tables of the predicate of largest arity whose axes are all over
+    /// the given domain (the first one added if there are several), and
+    /// returns that predicate. Use `set_symmetry_witness` to choose another
+    /// one.
+    pub fn add_symmetry_break(&mut self, domain: &Rc<Domain>) -> Rc<Predicate> {
+        let mut witness: Option<&Rc<Predicate>> = None;
+        for pred in self.predicates.iter() {
+            if pred.arity() > witness.map_or(0, |w| w.arity())
+                && pred.domains.iter().all(|dom| Rc::ptr_eq(dom, domain))
+            {
+                witness = Some(pred);
+            }
+        }
+        let witness = witness
+            .unwrap_or_else(|| panic!("no predicate over domain {}", domain.name))
+            .clone();
+        self.set_symmetry_witness(witness.clone());
+        witness
+    }
+
+    /// Makes all searches report only one model in each isomorphism class of
+    /// the tables of the given predicate, whose axes must be over the same
+    /// domain. Lex-leader clauses prune most of the other labelings during
+    /// search, and of the remaining models only the
+    /// canonically labeled ones are reported (see `Model::is_canonical`).
+    /// If the witness is not fully determined at a model, then its remaining
+    /// cells are decided too, so each reported model has a total witness.
+    pub fn set_symmetry_witness(&mut self, witness: Rc<Predicate>) {
+        assert!(self.symmetry.is_none() && self.state.levels.is_empty());
+        let domain = &witness.domains[0];
+        assert!(witness.domains.iter().all(|dom| Rc::ptr_eq(dom, domain)));
+        self.add_lex_leader(&witness);
+        self.symmetry = Some(witness);
+    }
+
+    // Posts for each transposition of neighbouring elements that the table
+    // of the witness is not larger, comparing the cells in the order of their
+    // positions, than the one obtained by swapping the two elements. The
+    // canonical labeling satisfies these, since it gives the smallest table.
+    // A chain of auxiliary cells records whether the cells compared so far
+    // are all equal, and cells swapped into an earlier pair are skipped.
+    fn add_lex_leader(&mut self, witness: &Rc<Predicate>) {
+        let shape = &witness.shape;
+        let mut coordinates = vec![0; witness.arity()];
+        let mut chains = vec![];
+        for elem in 1..witness.domains[0].size() {
+            let mut pairs = vec![];
+            for pos in shape.positions() {
+                shape.coordinates(pos, &mut coordinates);
+                for c in coordinates.iter_mut() {
+                    if *c == elem {
+                        *c = elem - 1;
+                    } else if *c == elem - 1 {
+                        *c = elem;
+                    }
+                }
+                let other = shape.position(coordinates.iter());
+                let (a, b) = (witness.cell(pos), witness.cell(other));
+                if other > pos && a != b {
+                    pairs.push((a, b));
+                }
+            }
+            chains.push(pairs);
+        }
+
+        let length = chains.iter().map(|pairs| pairs.len()).max().unwrap_or(0);
+        if length <= 1 {
+            for &(a, b) in chains.iter().flatten() {
+                self.lex_clauses.push(Learnt {
+                    literals: Box::new([(false, a), (true, b)]),
+                });
+            }
+            return;
+        }
+        self.lex_cells = self.state.assignment.len();
+        for pairs in chains.iter() {
+            let equal = self.state.push_table(pairs.len().saturating_sub(1));
+            let mut clauses: Vec<Vec<(bool, usize)>> = vec![];
+            let mut prefix = None;
+            for (step, &(a, b)) in pairs.iter().enumerate() {
+                let guard: Vec<(bool, usize)> = prefix.map(|e| (false, e)).into_iter().collect();
+                clauses.push([&guard[..], &[(false, a), (true, b)]].concat());
+                if step + 1 == pairs.len() {
+                    break;
+                }
+
+                // next is true exactly if the prefix is equal and a = b
+                let next = equal + step;
+                clauses.push([&guard[..], &[(false, a), (false, b), (true, next)]].concat());
+                clauses.push([&guard[..], &[(true, a), (true, b), (true, next)]].concat());
+                clauses.push(vec![(false, next), (true, a), (false, b)]);
+                if let Some(e) = prefix {
+                    clauses.push(vec![(false, next), (true, e)]);
+                }
+                prefix = Some(next);
+            }
+            for literals in clauses {
+                self.lex_clauses.push(Learnt {
+                    literals: literals.into_boxed_slice(),
+                });
+            }
+        }
+    }
+
+    // Decides an unassigned cell of the symmetry witness and returns true,
+    // or returns false if the witness is total or there is none.
+    fn decide_witness(&mut self) -> bool {
+        let witness = match &self.symmetry {
+            Some(witness) => witness,
+            None => return false,
+        };
+        let pos = witness
+            .variables()
+            .find(|&pos| self.state.assignment.get(pos) == BOOL_UNDEF1);
+        match pos {
+            Some(pos) => {
+                self.state.decide(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns true if there is no symmetry witness or its table is labeled
+    // canonically in the current model.
+    fn is_canonical_model(&self) -> bool {
+        match &self.symmetry {
+            Some(witness) => self.model().is_canonical(witness),
+            None => true,
+        }
+    }
+
+    /// When enabled, every branch that fails only after propagating the
+    /// exists or parities is explained by the decisions it depends on, and
+    /// the negation of these decisions is kept as a learned clause, so the
+    /// same dead configuration is not explored again. Disabled by default.
+    /// This only affects the counting methods, since `search_all` always
+    /// learns from its conflicts.
+    pub fn set_exist_learning(&mut self, enabled: bool) {
+        self.exist_learning = enabled;
+    }
+
+    /// When enabled, `search_all` records the constraints taking part in
+    /// each of its conflicts, so `unsat_core` can report them after the
+    /// search finished without models. Disabled by default.
+    pub fn set_core_tracking(&mut self, enabled: bool) {
+        self.core_tracking = enabled;
+    }
+
+    /// Sets the way the cell of the next decision is selected. The default
+    /// is `Heuristic::FirstUndef`.
+    pub fn set_decision_heuristic(&mut self, heuristic: Heuristic) {
+        self.state.heuristic = heuristic;
+    }
+
+    /// Sets the restart policy of `search_all`, which is disabled by default.
+    /// A restart undoes all decisions and keeps the learned clauses, and the
+    /// true branches explored so far are excluded by further learnt clauses,
+    /// so no model is lost or enumerated twice.
+    pub fn set_restart_policy(&mut self, policy: Option<RestartPolicy>) {
+        self.restart_policy = policy;
+    }
+
+    /// When enabled, the clauses and learnt clauses watch two literals of
+    /// each instance and unit propagation only revisits the instances whose
+    /// watched literal became false. When disabled, all instances are
+    /// evaluated at each propagation. Enabled by default.
+    pub fn set_watched_literals(&mut self, enabled: bool) {
+        self.dense = !enabled;
+        self.watches = Default::default();
+    }
+
+    /// Requires that for all values of the other coordinates the predicate
+    /// is true for exactly one value along the result axis. This is the
+    /// same as an exist along the axis together with the clause that two
+    /// results must be equal, but without the equality predicate.
+    pub fn add_function(&mut self, predicate: &Rc<Predicate>, result_axis: usize) {
+        self.functions
+            .push(Function::new(predicate.clone(), result_axis));
+    }
+
+    /// Requires that the number of true cells in each block along the
+    /// given axis of the predicate is even or odd.
+    pub fn add_parity(&mut self, predicate: &Rc<Predicate>, axis: usize, even: bool) {
+        self.parities
+            .push(Parity::new(predicate.clone(), axis, even));
+    }
+
+    /// Sets the value of the predicate at the given coordinates. If the cell
+    /// already has the opposite value, then the solver is marked as
+    /// inconsistent and all searches return no solutions.
+    pub fn set_value(&mut self, sign: bool, predicate: &Predicate, coordinates: &[usize]) {
+        let pos = predicate.position(coordinates);
+        self.set_initial(pos, sign);
+    }
+
+    fn set_initial(&mut self, pos: usize, sign: bool) {
+        let val = self.state.assignment.get(pos);
+        if val == BOOL_UNDEF1 {
+            self.state.assign(pos, sign, Reason::Initial);
+        } else if (val == BOOL_TRUE) != sign {
+            self.contradiction = true;
+        }
+    }
+
+    /// Tentatively sets the value of the predicate at the given coordinates
+    /// as a new decision, propagates and returns the resulting status, then
+    /// undoes all assignments of this pass. A `BOOL_FALSE` result means that
+    /// the opposite value is forced. The number of propagated cells is also
+    /// returned. The cell must be unassigned.
+    pub fn probe(
+        &mut self,
+        sign: bool,
+        predicate: &Predicate,
+        coordinates: &[usize],
+    ) -> (Bit2, usize) {
+        let pos = predicate.position(coordinates);
+        assert_eq!(self.state.assignment.get(pos), BOOL_UNDEF1);
+        self.state.begin_speculation();
+        self.state.levels.push(self.state.steps.len());
+        self.state.assign(pos, sign, Reason::Decision);
+        let (value, _) = self.propagate_all();
+        let count = self.state.speculative_steps().len() - 1;
+        self.state.rollback_speculation();
+        (value, count)
+    }
+
+    /// Returns false if contradictory initial facts were set.
+    pub fn is_consistent(&self) -> bool {
+        !self.contradiction
+    }
+
+    /// Same as `set_value`, but checks the coordinates and returns an error
+    /// instead of panicking. Setting a cell to its current value is allowed.
+    pub fn try_set_value(
+        &mut self,
+        sign: bool,
+        predicate: &Predicate,
+        coordinates: &[usize],
+    ) -> Result<(), AssignError> {
+        if coordinates.len() != predicate.arity() {
+            return Err(AssignError::Arity {
+                expected: predicate.arity(),
+                found: coordinates.len(),
+            });
+        }
+        for (axis, &coordinate) in coordinates.iter().enumerate() {
+            let length = predicate.shape.length(axis);
+            if coordinate >= length {
+                return Err(AssignError::Range {
+                    axis,
+                    coordinate,
+                    length,
+                });
+            }
+        }
+
+        let pos = predicate.position(coordinates);
+        let val = self.state.assignment.get(pos);
+        if val == BOOL_UNDEF1 {
+            self.state.assign(pos, sign, Reason::Initial);
+            Ok(())
+        } else if (val == BOOL_TRUE) == sign {
+            Ok(())
+        } else {
+            Err(AssignError::Conflict { value: !sign })
+        }
+    }
+
+    /// Sets the values of the predicate at all given coordinates. If any
+    /// of them fails, then none of them is applied and the index of the
+    /// failing entry is returned with the error. Cells that already have
+    /// the given value are skipped, so this can be called again after
+    /// propagation, but the same coordinates cannot appear twice.
+    pub fn set_values(
+        &mut self,
+        predicate: &Rc<Predicate>,
+        entries: &[(Vec<usize>, bool)],
+    ) -> Result<(), (usize, AssignError)> {
+        let start = self.state.steps.len();
+        let mut seen: HashMap<&[usize], usize> = HashMap::new();
+        for (idx, (coordinates, sign)) in entries.iter().enumerate() {
+            if let Some(&entry) = seen.get(coordinates.as_slice()) {
+                self.state.unwind(start);
+                return Err((idx, AssignError::Duplicate { entry }));
+            }
+            seen.insert(coordinates, idx);
+            if let Err(err) = self.try_set_value(*sign, predicate, coordinates) {
+                self.state.unwind(start);
+                return Err((idx, err));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if every cell of the predicate is assigned.
+    pub fn is_total(&self, predicate: &Predicate) -> bool {
+        predicate.variables().all(|pos| {
+            let val = self.state.assignment.get(pos);
+            val == BOOL_FALSE || val == BOOL_TRUE
+        })
+    }
+
+    /// Returns true if the current assignment is total and satisfies all
+    /// clauses, exists and parity constraints. Unlike `get_status` it does
+    /// not need evaluated clauses and stops at the first violation, and it
+    /// returns false if any cell is unassigned.
+    pub fn is_model(&self) -> bool {
+        let assignment = &self.state.assignment;
+        if self.contradiction || (0..assignment.len()).any(|pos| assignment.get(pos) == BOOL_UNDEF1)
+        {
+            return false;
+        }
+        let clauses = self.clauses.iter().all(|cla| {
+            (0..cla.buffer.len()).all(|pos| {
+                cla.literals
+                    .iter()
+                    .zip(cla.cell(pos))
+                    .any(|(lit, &bvar)| (assignment.get(bvar) == BOOL_TRUE) == lit.sign)
+            })
+        });
+        clauses
+            && self
+                .exists
+                .iter()
+                .all(|ext| ext.get_status(&self.state) == BOOL_TRUE)
+            && self
+                .functions
+                .iter()
+                .all(|fun| fun.get_status(&self.state) == BOOL_TRUE)
+            && self
+                .parities
+                .iter()
+                .all(|par| par.get_status(&self.state) == BOOL_TRUE)
+    }
+
+    /// Returns the current value of the predicate at the given coordinates,
+    /// which must be within the domains of the predicate.
+    pub fn get_value(&self, predicate: &Predicate, coordinates: &[usize]) -> Bit2 {
+        assert_eq!(predicate.arity(), coordinates.len());
+        assert!(predicate
+            .domains
+            .iter()
+            .zip(coordinates)
+            .all(|(dom, &elem)| elem < dom.size));
+        let pos = predicate.position(coordinates);
+        self.state.assignment.get(pos)
+    }
+
+    /// Same as `get_value`, but distinguishes unassigned cells from the ones
+    /// marked as missing.
+    pub fn get_cell(&self, predicate: &Predicate, coordinates: &[usize]) -> Cell {
+        self.get_value(predicate, coordinates).into()
+    }
+
+    pub fn set_equality(&mut self, predicate: &Predicate) {
+        for i in 0..predicate.shape.length(0) {
+            for j in 0..predicate.shape.length(1) {
+                let pos = predicate.position(&[i, j]);
+                self.set_initial(pos, i == j);
+            }
+        }
+    }
+
+    /// Adds the substitution clauses for the binary predicate `equ`: if
+    /// `equ(a,b)` and `p(..,a,..)` hold, then `p(..,b,..)` must hold too.
+    /// A clause is added for every other predicate and for every argument
+    /// position whose domain is the domain of `equ`.
+    pub fn set_congruence(&mut self, equ: &Rc<Predicate>) {
+        assert_eq!(equ.arity(), 2);
+        let dom = &equ.domains[0];
+        assert!(Rc::ptr_eq(dom, &equ.domains[1]));
+
+        let predicates: Vec<Rc<Predicate>> = self
+            .predicates
+            .iter()
+            .filter(|pred| !Rc::ptr_eq(pred, equ))
+            .cloned()
+            .collect();
+        for pred in predicates {
+            let arity = pred.arity();
+            for pos in 0..arity {
+                if !Rc::ptr_eq(&pred.domains[pos], dom) {
+                    continue;
+                }
+                let vars1: Vec<usize> = (0..arity).collect();
+                let mut vars2 = vars1.clone();
+                vars2[pos] = arity;
+                self.add_clause(vec![
+                    (false, pred.clone(), vars1),
+                    (false, equ.clone(), vec![pos, arity]),
+                    (true, pred.clone(), vars2),
+                ]);
+            }
+        }
+    }
+
+    pub fn get_clauses_status(&self) -> Bit2 {
+        let mut res = BOOL_TRUE;
+        for cla in self.clauses.iter() {
+            let val = if self.dense {
+                cla.get_status()
+            } else {
+                cla.get_current_status(&self.state)
+            };
+            res = BOOL_AND.of(res, val);
+        }
+        for lrn in self.lex_clauses.iter().chain(self.learnts.iter()) {
+            res = BOOL_AND.of(res, lrn.get_status(&self.state));
+        }
+        res
+    }
+
+    pub fn get_exists_status(&self) -> Bit2 {
+        let mut res = BOOL_TRUE;
+        for ext in self.exists.iter() {
+            res = BOOL_AND.of(res, ext.get_status(&self.state));
+        }
+        res
+    }
+
+    pub fn get_functions_status(&self) -> Bit2 {
+        let mut res = BOOL_TRUE;
+        for fun in self.functions.iter() {
+            res = BOOL_AND.of(res, fun.get_status(&self.state));
+        }
+        res
+    }
+
+    pub fn get_parities_status(&self) -> Bit2 {
+        let mut res = BOOL_TRUE;
+        for par in self.parities.iter() {
+            res = BOOL_AND.of(res, par.get_status(&self.state));
+        }
+        res
+    }
+
+    pub fn get_status(&self) -> Bit2 {
+        let res = BOOL_AND.of(self.get_clauses_status(), self.get_exists_status());
+        let res = BOOL_AND.of(res, self.get_functions_status());
+        BOOL_AND.of(res, self.get_parities_status())
+    }
+
+    pub fn evaluate_all(&mut self) {
+        for cla in self.clauses.iter_mut() {
+            cla.evaluate(&self.state);
+        }
+    }
+
+    // Returns BOOL_FALSE if the clause has failed (maybe with propagations),
+    // BOOL_UNDEF1 if some propagations were made and the status is unclear,
+    // BOOL_TRUE if the clause is universally true, and BOOL_UNDEF2 otherwise.
+    pub fn propagate_clauses(&mut self) -> Bit2 {
+        if !self.dense {
+            let grounds = Grounds {
+                clauses: &self.clauses,
+                lex_clauses: &self.lex_clauses,
+                learnts: &self.learnts,
+            };
+            return self.watches.propagate(&grounds, &mut self.state);
+        }
+
+        let mut result = BOOL_TRUE;
+        for cla in self.clauses.iter_mut() {
+            cla.evaluate(&self.state);
+            self.state.visits += cla.buffer.len();
+            let val = cla.propagate(&mut self.state);
+            result = BOOL_AND.of(result, val);
+        }
+        for lrn in self.lex_clauses.iter().chain(self.learnts.iter()) {
+            self.state.visits += 1;
+            let val = lrn.propagate(&mut self.state);
+            result = BOOL_AND.of(result, val);
+        }
+
+        debug_assert!(result == self.get_clauses_status() || result == BOOL_UNDEF1);
+        result
+    }
+
+    pub fn propagate_exists(&mut self) -> Bit2 {
+        let mut result = BOOL_TRUE;
+        for xst in self.exists.iter() {
+            let val = xst.propagate(&mut self.state);
+            result = BOOL_AND.of(result, val);
+        }
+
+        debug_assert!(result == self.get_exists_status() || result == BOOL_UNDEF1);
+        result
+    }
+
+    pub fn propagate_functions(&mut self) -> Bit2 {
+        let mut result = BOOL_TRUE;
+        for fun in self.functions.iter() {
+            let val = fun.propagate(&mut self.state);
+            result = BOOL_AND.of(result, val);
+        }
+
+        debug_assert!(result == self.get_functions_status() || result == BOOL_UNDEF1);
+        result
+    }
+
+    pub fn propagate_parities(&mut self) -> Bit2 {
+        let mut result = BOOL_TRUE;
+        for par in self.parities.iter() {
+            let val = par.propagate(&mut self.state);
+            result = BOOL_AND.of(result, val);
+        }
+
+        debug_assert!(result == self.get_parities_status() || result == BOOL_UNDEF1);
+        result
+    }
+
+    fn get_analysis_failure(&self) -> Option<Vec<usize>> {
+        if !self.dense {
+            let grounds = Grounds {
+                clauses: &self.clauses,
+                lex_clauses: &self.lex_clauses,
+                learnts: &self.learnts,
+            };
+            return self.watches.failure(&grounds, &self.state);
+        }
+        for cla in self.clauses.iter() {
+            let failure = cla.get_failure();
+            if failure.is_some() {
+                return failure;
+            }
+        }
+        for lrn in self.lex_clauses.iter().chain(self.learnts.iter()) {
+            let failure = lrn.get_failure(&self.state);
+            if failure.is_some() {
+                return failure;
+            }
+        }
+        None
+    }
+
+    /// Returns the first failed instance of a clause, if any. The clauses
+    /// must be evaluated before (e.g. by `evaluate_all`).
+    pub fn failure_decoded(&self) -> Option<Failure> {
+        for cla in self.clauses.iter() {
+            if let Some(failure) = cla.get_failure() {
+                let literals = cla
+                    .literals
+                    .iter()
+                    .zip(failure)
+                    .map(|(lit, bvar)| {
+                        let pred = self.lookup_var(bvar);
+                        let mut coordinates = vec![0; pred.arity()];
+                        pred.coordinates(bvar, &mut coordinates);
+                        (lit.sign, pred.name.clone(), coordinates)
+                    })
+                    .collect();
+                return Some(Failure {
+                    clause: cla.formula(),
+                    literals,
+                });
+            }
+        }
+        None
+    }
+
+    /// Propagates the initial facts to a fixed point before any decision and
+    /// reports the failure if the theory is already contradictory, in which
+    /// case all searches return no solutions. The propagated values are kept
+    /// for the later searches.
+    pub fn preprocess(&mut self) -> Result<(), Conflict> {
+        assert!(self.state.levels.is_empty());
+        if self.contradiction {
+            return Err(self.conflict.clone().unwrap_or(Conflict::Initial));
+        }
+        let (value, _) = self.propagate_all();
+        if value != BOOL_FALSE {
+            return Ok(());
+        }
+
+        self.contradiction = true;
+        self.record_core();
+        let conflict = self.decode_conflict();
+        self.conflict = Some(conflict.clone());
+        Err(conflict)
+    }
+
+    // Returns the reason of the failed propagation at level zero.
+    fn decode_conflict(&mut self) -> Conflict {
+        self.evaluate_all();
+        if let Some(failure) = self.failure_decoded() {
+            return Conflict::Clause(failure);
+        }
+        if let Some(witness) = &self.symmetry {
+            let failed = self
+                .lex_clauses
+                .iter()
+                .any(|lrn| lrn.get_status(&self.state) == BOOL_FALSE);
+            if failed {
+                return Conflict::Constraint(format!("symmetry breaking of {}", witness.name));
+            }
+        }
+        let learnt = self
+            .learnts
+            .iter()
+            .find(|lrn| lrn.get_status(&self.state) == BOOL_FALSE);
+        if let Some(lrn) = learnt {
+            let literals = lrn
+                .literals
+                .iter()
+                .map(|&(sign, bvar)| {
+                    let pred = self.lookup_var(bvar);
+                    let mut coordinates = vec![0; pred.arity()];
+                    pred.coordinates(bvar, &mut coordinates);
+                    (sign, pred.name.clone(), coordinates)
+                })
+                .collect();
+            return Conflict::Learnt(literals);
+        }
+        let constraint = self
+            .exists
+            .iter()
+            .find(|ext| ext.get_status(&self.state) == BOOL_FALSE)
+            .map(|ext| ext.to_string())
+            .or_else(|| {
+                self.functions
+                    .iter()
+                    .find(|fun| fun.get_status(&self.state) == BOOL_FALSE)
+                    .map(|fun| fun.to_string())
+            })
+            .or_else(|| {
+                self.parities
+                    .iter()
+                    .find(|par| par.get_status(&self.state) == BOOL_FALSE)
+                    .map(|par| par.to_string())
+            })
+            .expect("the propagation failed without a false constraint");
+        Conflict::Constraint(constraint)
+    }
+
+    // Returns the boolean variables of some failed clause, exist block or
+    // parity block. The clauses must be evaluated before.
+    fn get_conflict(&self) -> Option<Vec<usize>> {
+        if let Some(failure) = self.get_analysis_failure() {
+            return Some(failure);
+        }
+        for ext in self.exists.iter() {
+            let block = ext
+                .blocks()
+                .find(|block| ext.get_value(&self.state, block) == BOOL_FALSE);
+            if let Some(block) = block {
+                return Some(block.to_vec());
+            }
+        }
+        for fun in self.functions.iter() {
+            if let Some(failure) = fun.get_failure(&self.state) {
+                return Some(failure);
+            }
+        }
+        for par in self.parities.iter() {
+            let block = par
+                .blocks()
+                .find(|block| par.get_value(&self.state, block) == BOOL_FALSE);
+            if let Some(block) = block {
+                return Some(block.to_vec());
+            }
+        }
+        None
+    }
+
+    /// Returns the implication graph of the current conflict in Graphviz DOT
+    /// format, or `None` if there is no conflict. The nodes are the assigned
+    /// literals the conflict depends on, with an edge from each antecedent
+    /// to the literal it implied, and the decisions are drawn as boxes. The
+    /// clauses must be evaluated before (e.g. by `propagate_clauses`).
+    pub fn conflict_graph_dot(&self) -> Option<String> {
+        let conflict = self.get_conflict()?;
+        let mut marked = vec![false; self.state.assignment.len()];
+        for &bvar in conflict.iter() {
+            marked[bvar] = true;
+        }
+
+        let mut lines = vec![];
+        for step in self.state.steps.iter().rev() {
+            if !marked[step.bvar] {
+                continue;
+            }
+            let shape = match step.reason {
+                Reason::Decision => "box",
+                _ => "ellipse",
+            };
+            lines.push(format!(
+                "  v{} [label=\"{} @{}\", shape={}];",
+                step.bvar,
+                self.format_var(step.bvar),
+                step.level,
+                shape
+            ));
+            if let Reason::Clause(bvars) | Reason::Exists(bvars) = &step.reason {
+                for &bvar in bvars.iter() {
+                    marked[bvar] = true;
+                    lines.push(format!("  v{} -> v{};", bvar, step.bvar));
+                }
+            }
+        }
+        lines.reverse();
+
+        let mut dot = String::from("digraph conflict {\n");
+        for line in lines.iter() {
+            dot.push_str(line);
+            dot.push('\n');
+        }
+        dot.push_str("  conflict [shape=octagon];\n");
+        for &bvar in conflict.iter() {
+            dot.push_str(&format!("  v{} -> conflict;\n", bvar));
+        }
+        dot.push_str("}\n");
+        Some(dot)
+    }
+
+    // Records the instances of the current conflict and of the reasons of
+    // the cells it depends on, which are followed back through the trail.
+    fn record_core(&mut self) {
+        if self.dense {
+            self.evaluate_all();
+        }
+        let conflict = match self.get_conflict() {
+            Some(conflict) => conflict,
+            None => return,
+        };
+        let mut marked = vec![false; self.state.assignment.len()];
+        for &bvar in conflict.iter() {
+            marked[bvar] = true;
+        }
+        let mut instances = vec![conflict];
+        for step in self.state.steps.iter().rev() {
+            if !marked[step.bvar] {
+                continue;
+            }
+            if let Reason::Clause(bvars) | Reason::Exists(bvars) = &step.reason {
+                for &bvar in bvars.iter() {
+                    marked[bvar] = true;
+                }
+                let mut instance = bvars.clone();
+                instance.push(step.bvar);
+                instances.push(instance);
+            }
+        }
+        for mut instance in instances {
+            instance.sort_unstable();
+            instance.dedup();
+            self.core.insert(instance.into_boxed_slice());
+        }
+    }
+
+    /// Returns the constraints that took part in the conflicts refuting the
+    /// theory, either in `preprocess` or in a `search_all` that finished
+    /// without models after `set_core_tracking` was enabled, and `None` if
+    /// the theory was not refuted this way. The conflicts and the reasons
+    /// of their cells are followed back through the trail, including the
+    /// conflicts the learnt clauses were derived from, so the constraints
+    /// are unsatisfiable together, but they might not form a minimal core.
+    /// A clause is listed if one of the instances matches, and an exist,
+    /// function or parity if the cells are within one of its blocks. The
+    /// list is empty if the initial facts are contradictory by themselves.
+    pub fn unsat_core(&self) -> Option<Vec<ClauseRef>> {
+        if !self.contradiction && !self.refuted {
+            return None;
+        }
+
+        let mut core = vec![];
+        for (idx, cla) in self.clauses.iter().enumerate() {
+            let used = (0..cla.shape.volume()).any(|pos| {
+                let mut instance = cla.cell(pos).to_vec();
+                instance.sort_unstable();
+                instance.dedup();
+                self.core.contains(instance.as_slice())
+            });
+            if used {
+                core.push(ClauseRef::Clause(idx));
+            }
+        }
+        let mut within = |constraint: ClauseRef, blocks: std::slice::Chunks<'_, usize>| {
+            let mut block_of = vec![usize::MAX; self.state.assignment.len()];
+            for (idx, block) in blocks.enumerate() {
+                for &bvar in block.iter() {
+                    block_of[bvar] = idx;
+                }
+            }
+            let used = self.core.iter().any(|instance| {
+                let idx = instance.first().map_or(usize::MAX, |&bvar| block_of[bvar]);
+                idx != usize::MAX && instance.iter().all(|&bvar| block_of[bvar] == idx)
+            });
+            if used {
+                core.push(constraint);
+            }
+        };
+        for (idx, ext) in self.exists.iter().enumerate() {
+            within(ClauseRef::Exist(idx), ext.blocks());
+        }
+        for (idx, fun) in self.functions.iter().enumerate() {
+            within(ClauseRef::Function(idx), fun.blocks());
+        }
+        for (idx, par) in self.parities.iter().enumerate() {
+            within(ClauseRef::Parity(idx), par.blocks());
+        }
+        Some(core)
+    }
+
+    /// Returns the formula of the clause or the description of the other
+    /// constraint, as used in the messages of the solver.
+    pub fn format_constraint(&self, constraint: ClauseRef) -> String {
+        match constraint {
+            ClauseRef::Clause(idx) => self.clauses[idx].formula(),
+            ClauseRef::Exist(idx) => self.exists[idx].to_string(),
+            ClauseRef::Function(idx) => self.functions[idx].to_string(),
+            ClauseRef::Parity(idx) => self.parities[idx].to_string(),
+        }
+    }
+
+    /// Returns the fraction of the satisfied instances of each clause,
+    /// which shows which axioms are constraining the search the most. The
+    /// clauses must be evaluated before (e.g. by `evaluate_all`).
+    pub fn clause_density(&self) -> Vec<(String, f64)> {
+        self.clauses
+            .iter()
+            .map(|cla| (cla.formula(), cla.density()))
+            .collect()
+    }
+
+    // Follows the reasons of the given boolean variables back through the
+    // trail and returns the decisions (above level 0) they depend on.
+    fn get_decisions(&self, bvars: &[usize]) -> Vec<usize> {
+        let mut marked = vec![false; self.state.assignment.len()];
+        for &bvar in bvars.iter() {
+            marked[bvar] = true;
+        }
+
+        let mut decisions = vec![];
+        let start = self
+            .state
+            .levels
+            .first()
+            .copied()
+            .unwrap_or(self.state.steps.len());
+        for step in self.state.steps[start..].iter().rev() {
+            if !marked[step.bvar] {
+                continue;
+            }
+            match &step.reason {
+                Reason::Decision => decisions.push(step.bvar),
+                Reason::Clause(bvars) | Reason::Exists(bvars) => {
+                    for &bvar in bvars.iter() {
+                        marked[bvar] = true;
+                    }
+                }
+                Reason::Initial => {}
+            }
+        }
+        decisions
+    }
+
+    // Learns the negation of the decisions that lead to the current
+    // conflict. Returns false if there was no conflict to explain.
+    fn learn_conflict(&mut self) -> bool {
+        self.evaluate_all();
+        let conflict = match self.get_conflict() {
+            Some(conflict) => conflict,
+            None => return false,
+        };
+        let literals = self
+            .get_decisions(&conflict)
+            .into_iter()
+            .map(|bvar| (self.state.assignment.get(bvar) != BOOL_TRUE, bvar))
+            .collect();
+        self.learnts.push(Learnt { literals });
+        true
+    }
+
+    // Derives the first unique implication point clause of the current
+    // conflict and stores it. Returns the highest decision level of the
+    // other literals of the learnt clause, or `None` if nothing was learnt.
+    fn learn_first_uip(&mut self) -> Option<usize> {
+        if self.dense {
+            self.evaluate_all();
+        }
+        let top = self.state.levels.len();
+        let conflict = match self.get_conflict() {
+            Some(conflict) if top > 0 => conflict,
+            _ => return None,
+        };
+        let start = self.state.levels[top - 1];
+        let mut on_top = vec![false; self.state.assignment.len()];
+        for step in self.state.steps[start..].iter() {
+            on_top[step.bvar] = true;
+        }
+
+        let mut marked = vec![false; self.state.assignment.len()];
+        let mut count = 0;
+        for &bvar in conflict.iter() {
+            if !marked[bvar] {
+                marked[bvar] = true;
+                self.state.bump(bvar);
+                count += on_top[bvar] as usize;
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+
+        let mut uip = None;
+        let mut bumps = Vec::new();
+        for step in self.state.steps[start..].iter().rev() {
+            if !marked[step.bvar] {
+                continue;
+            } else if count == 1 {
+                uip = Some(step.bvar);
+                break;
+            }
+            marked[step.bvar] = false;
+            count -= 1;
+            if let Reason::Clause(bvars) | Reason::Exists(bvars) = &step.reason {
+                for &bvar in bvars.iter() {
+                    if !marked[bvar] {
+                        marked[bvar] = true;
+                        bumps.push(bvar);
+                        count += on_top[bvar] as usize;
+                    }
+                }
+            }
+        }
+        let uip = uip.unwrap();
+        for bvar in bumps {
+            self.state.bump(bvar);
+        }
+        self.state.decay();
+
+        // literals at level zero are false forever, so they are dropped
+        let mut level = 0;
+        let mut literals = vec![(self.state.assignment.get(uip) != BOOL_TRUE, uip)];
+        for step in self.state.steps[..start].iter() {
+            if marked[step.bvar] && step.level > 0 {
+                level = level.max(step.level);
+                let sign = self.state.assignment.get(step.bvar) != BOOL_TRUE;
+                literals.push((sign, step.bvar));
+            }
+        }
+        self.learnts.push(Learnt {
+            literals: literals.into_boxed_slice(),
+        });
+
+        Some(level)
+    }
+
+    // Returns the level to backjump to after learning a clause whose other
+    // literals are at most at the given level, or `None` if the search has
+    // to backtrack chronologically. Decisions whose true branch was already
+    // explored are never undone, so no model is enumerated twice.
+    fn backjump_target(&self, level: usize) -> Option<usize> {
+        let decision = |lvl: usize| {
+            let step = &self.state.steps[self.state.levels[lvl - 1]];
+            self.state.assignment.get(step.bvar)
+        };
+        let top = self.state.levels.len();
+        if decision(top) != BOOL_TRUE {
+            return None;
+        }
+        let flipped = (1..top)
+            .rev()
+            .find(|&lvl| decision(lvl) == BOOL_FALSE)
+            .unwrap_or(0);
+        Some(level.max(flipped))
+    }
+
+    // Adds a learnt clause for each flipped decision that excludes its
+    // explored true branch under the earlier decisions, so the search can
+    // restart from level zero without enumerating a model twice.
+    fn block_explored(&mut self) {
+        let mut prefix = vec![];
+        for &start in self.state.levels.iter() {
+            let bvar = self.state.steps[start].bvar;
+            let val = self.state.assignment.get(bvar) == BOOL_TRUE;
+            if !val {
+                let mut literals = prefix.clone();
+                literals.push((false, bvar));
+                self.learnts.push(Learnt {
+                    literals: literals.into_boxed_slice(),
+                });
+            }
+            prefix.push((!val, bvar));
+        }
+    }
+
+    fn get_analysis_step(&self, bvar: usize) -> Option<usize> {
+        let last = *self.state.levels.last().unwrap();
+        self.state
+            .steps
+            .iter()
+            .skip(last)
+            .position(|s| s.bvar == bvar)
+            .map(|p| p + last)
+    }
+
+    fn analyze(&self) {
+        println!("*** ANALYSIS ***");
+        let failure = self.get_analysis_failure().unwrap();
+
+        let mut before: Vec<usize> = Default::default();
+        let mut after: Vec<usize> = Default::default();
+        for &bvar in failure.iter() {
+            let step = self.get_analysis_step(bvar);
+            println!("{} {} {:?}", bvar, self.format_var(bvar), step);
+            match step {
+                None => {
+                    match before.binary_search(&bvar) {
+                        Ok(_) => {}
+                        Err(pos) => before.insert(pos, bvar),
+                    };
+                }
+                Some(step) => {
+                    match after.binary_search(&step) {
+                        Ok(_) => {}
+                        Err(pos) => after.insert(pos, step),
+                    };
+                }
+            };
+        }
+        assert!(!after.is_empty());
+        println!("before: {:?}, after: {:?}", before, after);
+
+        while after.len() >= 2 {
+            let last = after.pop().unwrap();
+            match &self.state.steps[last].reason {
+                Reason::Clause(bvars) | Reason::Exists(bvars) => {
+                    for &bvar in bvars.iter() {
+                        let step = self.get_analysis_step(bvar);
+                        println!("{} {} {:?}", bvar, self.format_var(bvar), step);
+                        match step {
+                            None => {
+                                match before.binary_search(&bvar) {
+                                    Ok(_) => {}
+                                    Err(pos) => before.insert(pos, bvar),
+                                };
+                            }
+                            Some(step) => {
+                                match after.binary_search(&step) {
+                                    Ok(_) => {}
+                                    Err(pos) => after.insert(pos, step),
+                                };
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    panic!();
+                }
+            };
+        }
+
+        assert_eq!(after.len(), 1);
+        let bvar = self.state.steps[after.pop().unwrap()].bvar;
+        assert!(!before.contains(&bvar));
+        before.push(bvar);
+        println!("literals: {:?}", before);
+
+        print!("learned clause:");
+        for &bvar in before.iter() {
+            print!(" {}", self.format_var(bvar));
+        }
+        println!();
+
+        println!("*** END OF ANALYSIS ***");
+    }
+
+    // Propagates the clauses and then the exists, functions and parities
+    // until a fixed point is reached. Returns the final status, which is
+    // never BOOL_UNDEF1, and whether the latter ones were propagated.
+    fn propagate_all(&mut self) -> (Bit2, bool) {
+        let mut used_exists = false;
+        let mut value;
+        loop {
+            value = self.propagate_clauses();
+            if value == BOOL_UNDEF1 {
+                continue;
+            } else if value == BOOL_FALSE {
+                break;
+            }
+
+            used_exists = true;
+            value = BOOL_AND.of(value, self.propagate_exists());
+            value = BOOL_AND.of(value, self.propagate_functions());
+            value = BOOL_AND.of(value, self.propagate_parities());
+            if value == BOOL_UNDEF1 {
+                continue;
+            } else {
+                break;
+            }
+        }
+
+        debug_assert!(value != BOOL_UNDEF1 && value == self.get_status());
+        (value, used_exists)
+    }
+
+    // Enumerates all models without printing anything and calls the
+    // callback with the solver at each of them.
+    fn for_each_model<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(&Solver),
+    {
+        while self.next_model() {
+            callback(self);
+        }
+    }
+
+    // Continues the enumeration until the next model and returns true, or
+    // returns false if there are no more models. The enumeration resumes
+    // after the last reported model.
+    fn next_model(&mut self) -> bool {
+        if self.contradiction {
+            return false;
+        }
+        if self.at_model {
+            self.at_model = false;
+            if !self.state.next_decision() {
+                return false;
+            }
+        }
+
+        self.state.reserve_trail();
+        loop {
+            let (value, used_exists) = self.propagate_all();
+            if value == BOOL_UNDEF2 {
+                let ret = self.state.make_decision();
+                assert!(ret);
+                continue;
+            } else if value == BOOL_TRUE {
+                if self.decide_witness() {
+                    continue;
+                } else if self.is_canonical_model() {
+                    self.at_model = true;
+                    return true;
+                }
+            } else if used_exists && self.exist_learning {
+                self.learn_conflict();
+            }
+            if !self.state.next_decision() {
+                return false;
+            }
+        }
+    }
+
+    /// Returns an iterator over the models, each given as the list of the
+    /// assigned cells of all predicates with their values. As in `count_all`
+    /// a model is reported as soon as all constraints are satisfied, so some
+    /// cells might be missing from the list. The search is suspended between
+    /// the models.
+    pub fn solutions(&mut self) -> Solutions<'_> {
+        Solutions { solver: self }
+    }
+
+    // Returns the assigned cells of all predicates.
+    fn assigned_cells(&self) -> Vec<(String, Vec<usize>, bool)> {
+        let mut cells = vec![];
+        for pred in self.predicates.iter() {
+            let mut coordinates = vec![0; pred.arity()];
+            for pos in pred.variables() {
+                let val = self.state.assignment.get(pos);
+                if val == BOOL_TRUE || val == BOOL_FALSE {
+                    pred.coordinates(pos, &mut coordinates);
+                    cells.push((pred.name.clone(), coordinates.clone(), val == BOOL_TRUE));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Calls the callback at each model until it returns false. The solver
+    /// is then left at that model, and a following call (or any other
+    /// enumeration) continues with the next model. The callback gets the
+    /// solver, since the search state itself is private.
+    pub fn for_each_solution<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(&Solver) -> bool,
+    {
+        while self.next_model() {
+            if !callback(self) {
+                break;
+            }
+        }
+    }
+
+    /// Adds a learnt clause that excludes the current values of the given
+    /// predicates (or of all of them), so that the following enumeration
+    /// sees only the remaining models. The search continues from the current
+    /// trail, so the models reported earlier are not found again whether or
+    /// not they were blocked. This only makes sense at a complete assignment,
+    /// e.g. after a model was found, so it panics if any of the cells is
+    /// unassigned.
+    pub fn block_model(&mut self, predicates: Option<&[Rc<Predicate>]>) {
+        let predicates = predicates.unwrap_or(&self.predicates);
+        let mut literals = vec![];
+        for pred in predicates.iter() {
+            for pos in pred.variables() {
+                let val = self.state.assignment.get(pos);
+                assert!(
+                    val != BOOL_UNDEF1,
+                    "block_model needs a complete assignment"
+                );
+                literals.push((val != BOOL_TRUE, pos));
+            }
+        }
+        self.learnts.push(Learnt {
+            literals: literals.into_boxed_slice(),
+        });
+    }
+
+    /// Enumerates all models without printing anything and returns
+    /// their number.
+    pub fn count_all(&mut self) -> usize {
+        let mut num_solutions: usize = 0;
+        self.for_each_model(|_| num_solutions += 1);
+        num_solutions
+    }
+
+    /// Counts the remaining models found by `next_model`, like `count_all`
+    /// but with a counter that cannot overflow.
+    pub fn count_models(&mut self) -> u128 {
+        let mut count: u128 = 0;
+        while self.next_model() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Counts the models for each canonical hash of the witness predicate,
+    /// so models with isomorphic witness tables are counted together.
+    pub fn histogram_by(&mut self, witness: &Rc<Predicate>) -> HashMap<u64, usize> {
+        let mut histogram: HashMap<u64, usize> = Default::default();
+        self.for_each_model(|sol| {
+            let hash = sol.model().canonical_hash(witness);
+            *histogram.entry(hash).or_insert(0) += 1;
+        });
+        histogram
+    }
+
+    /// Enumerates all models and groups them by the canonical form of the
+    /// witness predicate. Returns one representative of each isomorphism
+    /// class together with the number of models in that class, ordered by
+    /// the canonical forms.
+    pub fn class_reps(&mut self, witness: &Rc<Predicate>) -> Vec<(Model, usize)> {
+        let mut classes: BTreeMap<Vec<u8>, (Model, usize)> = Default::default();
+        self.for_each_model(|sol| {
+            let model = sol.model();
+            let form = model.canonical_form(witness);
+            classes.entry(form).or_insert((model, 0)).1 += 1;
+        });
+        classes.into_values().collect()
+    }
+
+    /// Prints the canonical table of the witness predicate for each
+    /// isomorphism class of models, with the number of its automorphisms
+    /// and labeled copies.
+    pub fn print_class_reps(&mut self, witness: &Rc<Predicate>) {
+        let classes = self.class_reps(witness);
+        let shape = witness.shape();
+        let mut coordinates = vec![0; shape.dimension()];
+        for (idx, (model, copies)) in classes.iter().enumerate() {
+            println!(
+                "class {}: automorphisms {}, labeled copies {}",
+                idx,
+                model.automorphisms(witness),
+                copies
+            );
+            let form = model.canonical_form(witness);
+            for (pos, &val) in shape.positions().zip(form.iter()) {
+                shape.coordinates(pos, &mut coordinates);
+                println!("  {:?} = {}", coordinates, BOOL_FORMAT1[val as usize]);
+            }
+        }
+        println!("Total classes: {}", classes.len());
+    }
+
+    pub fn search_all(&mut self) -> SearchStats {
+        let stats = self.search_all_cancellable(&AtomicBool::new(false));
+        if self.verbosity >= Verbosity::Summary {
+            println!("Total solutions: {}", stats.solutions);
+            println!("Total learnings: {}", stats.learnings);
+            println!("Total deadends: {}", stats.deadends);
+            println!("Total decisions: {}", stats.decisions);
+            println!("Total propagations: {}", stats.propagations);
+            println!("Total clause visits: {}", stats.clause_visits);
+            println!("Total learnt clauses: {}", stats.learnt_clauses);
+            println!("Total function conflicts: {}", stats.function_conflicts);
+            println!("Total restarts: {}", stats.restarts);
+            println!("Peak trail: {}", stats.peak_trail);
+            println!("Max level: {}", stats.max_level);
+        }
+        stats
+    }
+
+    /// Same as `search_all`, but checks the flag every few steps and returns
+    /// the statistics so far when it is set. The search can be resumed by
+    /// calling this method again.
+    pub fn search_all_cancellable(&mut self, cancel: &AtomicBool) -> SearchStats {
+        let mut iteration: usize = 0;
+        self.search_until(|| {
+            let check = iteration.is_multiple_of(CANCEL_CHECK_INTERVAL);
+            iteration += 1;
+            check && cancel.load(Ordering::Relaxed)
+        })
+    }
+
+    /// Returns the total statistics of all searches made by `search_all` and
+    /// `search_all_cancellable`, including the ones saved in a checkpoint.
+    pub fn search_stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    // Runs the search of `search_all` until it is finished or the closure,
+    // which is called before each step, returns true.
+    fn search_until<F>(&mut self, mut stop: F) -> SearchStats
+    where
+        F: FnMut() -> bool,
+    {
+        let mut num_solutions: usize = 0;
+        let mut num_learnings: usize = 0;
+        let mut num_deadends: usize = 0;
+        let mut peak_trail: usize = 0;
+        let mut max_level: usize = 0;
+        let mut learnt_clauses: usize = 0;
+        let mut restarts: u64 = 0;
+        let mut function_conflicts: usize = 0;
+        if self.contradiction {
+            return Default::default();
+        }
+        let decisions = self.state.decisions;
+        let propagations = self.state.propagations;
+        let visits = self.state.visits;
+
+        self.state.reserve_trail();
+        let mut finished = true;
+        loop {
+            if stop() {
+                finished = false;
+                break;
+            }
+
+            let (value, used_exists) = self.propagate_all();
+            peak_trail = peak_trail.max(self.state.steps.len());
+            max_level = max_level.max(self.state.levels.len());
+            if value == BOOL_FALSE {
+                if self.get_functions_status() == BOOL_FALSE {
+                    function_conflicts += 1;
+                }
+                if self.core_tracking {
+                    self.record_core();
+                }
+                if !used_exists {
+                    num_learnings += 1;
+                    if self.verbosity >= Verbosity::Trace {
+                        println!("*** LEARNING ***");
+                        self.evaluate_all();
+                        self.print();
+                        println!("*** END OF LEARNING ***");
+                        if !self.state.levels.is_empty() {
+                            self.analyze();
+                        }
+                    }
+                } else {
+                    num_deadends += 1;
+                    if self.verbosity >= Verbosity::Trace {
+                        println!("*** EXISTS ***");
+                        self.evaluate_all();
+                        self.print();
+                        println!("*** END OF EXISTS ***");
+                    }
+                }
+                if let Some(level) = self.learn_first_uip() {
+                    learnt_clauses += 1;
+                    if let Some(mut target) = self.backjump_target(level) {
+                        if let Some(policy) = self.restart_policy {
+                            self.conflicts += 1;
+                            if self.conflicts >= policy.budget(self.stats.restarts + restarts) {
+                                self.conflicts = 0;
+                                restarts += 1;
+                                self.block_explored();
+                                target = 0;
+                            }
+                        }
+                        self.state.backjump(target);
+                        continue;
+                    }
+                }
+                if !self.state.next_decision() {
+                    break;
+                }
+            } else if value == BOOL_TRUE {
+                if self.decide_witness() {
+                    continue;
+                }
+                if !self.is_canonical_model() {
+                    if !self.state.next_decision() {
+                        break;
+                    }
+                    continue;
+                }
+                num_solutions += 1;
+                if self.verbosity >= Verbosity::Solutions {
+                    println!("*** SOLUTION ***");
+                    for pred in self.predicates.iter() {
+                        println!("{}", pred);
+                        self.state.print_table(pred);
+                    }
+                    println!("*** END OF SOLUTION ***");
+                }
+                if !self.state.next_decision() {
+                    break;
+                }
+            } else {
+                assert_eq!(value, BOOL_UNDEF2);
+                let ret = self.state.make_decision();
+                assert!(ret);
+            }
+        }
+
+        let stats = SearchStats {
+            solutions: num_solutions,
+            learnings: num_learnings,
+            deadends: num_deadends,
+            peak_trail,
+            max_level,
+            restarts,
+            learnt_clauses,
+            function_conflicts,
+            decisions: self.state.decisions - decisions,
+            propagations: self.state.propagations - propagations,
+            clause_visits: self.state.visits - visits,
+        };
+        self.stats.merge(&stats);
+        self.refuted = finished && self.core_tracking && self.stats.solutions == 0;
+        stats
+    }
+
+    /// Combines the tables of two predicates elementwise with the given
+    /// operation. The axis `i` of the first predicate is mapped to the axis
+    /// `map_a[i]` of the target shape and similarly for the second one, so
+    /// for example maps `[0, 1]` and `[1, 2]` give the table of
+    /// `r(x0,x1) op s(x1,x2)`. The target shape has the last coordinate
+    /// advancing the fastest.
+    pub fn combine(
+        &self,
+        a: &Rc<Predicate>,
+        b: &Rc<Predicate>,
+        map_a: &[usize],
+        map_b: &[usize],
+        op: Op222,
+    ) -> Buffer2 {
+        let mut domains: Vec<Option<Rc<Domain>>> = Default::default();
+        for (pred, map) in [(a, map_a), (b, map_b)] {
+            assert_eq!(pred.arity(), map.len());
+            for (dom1, &idx) in pred.domains.iter().zip(map.iter()) {
+                if domains.len() <= idx {
+                    domains.resize(idx + 1, None);
+                }
+                let dom2 = &mut domains[idx];
+                if let Some(dom2) = dom2 {
+                    assert!(Rc::ptr_eq(dom1, dom2));
+                } else {
                     *dom2 = Some(dom1.clone());
+                }
+            }
+        }
+        let lengths = domains.into_iter().map(|dom| dom.unwrap().size).collect();
+        let shape = Shape::new(lengths, 0);
+
+        let mut buffer = Buffer2::new(shape.volume(), BOOL_FALSE);
+        let positions = a.shape.view().polymer(&shape, map_a).simplify().positions();
+        let mut iter = positions.map(|pos| a.cell(pos));
+        buffer.apply(BOOL_OR, &self.state.assignment, &mut iter);
+        let positions = b.shape.view().polymer(&shape, map_b).simplify().positions();
+        let mut iter = positions.map(|pos| b.cell(pos));
+        buffer.apply(op, &self.state.assignment, &mut iter);
+        buffer
+    }
+
+    /// Saves the assignment, the trail, the learnt clauses and the search
+    /// statistics of the solver to the given file, so that an interrupted
+    /// search can be continued with `resume_checkpoint`.
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        let assignment = self.state.assignment.to_bytes();
+        push_u64(&mut bytes, assignment.len());
+        bytes.extend_from_slice(&assignment);
+
+        push_u64(&mut bytes, self.state.steps.len());
+        for step in self.state.steps.iter() {
+            push_u64(&mut bytes, step.bvar);
+            push_u64(&mut bytes, step.level);
+            let (tag, bvars): (u8, &[usize]) = match &step.reason {
+                Reason::Initial => (0, &[]),
+                Reason::Decision => (1, &[]),
+                Reason::Clause(bvars) => (2, bvars),
+                Reason::Exists(bvars) => (3, bvars),
+            };
+            bytes.push(tag);
+            push_u64(&mut bytes, bvars.len());
+            for &bvar in bvars.iter() {
+                push_u64(&mut bytes, bvar);
+            }
+        }
+
+        push_u64(&mut bytes, self.state.levels.len());
+        for &level in self.state.levels.iter() {
+            push_u64(&mut bytes, level);
+        }
+
+        push_u64(&mut bytes, self.learnts.len());
+        for lrn in self.learnts.iter() {
+            push_u64(&mut bytes, lrn.literals.len());
+            for &(sign, bvar) in lrn.literals.iter() {
+                bytes.push(sign as u8);
+                push_u64(&mut bytes, bvar);
+            }
+        }
+
+        let stats = &self.stats;
+        for val in [
+            stats.solutions,
+            stats.learnings,
+            stats.deadends,
+            stats.peak_trail,
+            stats.max_level,
+            stats.restarts as usize,
+            stats.learnt_clauses,
+            stats.function_conflicts,
+            stats.decisions,
+            stats.propagations,
+            stats.clause_visits,
+            self.conflicts as usize,
+        ] {
+            push_u64(&mut bytes, val);
+        }
+        bytes.push(self.contradiction as u8);
+        bytes.push(self.at_model as u8);
+        std::fs::write(path, bytes)
+    }
+
+    /// Restores the assignment and the trail saved by `save_checkpoint`.
+    /// The solver must have been built with the same domains, predicates
+    /// and constraints as the one that saved the checkpoint.
+    pub fn resume_checkpoint<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let bytes = std::fs::read(path)?;
+        let mut input = bytes
+            .strip_prefix(CHECKPOINT_MAGIC)
+            .ok_or_else(|| invalid_data("not a checkpoint file"))?;
+
+        let len = read_u64(&mut input)?;
+        let assignment = Buffer2::from_bytes(read_bytes(&mut input, len)?)
+            .ok_or_else(|| invalid_data("invalid assignment"))?;
+        if assignment.len() != self.state.assignment.len() {
+            return Err(invalid_data("assignment length mismatch"));
+        }
+        let read_bvar = |input: &mut &[u8]| {
+            let bvar = read_u64(input)?;
+            if bvar < assignment.len() {
+                Ok(bvar)
+            } else {
+                Err(invalid_data("variable out of range"))
+            }
+        };
+
+        let len = read_u64(&mut input)?;
+        let mut steps = Vec::with_capacity(len.min(assignment.len()));
+        for _ in 0..len {
+            let bvar = read_bvar(&mut input)?;
+            let level = read_u64(&mut input)?;
+            let tag = read_bytes(&mut input, 1)?[0];
+            let len = read_u64(&mut input)?;
+            let mut bvars = Vec::with_capacity(len.min(assignment.len()));
+            for _ in 0..len {
+                bvars.push(read_bvar(&mut input)?);
+            }
+            let reason = match tag {
+                0 => Reason::Initial,
+                1 => Reason::Decision,
+                2 => Reason::Clause(bvars),
+                3 => Reason::Exists(bvars),
+                _ => return Err(invalid_data("invalid reason")),
+            };
+            steps.push(Step {
+                bvar,
+                reason,
+                level,
+            });
+        }
+
+        let len = read_u64(&mut input)?;
+        let mut levels = Vec::with_capacity(len.min(steps.len()));
+        for _ in 0..len {
+            let level = read_u64(&mut input)?;
+            if level >= steps.len() {
+                return Err(invalid_data("level out of range"));
+            }
+            levels.push(level);
+        }
+
+        let len = read_u64(&mut input)?;
+        let mut learnts = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            let len = read_u64(&mut input)?;
+            let mut literals = Vec::with_capacity(len.min(assignment.len()));
+            for _ in 0..len {
+                let sign = read_bytes(&mut input, 1)?[0] != 0;
+                literals.push((sign, read_bvar(&mut input)?));
+            }
+            learnts.push(Learnt {
+                literals: literals.into_boxed_slice(),
+            });
+        }
+
+        let mut counters = [0; 12];
+        for val in counters.iter_mut() {
+            *val = read_u64(&mut input)?;
+        }
+        let stats = SearchStats {
+            solutions: counters[0],
+            learnings: counters[1],
+            deadends: counters[2],
+            peak_trail: counters[3],
+            max_level: counters[4],
+            restarts: counters[5] as u64,
+            learnt_clauses: counters[6],
+            function_conflicts: counters[7],
+            decisions: counters[8],
+            propagations: counters[9],
+            clause_visits: counters[10],
+        };
+        let contradiction = read_bytes(&mut input, 1)?[0] != 0;
+        let at_model = read_bytes(&mut input, 1)?[0] != 0;
+        if !input.is_empty() {
+            return Err(invalid_data("trailing bytes"));
+        }
+
+        self.state.assignment = assignment;
+        self.state.touch_all();
+        for (idx, step) in steps.iter().enumerate() {
+            self.state.positions[step.bvar] = idx;
+        }
+        self.state.steps = steps;
+        self.state.levels = levels;
+        self.state.speculative = None;
+        self.state.watched = 0;
+        self.watches = Default::default();
+        self.learnts = learnts;
+        self.stats = stats;
+        self.conflicts = counters[11] as u64;
+        self.contradiction = contradiction;
+        self.conflict = None;
+        self.at_model = at_model;
+        Ok(())
+    }
+
+    /// Takes a snapshot of the current values of all predicates.
+    pub fn model(&self) -> Model {
+        Model::new(self.predicates.clone(), self.state.assignment.clone())
+    }
+
+    /// Returns the tables of all predicates as a JSON array, where each
+    /// entry has the name of the predicate, the names of its domains and
+    /// the table as nested arrays indexed by the coordinates. True cells are
+    /// written as 1, false cells as 0, unassigned cells as null and missing
+    /// cells as the string "missing". Meant to be called at a model, when
+    /// `get_status` is true.
+    pub fn model_to_json(&self) -> String {
+        let mut out = String::from("[");
+        for (idx, pred) in self.predicates.iter().enumerate() {
+            if idx != 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            push_json_string(&mut out, &pred.name);
+            out.push_str(",\"domains\":[");
+            for (idx, dom) in pred.domains.iter().enumerate() {
+                if idx != 0 {
+                    out.push(',');
+                }
+                push_json_string(&mut out, &dom.name);
+            }
+            out.push_str("],\"table\":");
+            let mut coordinates = Vec::with_capacity(pred.arity());
+            self.push_json_table(&mut out, pred, &mut coordinates);
+            out.push('}');
+        }
+        out.push(']');
+        out
+    }
+
+    // Writes the part of the table of the predicate whose first coordinates
+    // are the given ones.
+    fn push_json_table(&self, out: &mut String, pred: &Predicate, coordinates: &mut Vec<usize>) {
+        let axis = coordinates.len();
+        if axis == pred.arity() {
+            let val = self.state.assignment.get(pred.position(coordinates));
+            out.push_str(match Cell::from(val) {
+                Cell::True => "1",
+                Cell::False => "0",
+                Cell::Undef => "null",
+                Cell::Missing => "\"missing\"",
+            });
+            return;
+        }
+        out.push('[');
+        for coordinate in 0..pred.domains[axis].size {
+            if coordinate != 0 {
+                out.push(',');
+            }
+            coordinates.push(coordinate);
+            self.push_json_table(out, pred, coordinates);
+            coordinates.pop();
+        }
+        out.push(']');
+    }
+
+    /// Returns the problem as a DIMACS CNF formula, where the cell at
+    /// position `i` is the variable `i + 1`. Every instance of the clauses,
+    /// every lex-leader clause of the symmetry witness and every block of
+    /// the exists becomes a clause, the functions give
+    /// an exist and the pairwise exclusions of their blocks, and the values set
+    /// with `set_value` or `set_equality` become unit clauses. Each block of
+    /// a parity becomes a chain of exclusive ors, whose intermediate values
+    /// are auxiliary variables numbered after the cells. Soft and learned
+    /// clauses are not exported.
+    pub fn export_dimacs(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        let mut push_line = |literals: &mut dyn Iterator<Item = (bool, usize)>| {
+            let mut line = String::new();
+            for (sign, bvar) in literals {
+                line.push_str(&format!("{}{} ", if sign { "" } else { "-" }, bvar + 1));
+            }
+            line.push('0');
+            lines.push(line);
+        };
+
+        for cla in self.clauses.iter() {
+            for pos in 0..cla.shape.volume() {
+                let bvars = cla.cell(pos).iter().copied();
+                push_line(&mut cla.literals.iter().map(|lit| lit.sign).zip(bvars));
+            }
+        }
+        for lrn in self.lex_clauses.iter() {
+            push_line(&mut lrn.literals.iter().copied());
+        }
+        for ext in self.exists.iter() {
+            for block in ext.blocks() {
+                push_line(&mut block.iter().map(|&bvar| (true, bvar)));
+            }
+        }
+        for fun in self.functions.iter() {
+            for block in fun.blocks() {
+                push_line(&mut block.iter().map(|&bvar| (true, bvar)));
+                for (idx, &bvar1) in block.iter().enumerate() {
+                    for &bvar2 in block[idx + 1..].iter() {
+                        push_line(&mut [(false, bvar1), (false, bvar2)].into_iter());
+                    }
+                }
+            }
+        }
+        let mut auxiliary = self.state.assignment.len();
+        for par in self.parities.iter() {
+            for block in par.blocks() {
+                let (&last, rest) = block.split_last().unwrap();
+                // the variable that is true if the cells before the last one
+                // have an odd number of true cells
+                let mut odd = None;
+                for &bvar in rest.iter() {
+                    odd = Some(match odd {
+                        None => bvar,
+                        Some(prev) => {
+                            for (s1, s2, s3) in [
+                                (false, true, true),
+                                (false, false, false),
+                                (true, false, true),
+                                (true, true, false),
+                            ] {
+                                push_line(
+                                    &mut [(s1, auxiliary), (s2, prev), (s3, bvar)].into_iter(),
+                                );
+                            }
+                            auxiliary += 1;
+                            auxiliary - 1
+                        }
+                    });
+                }
+                match odd {
+                    None => push_line(&mut std::iter::once((!par.even, last))),
+                    Some(odd) => {
+                        push_line(&mut [(true, odd), (!par.even, last)].into_iter());
+                        push_line(&mut [(false, odd), (par.even, last)].into_iter());
+                    }
+                }
+            }
+        }
+        for step in self.state.steps.iter() {
+            if let Reason::Initial = step.reason {
+                let sign = self.state.assignment.get(step.bvar) == BOOL_TRUE;
+                push_line(&mut std::iter::once((sign, step.bvar)));
+            }
+        }
+        if self.contradiction {
+            push_line(&mut std::iter::empty());
+        }
+        debug_assert_eq!(auxiliary, self.dimacs_variables());
+
+        let mut out = format!("p cnf {} {}\n", auxiliary, lines.len());
+        for line in lines {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Returns the number of variables of `export_dimacs`, which are the
+    /// cells followed by the auxiliary variables of the parities.
+    fn dimacs_variables(&self) -> usize {
+        let auxiliary: usize = self
+            .parities
+            .iter()
+            .flat_map(|par| par.blocks())
+            .map(|block| block.len().saturating_sub(2))
+            .sum();
+        self.state.assignment.len() + auxiliary
+    }
+
+    /// Assigns the cells from a model of an external SAT solver given as
+    /// signed DIMACS variables, numbered as in `export_dimacs`. Zeros and the
+    /// auxiliary variables are ignored, so the output of minisat can be
+    /// passed as is. Cells that
+    /// already have the same value are skipped. If a variable is out of
+    /// range or conflicts with the current value of its cell, then none of
+    /// the cells are assigned. The clauses are evaluated at the end, so
+    /// `get_status` can be checked right after.
+    pub fn import_dimacs_model(&mut self, model: &[i32]) -> Result<(), ImportError> {
+        let start = self.state.steps.len();
+        let variables = self.dimacs_variables();
+        for &index in model.iter().filter(|&&index| index != 0) {
+            let bvar = index.unsigned_abs() as usize - 1;
+            if bvar >= variables {
+                self.state.unwind(start);
+                return Err(ImportError::Range { index, variables });
+            } else if bvar >= self.state.assignment.len() {
+                continue;
+            }
+            let val = self.state.assignment.get(bvar);
+            if val == BOOL_UNDEF1 {
+                self.state.assign(bvar, index > 0, Reason::Initial);
+            } else if (val == BOOL_TRUE) != (index > 0) {
+                self.state.unwind(start);
+                return Err(ImportError::Conflict { index });
+            }
+        }
+        self.evaluate_all();
+        Ok(())
+    }
+
+    /// Builds a solver on the disjoint union of the domains of the given
+    /// predicate in the two solvers, where the predicate has the values of
+    /// the first solver on the first part, of the second solver on the
+    /// second part, and is false on all mixed tuples. The two solvers must
+    /// have the same domain names and predicates, and all axes of the given
+    /// predicate must be over a single domain. Only this predicate and its
+    /// domain are carried over to the new solver, the other predicates and
+    /// all constraints are dropped.
+    pub fn disjoint_union(&self, other: &Solver, predicate: &Rc<Predicate>) -> Solver {
+        let domains = |sol: &Solver| {
+            let mut names: Vec<String> = sol.domains.iter().map(|dom| dom.name.clone()).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            domains(self),
+            domains(other),
+            "the domains of the two solvers differ"
+        );
+        let predicates = |sol: &Solver| {
+            let mut preds: Vec<(String, Vec<String>)> = sol
+                .predicates
+                .iter()
+                .map(|pred| {
+                    let names = pred.domains.iter().map(|dom| dom.name.clone()).collect();
+                    (pred.name.clone(), names)
+                })
+                .collect();
+            preds.sort();
+            preds
+        };
+        assert_eq!(
+            predicates(self),
+            predicates(other),
+            "the predicates of the two solvers differ"
+        );
+        assert!(
+            self.predicates
+                .iter()
+                .any(|pred| Rc::ptr_eq(pred, predicate)),
+            "the predicate {} is not of the first solver",
+            predicate.name
+        );
+
+        let other_pred = other
+            .predicates
+            .iter()
+            .find(|pred| pred.name == predicate.name)
+            .unwrap_or_else(|| panic!("predicate {} is missing", predicate.name));
+        assert_eq!(predicate.arity(), other_pred.arity());
+        for pred in [predicate, other_pred] {
+            assert!(pred
+                .domains
+                .iter()
+                .all(|dom| Rc::ptr_eq(dom, &pred.domains[0])));
+        }
+
+        let dom1 = &predicate.domains[0];
+        let dom2 = &other_pred.domains[0];
+        let mut sol: Solver = Default::default();
+        let dom = sol.add_domain(dom1.name.clone(), dom1.size + dom2.size);
+        let pred = sol.add_variable(predicate.name.clone(), vec![dom; predicate.arity()]);
+
+        let mut coordinates = vec![0; pred.arity()];
+        let mut part = vec![0; pred.arity()];
+        for pos in pred.shape.positions() {
+            pred.shape.coordinates(pos, &mut coordinates);
+            let val = if coordinates.iter().all(|&c| c < dom1.size) {
+                self.get_value(predicate, &coordinates)
+            } else if coordinates.iter().all(|&c| c >= dom1.size) {
+                for (p, &c) in part.iter_mut().zip(coordinates.iter()) {
+                    *p = c - dom1.size;
+                }
+                other.get_value(other_pred, &part)
+            } else {
+                BOOL_FALSE
+            };
+            if val == BOOL_TRUE || val == BOOL_FALSE {
+                sol.set_value(val == BOOL_TRUE, &pred, &coordinates);
+            }
+        }
+        sol
+    }
+
+    /// Returns the decision level at which the given boolean variable was
+    /// assigned, or `None` if it is undefined.
+    pub fn level_of(&self, bvar: usize) -> Option<usize> {
+        if self.state.assignment.get(bvar) == BOOL_UNDEF1 {
+            return None;
+        }
+        self.state
+            .steps
+            .iter()
+            .rev()
+            .find(|step| step.bvar == bvar)
+            .map(|step| step.level)
+    }
+
+    fn lookup_var(&self, bvar: usize) -> &Predicate {
+        self.try_lookup_var(bvar)
+            .unwrap_or_else(|| panic!("invalid boolean variable {}", bvar))
+    }
+
+    /// Returns the predicate whose table contains the given boolean variable.
+    /// The tables are allocated one after the other, so this is a binary
+    /// search over their start positions.
+    pub fn try_lookup_var(&self, bvar: usize) -> Option<&Rc<Predicate>> {
+        let idx = self
+            .predicates
+            .partition_point(|pred| pred.variables().start <= bvar);
+        let pred = self.predicates.get(idx.checked_sub(1)?)?;
+        if pred.variables().contains(&bvar) {
+            Some(pred)
+        } else {
+            None
+        }
+    }
+
+    fn format_var(&self, bvar: usize) -> String {
+        let bval = self.state.assignment.get(bvar);
+        assert!(bval == BOOL_FALSE || bval == BOOL_TRUE);
+
+        let sign = if bval == BOOL_TRUE { '+' } else { '-' };
+        let rvar = match (self.try_lookup_var(bvar), &self.symmetry) {
+            (Some(rvar), _) => rvar,
+            // the auxiliary cells of the lex-leader clauses
+            (None, Some(witness)) if bvar >= self.lex_cells => {
+                return format!("{}{}_lex[{}]", sign, witness.name, bvar - self.lex_cells);
+            }
+            _ => panic!("invalid boolean variable {}", bvar),
+        };
+        let mut coordinates = vec![0; rvar.arity()];
+        rvar.coordinates(bvar, &mut coordinates);
+
+        format!(
+            "{}{}{}",
+            sign,
+            rvar.name,
+            Domain::format_coordinates(&rvar.domains, &coordinates),
+        )
+    }
+
+    /// Returns the assigned cells in the order of the trail, each with its
+    /// reason and the decision level it was assigned at, so the implication
+    /// graph can be rebuilt outside of the solver.
+    pub fn trail(&self) -> impl Iterator<Item = (String, &Reason, usize)> + '_ {
+        self.state
+            .steps
+            .iter()
+            .map(|step| (self.format_var(step.bvar), &step.reason, step.level))
+    }
+
+    fn format_reason(&self, reason: &Reason) -> String {
+        match reason {
+            Reason::Initial => "initial".into(),
+            Reason::Decision => "decision".into(),
+            Reason::Clause(vars) => vars
+                .iter()
+                .map(|&bvar| self.format_var(bvar))
+                .collect::<Vec<String>>()
+                .join(" "),
+            Reason::Exists(vars) => {
+                let vars: Vec<String> = vars.iter().map(|&bvar| self.format_var(bvar)).collect();
+                format!("exists {}", vars.join(" "))
+            }
+        }
+    }
+
+    pub fn print(&self) {
+        for dom in self.domains.iter() {
+            println!("{}", dom);
+        }
+        for pred in self.predicates.iter() {
+            println!("{}", pred);
+            self.state.print_table(pred);
+        }
+        for step in self.state.steps.iter() {
+            println!(
+                "step {} from {} at level {}",
+                self.format_var(step.bvar),
+                self.format_reason(&step.reason),
+                step.level
+            );
+        }
+        for cla in self.clauses.iter() {
+            println!("{}", cla);
+            if self.verbosity >= Verbosity::Trace {
+                println!("density {:.3}", cla.density());
+            }
+            if let Some(coordinates) = cla.failure_coordinates() {
+                println!("failure {}", cla.format_instance(&coordinates));
+            }
+        }
+        for ext in self.exists.iter() {
+            // println!("exist {}", ext);
+            println!(
+                "{} = {}",
+                ext,
+                BOOL_FORMAT2[ext.get_status(&self.state).idx()]
+            );
+            if let Some(failure) = ext.get_failure(&self.state) {
+                println!("failure {}", self.format_var(failure));
+            }
+        }
+        for fun in self.functions.iter() {
+            println!(
+                "{} = {}",
+                fun,
+                BOOL_FORMAT2[fun.get_status(&self.state).idx()]
+            );
+            if let Some(failure) = fun.get_failure(&self.state) {
+                let failure: Vec<String> = failure
+                    .into_iter()
+                    .map(|bvar| self.format_var(bvar))
+                    .collect();
+                println!("failure {}", failure.join(" "));
+            }
+        }
+        for par in self.parities.iter() {
+            println!(
+                "{} = {}",
+                par,
+                BOOL_FORMAT2[par.get_status(&self.state).idx()]
+            );
+            if let Some(failure) = par.get_failure(&self.state) {
+                println!("failure {}", self.format_var(failure));
+            }
+        }
+        if self.verbosity >= Verbosity::Trace {
+            println!("steps = {:?}", self.state.steps);
+            println!("levels = {:?}", self.state.levels);
+        }
+        println!(
+            "clauses status = {}",
+            BOOL_FORMAT2[self.get_clauses_status().idx()]
+        );
+        println!(
+            "exists status = {}",
+            BOOL_FORMAT2[self.get_exists_status().idx()]
+        );
+    }
+
+    /// Prints the predicate and coordinates that each boolean variable
+    /// denotes together with its current value.
+    pub fn print_var_legend(&self) {
+        for pred in self.predicates.iter() {
+            let mut coordinates = vec![0; pred.arity()];
+            for bvar in pred.variables() {
+                pred.coordinates(bvar, &mut coordinates);
+                println!(
+                    "{} = {}{:?} ({})",
+                    bvar,
+                    pred.name,
+                    coordinates,
+                    BOOL_FORMAT2[self.state.assignment.get(bvar).idx()]
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random(mut seed: u32, len: usize) -> Vec<u32> {
+        assert!(seed != 0);
+        let mut vec: Vec<u32> = Default::default();
+        while vec.len() < len {
+            let msb = (seed as i32) < 0;
+            seed <<= 1;
+            if msb {
+                seed ^= 0x04c11db7;
+            }
+            vec.push(seed);
+        }
+        vec
+    }
+
+    // The value of a single block computed the slow way.
+    fn block_value(assignment: &Buffer2, start: usize, block: usize) -> Bit2 {
+        let mut trues = 0;
+        let mut undefs = 0;
+        for pos in start..(start + block) {
+            let val = assignment.get(pos);
+            if val == BOOL_TRUE {
+                trues += 1;
+            } else if val == BOOL_UNDEF1 {
+                undefs += 1;
+            }
+        }
+        if trues > 0 {
+            BOOL_TRUE
+        } else if undefs == 0 {
+            BOOL_FALSE
+        } else if undefs == 1 {
+            BOOL_UNDEF1
+        } else {
+            BOOL_UNDEF2
+        }
+    }
+
+    fn block_values(assignment: &Buffer2, predicate: &Predicate) -> Vec<(usize, Bit2)> {
+        let shape = &predicate.shape;
+        let block = shape.length(shape.dimension() - 1);
+        shape
+            .positions()
+            .step_by(block)
+            .map(|start| (start, block_value(assignment, start, block)))
+            .collect()
+    }
+
+    fn exist_status(values: &[(usize, Bit2)]) -> Bit2 {
+        if values.iter().any(|&(_, val)| val == BOOL_FALSE) {
+            BOOL_FALSE
+        } else if values.iter().any(|&(_, val)| val == BOOL_UNDEF1) {
+            BOOL_UNDEF1
+        } else if values.iter().any(|&(_, val)| val == BOOL_UNDEF2) {
+            BOOL_UNDEF2
+        } else {
+            BOOL_TRUE
+        }
+    }
+
+    #[test]
+    fn trail_capacity() {
+        let state = State::with_capacity(10);
+        assert!(state.steps.capacity() >= 10 && state.levels.capacity() >= 10);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        sol.state.reserve_trail();
+        let capacity = sol.state.steps.capacity();
+        assert!(capacity >= 27);
+        while sol.state.make_decision() {}
+        assert_eq!(sol.state.steps.len(), 27);
+        assert_eq!(sol.state.steps.capacity(), capacity);
+    }
+
+    #[test]
+    fn level_of() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_clause(vec![
+            (false, p.clone(), vec![0]),
+            (true, q.clone(), vec![0]),
+        ]);
+        sol.set_value(true, &q, &[2]);
+
+        let p0 = p.shape.position([0].iter());
+        let p1 = p.shape.position([1].iter());
+        let q0 = q.shape.position([0].iter());
+        let q1 = q.shape.position([1].iter());
+        let q2 = q.shape.position([2].iter());
+        assert_eq!(sol.level_of(q2), Some(0));
+        assert_eq!(sol.level_of(p0), None);
+
+        assert!(sol.state.make_decision());
+        sol.propagate_clauses();
+        assert_eq!(sol.level_of(p0), Some(1));
+        assert_eq!(sol.level_of(q0), Some(1));
+
+        assert!(sol.state.make_decision());
+        sol.propagate_clauses();
+        assert_eq!(sol.level_of(p1), Some(2));
+        assert_eq!(sol.level_of(q1), Some(2));
+
+        assert!(sol.state.next_decision());
+        assert_eq!(sol.level_of(p1), Some(2));
+        assert_eq!(sol.level_of(q1), None);
+        assert_eq!(sol.level_of(q0), Some(1));
+        assert_eq!(sol.level_of(q2), Some(0));
+    }
+
+    #[test]
+    fn satisfied_instances() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        sol.set_equality(&equ);
+
+        assert_eq!(sol.add_clause(vec![(true, equ.clone(), vec![0, 0])]), 3);
+        assert_eq!(sol.clauses.len(), 0);
+
+        let count = sol.add_clause(vec![
+            (false, mul.clone(), vec![0, 1, 2]),
+            (false, mul.clone(), vec![0, 1, 3]),
+            (true, equ.clone(), vec![2, 3]),
+        ]);
+        assert_eq!(count, 27);
+        assert_eq!(sol.clauses.len(), 1);
+
+        assert!(sol.state.make_decision());
+        assert_eq!(sol.add_clause(vec![(true, equ, vec![0, 0])]), 0);
+        assert_eq!(sol.clauses.len(), 2);
+    }
+
+    fn count_parities(constraints: &[(usize, bool)]) -> usize {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        for &(axis, even) in constraints {
+            sol.add_parity(&rel, axis, even);
+        }
+        sol.count_all()
+    }
+
+    #[test]
+    fn axis_blocks() {
+        let mut sol: Solver = Default::default();
+        let two = sol.add_domain("two".into(), 2);
+        let three = sol.add_domain("three".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![two, three.clone()]);
+        let blocks = AxisBlocks::new(&rel, 0);
+        assert_eq!(blocks.iter().collect::<Vec<_>>(), [[0, 3], [1, 4], [2, 5]]);
+        let blocks = AxisBlocks::new(&rel, 1);
+        assert_eq!(blocks.iter().collect::<Vec<_>>(), [[0, 1, 2], [3, 4, 5]]);
+        let sym = sol.add_symmetric_relation("sym".into(), three);
+        let blocks = AxisBlocks::new(&sym, 1);
+        assert_eq!(
+            blocks.iter().collect::<Vec<_>>(),
+            [[6, 7, 8], [7, 9, 10], [8, 10, 11]]
+        );
+    }
+
+    #[test]
+    fn parity() {
+        for constraints in [
+            vec![(1, true)],
+            vec![(0, false)],
+            vec![(1, true), (0, false)],
+            vec![(1, true), (0, true)],
+            vec![(1, false), (0, false)],
+        ] {
+            let mut count = 0;
+            for bits in 0u32..512 {
+                let holds = constraints.iter().all(|&(axis, even)| {
+                    (0..3).all(|i| {
+                        let mut ones = 0;
+                        for j in 0..3 {
+                            let (x, y) = if axis == 1 { (i, j) } else { (j, i) };
+                            ones += (bits >> (3 * x + y)) & 1;
+                        }
+                        (ones % 2 == 0) == even
+                    })
+                });
+                count += holds as usize;
+            }
+            assert_eq!(count_parities(&constraints), count);
+        }
+    }
+
+    #[test]
+    fn exist_blocks() {
+        let mut sol: Solver = Default::default();
+        let dom2 = sol.add_domain("dom2".into(), 2);
+        let dom3 = sol.add_domain("dom3".into(), 3);
+        let dom5 = sol.add_domain("dom5".into(), 5);
+        let dom17 = sol.add_domain("dom17".into(), 17);
+        let p3 = sol.add_variable("p3".into(), vec![dom3.clone(), dom3.clone()]);
+        let p5 = sol.add_variable("p5".into(), vec![dom2.clone(), dom5.clone()]);
+        let p17 = sol.add_variable("p17".into(), vec![dom3, dom2, dom17]);
+        sol.add_exist(p3);
+        sol.add_exist(p5);
+        sol.add_exist(p17);
+
+        let len = sol.state.assignment.len();
+        for round in 0..500 {
+            let sparsity = 2 + (round % 20);
+            let vec = random(0x12345678 + round, len);
+            for (pos, &a) in vec.iter().enumerate() {
+                let val = if a % sparsity != 0 {
+                    BOOL_FALSE
+                } else if (a >> 16) & 1 != 0 {
+                    BOOL_TRUE
                 } else {
-                    assert!(Rc::ptr_eq(dom1, dom2.as_ref().unwrap()));
+                    BOOL_UNDEF1
+                };
+                sol.state.assignment.set(pos, val);
+            }
+
+            for ext in sol.exists.iter() {
+                sol.state.steps.clear();
+                let before = sol.state.assignment.clone();
+                let values = block_values(&before, &ext.predicate);
+                let status = exist_status(&values);
+                assert_eq!(ext.get_status(&sol.state), status);
+
+                let failure = values
+                    .iter()
+                    .find(|&&(_, val)| val == BOOL_FALSE)
+                    .map(|&(start, _)| start);
+                assert_eq!(ext.get_failure(&sol.state), failure);
+
+                let mut expected = before.clone();
+                for &(start, val) in values.iter() {
+                    if val == BOOL_FALSE {
+                        break;
+                    } else if val == BOOL_UNDEF1 {
+                        let block = values.len();
+                        let block = ext.predicate.shape.volume() / block;
+                        let unit = (start..(start + block))
+                            .find(|&pos| before.get(pos) == BOOL_UNDEF1)
+                            .unwrap();
+                        expected.set(unit, BOOL_TRUE);
+                    }
+                }
+
+                let result = ext.propagate(&mut sol.state);
+                assert_eq!(result, status);
+                assert_eq!(sol.state.assignment, expected);
+                if status != BOOL_FALSE {
+                    let values = block_values(&sol.state.assignment, &ext.predicate);
+                    assert!(values.iter().all(|&(_, val)| val != BOOL_UNDEF1));
                 }
+                sol.state.assignment = before;
             }
         }
-        let domains: Vec<Rc<Domain>> = domains.into_iter().map(|dom| dom.unwrap()).collect();
+    }
 
-        let shape = Shape::new(domains.iter().map(|dom| dom.size).collect(), 0);
-        let literals: Vec<Literal> = literals
+    #[test]
+    fn exist_axis() {
+        let mut sol: Solver = Default::default();
+        let dom2 = sol.add_domain("dom2".into(), 2);
+        let dom3 = sol.add_domain("dom3".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![dom2, dom3]);
+        sol.add_exist_axis(&rel, 0);
+        assert_eq!(sol.exists[0].to_string(), "exist rel along 0");
+        let pos = |i: usize, j: usize| rel.shape.position([i, j].iter());
+        let blocks: Vec<&[usize]> = sol.exists[0].blocks().collect();
+        assert_eq!(
+            blocks,
+            [
+                [pos(0, 0), pos(1, 0)],
+                [pos(0, 1), pos(1, 1)],
+                [pos(0, 2), pos(1, 2)]
+            ]
+        );
+
+        sol.set_value(false, &rel, &[0, 0]);
+        sol.set_value(true, &rel, &[0, 2]);
+        assert_eq!(sol.exists[0].propagate(&mut sol.state), BOOL_UNDEF1);
+        assert_eq!(sol.get_value(&rel, &[1, 0]), BOOL_TRUE);
+        assert_eq!(sol.exists[0].get_status(&sol.state), BOOL_UNDEF2);
+
+        sol.set_value(false, &rel, &[0, 1]);
+        sol.set_value(false, &rel, &[1, 1]);
+        assert_eq!(sol.exists[0].get_status(&sol.state), BOOL_FALSE);
+        assert_eq!(sol.exists[0].get_failure(&sol.state), Some(pos(0, 1)));
+    }
+
+    #[test]
+    fn function_blocks() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set.clone()]);
+        sol.add_function(&rel, 0);
+        assert_eq!(sol.functions[0].to_string(), "function rel along 0");
+
+        sol.set_value(true, &rel, &[1, 0]);
+        sol.set_value(false, &rel, &[0, 1]);
+        sol.set_value(false, &rel, &[1, 1]);
+        assert_eq!(sol.propagate_functions(), BOOL_UNDEF1);
+        assert_eq!(sol.get_value(&rel, &[0, 0]), BOOL_FALSE);
+        assert_eq!(sol.get_value(&rel, &[2, 0]), BOOL_FALSE);
+        assert_eq!(sol.get_value(&rel, &[2, 1]), BOOL_TRUE);
+        assert_eq!(sol.get_functions_status(), BOOL_UNDEF2);
+
+        sol.set_value(true, &rel, &[0, 2]);
+        sol.set_value(true, &rel, &[2, 2]);
+        assert_eq!(sol.get_functions_status(), BOOL_FALSE);
+        let pos = |i: usize, j: usize| rel.shape.position([i, j].iter());
+        let failure = sol.functions[0].get_failure(&sol.state);
+        assert_eq!(failure, Some(vec![pos(0, 2), pos(2, 2)]));
+
+        // a semigroup whose operation is posted as a function
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        sol.set_equality(&equ);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        sol.add_function(&mul, 2);
+        sol.add_clause(vec![
+            (false, mul.clone(), vec![0, 1, 3]),
+            (false, mul.clone(), vec![3, 2, 4]),
+            (false, mul.clone(), vec![1, 2, 5]),
+            (false, mul.clone(), vec![0, 5, 6]),
+            (true, equ, vec![4, 6]),
+        ]);
+        let stats = sol.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!(stats.solutions, 113);
+        assert!(stats.function_conflicts > 0);
+    }
+
+    #[test]
+    fn symmetric_relation() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_symmetric_relation("rel".into(), set);
+        assert_eq!(sol.state.assignment.len(), 6);
+        sol.set_value(true, &rel, &[0, 1]);
+        sol.set_value(false, &rel, &[2, 0]);
+        assert_eq!(sol.get_value(&rel, &[1, 0]), BOOL_TRUE);
+        assert_eq!(sol.get_value(&rel, &[0, 2]), BOOL_FALSE);
+        sol.for_each_model(|sol| {
+            for i in 0..3 {
+                for j in 0..3 {
+                    assert_eq!(sol.get_value(&rel, &[i, j]), sol.get_value(&rel, &[j, i]));
+                }
+            }
+        });
+
+        // the symmetric relations with even rows, which are determined by
+        // the cells above the diagonal
+        let mut sol1: Solver = Default::default();
+        let set = sol1.add_domain("set".into(), 4);
+        let rel = sol1.add_symmetric_relation("rel".into(), set);
+        sol1.add_parity(&rel, 1, true);
+        let mut sol2: Solver = Default::default();
+        let set = sol2.add_domain("set".into(), 4);
+        let rel = sol2.add_variable("rel".into(), vec![set.clone(), set]);
+        sol2.add_symmetric(&rel);
+        sol2.add_parity(&rel, 1, true);
+        assert_eq!(sol1.count_all(), 64);
+        assert_eq!(sol2.count_all(), 64);
+    }
+
+    #[test]
+    fn repeated_variables() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        sol.add_clause(vec![(true, mul.clone(), vec![0, 0, 0])]);
+        assert_ne!(sol.propagate_clauses(), BOOL_FALSE);
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    let val = sol.get_value(&mul, &[x, y, z]);
+                    if x == y && y == z {
+                        assert_eq!(val, BOOL_TRUE);
+                    } else {
+                        assert!(val != BOOL_FALSE && val != BOOL_TRUE);
+                    }
+                }
+            }
+        }
+
+        sol.add_clause(vec![(false, mul.clone(), vec![0, 0, 1])]);
+        assert_eq!(sol.propagate_clauses(), BOOL_FALSE);
+    }
+
+    #[test]
+    fn get_value() {
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_group(2);
+        sol.set_value(true, handles.one.as_ref().unwrap(), &[0]);
+        sol.set_value(true, &handles.mul, &[1, 1, 0]);
+        assert_eq!(sol.get_value(&handles.mul, &[1, 0, 1]), BOOL_UNDEF1);
+        assert_eq!(sol.preprocess(), Ok(()));
+        assert_eq!(sol.get_value(&handles.mul, &[1, 0, 1]), BOOL_TRUE);
+        assert_eq!(sol.get_value(&handles.mul, &[1, 0, 0]), BOOL_FALSE);
+    }
+
+    #[test]
+    fn predicate_by_name() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set]);
+        assert!(Rc::ptr_eq(sol.predicate_by_name("equ").unwrap(), &equ));
+        assert!(Rc::ptr_eq(sol.predicate_by_name("one").unwrap(), &one));
+        assert!(sol.predicate_by_name("mul").is_none());
+    }
+
+    #[test]
+    fn named_domain() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_named_domain("set".into(), vec!["e".into(), "a".into()]);
+        let num = sol.add_domain("num".into(), 3);
+        assert_eq!(set.size(), 2);
+        assert_eq!(set.element_name(1), "a");
+        assert_eq!(num.element_name(1), "1");
+
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        let rel = sol.add_variable("rel".into(), vec![num]);
+        sol.set_value(true, &mul, &[0, 1, 1]);
+        sol.set_value(false, &rel, &[2]);
+        let pos = mul.shape.position([0, 1, 1].iter());
+        assert_eq!(sol.format_var(pos), "+mul[e, a, a]");
+        let pos = rel.shape.position([2].iter());
+        assert_eq!(sol.format_var(pos), "-rel[2]");
+    }
+
+    #[test]
+    fn verbosity() {
+        assert_eq!(Verbosity::from(0), Verbosity::Silent);
+        assert_eq!(Verbosity::from(1), Verbosity::Summary);
+        assert_eq!(Verbosity::from(2), Verbosity::Solutions);
+        assert_eq!(Verbosity::from(7), Verbosity::Trace);
+        assert!(Verbosity::Summary < Verbosity::Solutions);
+
+        let mut sol: Solver = Default::default();
+        assert_eq!(sol.verbosity, Verbosity::Silent);
+        sol.set_verbosity(2);
+        assert_eq!(sol.verbosity, Verbosity::Solutions);
+        sol.set_verbosity(Verbosity::Summary);
+        assert_eq!(sol.verbosity, Verbosity::Summary);
+    }
+
+    #[test]
+    fn search_stats() {
+        let mut sol: Solver = Default::default();
+        sol.define_group(3);
+        let stats = sol.search_all();
+        assert_eq!(stats.solutions, 3);
+        assert!(stats.decisions > 0);
+        assert!(stats.propagations > stats.decisions);
+
+        // the table determines everything else without decisions
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_group(2);
+        for x in 0..2 {
+            for y in 0..2 {
+                for z in 0..2 {
+                    sol.set_value((x + y) % 2 == z, &handles.mul, &[x, y, z]);
+                }
+            }
+        }
+        let stats = sol.search_all();
+        assert_eq!(stats.solutions, 1);
+        assert_eq!(stats.decisions, 0);
+        assert!(stats.propagations > 0);
+    }
+
+    #[test]
+    fn count_models() {
+        let mut sol: Solver = Default::default();
+        sol.define_group(4);
+        // the watched literals keep this test fast in debug builds
+        sol.set_watched_literals(true);
+        assert_eq!(sol.count_models(), 16);
+
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        assert!(sol.next_model());
+        assert_eq!(sol.count_models(), 7);
+    }
+
+    #[test]
+    fn unsat_core() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set.clone()]);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_clause(vec![
+            (false, rel.clone(), vec![0, 1]),
+            (true, rel, vec![1, 0]),
+        ]);
+        sol.add_clause(vec![(true, p.clone(), vec![0])]);
+        assert_eq!(sol.preprocess(), Ok(()));
+        assert_eq!(sol.unsat_core(), None);
+
+        sol.add_clause(vec![(false, p, vec![0]), (true, q.clone(), vec![0])]);
+        sol.add_clause(vec![(false, q, vec![0])]);
+        assert!(sol.preprocess().is_err());
+        let core = sol.unsat_core().unwrap();
+        assert_eq!(
+            core,
+            [
+                ClauseRef::Clause(1),
+                ClauseRef::Clause(2),
+                ClauseRef::Clause(3)
+            ]
+        );
+        let formulas: Vec<String> = core.into_iter().map(|c| sol.format_constraint(c)).collect();
+        assert_eq!(formulas, ["+p(x0)", "-p(x0) +q(x0)", "-q(x0)"]);
+
+        // the exists are listed with the clauses
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_exist(q.clone());
+        sol.add_exist(p.clone());
+        sol.add_clause(vec![(false, p, vec![0])]);
+        assert!(sol.preprocess().is_err());
+        assert_eq!(
+            sol.unsat_core(),
+            Some(vec![ClauseRef::Clause(0), ClauseRef::Exist(1)])
+        );
+
+        // a refutation by search that needs decisions
+        let build = |sol: &mut Solver| {
+            let one = sol.add_domain("one".into(), 1);
+            let c = sol.add_variable("c".into(), vec![one.clone()]);
+            let d = sol.add_variable("d".into(), vec![one.clone()]);
+            let a = sol.add_variable("a".into(), vec![one.clone()]);
+            let b = sol.add_variable("b".into(), vec![one]);
+            sol.add_clause(vec![(true, c, vec![0]), (true, d, vec![0])]);
+            for (sa, sb) in [(true, true), (true, false), (false, true), (false, false)] {
+                sol.add_clause(vec![(sa, a.clone(), vec![0]), (sb, b.clone(), vec![0])]);
+            }
+        };
+        let mut sol: Solver = Default::default();
+        build(&mut sol);
+        assert_eq!(sol.preprocess(), Ok(()));
+        assert_eq!(sol.search_all().solutions, 0);
+        assert_eq!(sol.unsat_core(), None);
+
+        let mut sol: Solver = Default::default();
+        build(&mut sol);
+        sol.set_core_tracking(true);
+        let stats = sol.search_all();
+        assert!(stats.solutions == 0 && stats.max_level > 0);
+        let core: Vec<ClauseRef> = (1..5).map(ClauseRef::Clause).collect();
+        assert_eq!(sol.unsat_core(), Some(core));
+
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        sol.set_core_tracking(true);
+        assert_eq!(sol.search_all().solutions, 8);
+        assert_eq!(sol.unsat_core(), None);
+    }
+
+    #[test]
+    fn block_model() {
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        assert!(sol.next_model());
+        sol.block_model(None);
+        assert_eq!(sol.count_models(), 7);
+
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        let mut count = 0;
+        while sol.next_model() {
+            sol.block_model(None);
+            count += 1;
+        }
+        assert_eq!(count, 8);
+
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        assert!(sol.next_model());
+        sol.block_model(Some(&[handles.equ]));
+        assert!(!sol.next_model());
+
+        // the models before a blocked one are not found again
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        let mut models = vec![];
+        while sol.next_model() {
+            models.push(sol.model_to_json());
+            if models.len() % 3 == 2 {
+                sol.block_model(None);
+            }
+        }
+        assert_eq!(models.len(), 8);
+        models.sort();
+        models.dedup();
+        assert_eq!(models.len(), 8);
+    }
+
+    #[test]
+    fn exist_learning() {
+        for size in 2..4 {
+            let mut sol1: Solver = Default::default();
+            sol1.define_group(size);
+            let mut sol2: Solver = Default::default();
+            sol2.define_group(size);
+            sol2.set_exist_learning(true);
+            assert_eq!(sol1.count_all(), sol2.count_all());
+            assert!(sol1.learnts.is_empty());
+            assert!(!sol2.learnts.is_empty());
+        }
+
+        let mut sol1: Solver = Default::default();
+        sol1.define_monoid(3);
+        let mut sol2: Solver = Default::default();
+        sol2.define_monoid(3);
+        sol2.set_exist_learning(true);
+        assert_eq!(sol1.count_all(), sol2.count_all());
+    }
+
+    #[test]
+    fn set_values() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+
+        sol.set_value(true, &rel, &[0, 0]);
+        assert_eq!(
+            sol.set_values(&rel, &[(vec![0, 1], true), (vec![0, 0], true)]),
+            Ok(())
+        );
+        assert_eq!(sol.get_value(&rel, &[0, 1]), BOOL_TRUE);
+
+        let entries = [
+            (vec![1, 1], true),
+            (vec![1, 2], false),
+            (vec![0, 0], false),
+            (vec![2, 2], true),
+        ];
+        assert_eq!(
+            sol.set_values(&rel, &entries),
+            Err((2, AssignError::Conflict { value: true }))
+        );
+        assert_eq!(sol.get_value(&rel, &[1, 1]), BOOL_UNDEF1);
+        assert_eq!(sol.get_value(&rel, &[1, 2]), BOOL_UNDEF1);
+        assert_eq!(sol.state.steps.len(), 2);
+
+        assert_eq!(
+            sol.set_values(&rel, &[(vec![2, 3], true)]),
+            Err((
+                0,
+                AssignError::Range {
+                    axis: 1,
+                    coordinate: 3,
+                    length: 3
+                }
+            ))
+        );
+        assert_eq!(
+            sol.set_values(&rel, &[(vec![2], true)]),
+            Err((
+                0,
+                AssignError::Arity {
+                    expected: 2,
+                    found: 1
+                }
+            ))
+        );
+
+        let entries = [(vec![2, 0], true), (vec![2, 1], true), (vec![2, 0], false)];
+        assert_eq!(
+            sol.set_values(&rel, &entries),
+            Err((2, AssignError::Duplicate { entry: 0 }))
+        );
+        assert_eq!(sol.get_value(&rel, &[2, 0]), BOOL_UNDEF1);
+        assert_eq!(sol.state.steps.len(), 2);
+    }
+
+    #[test]
+    fn clause_predicates() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        sol.add_clause(vec![
+            (false, mul.clone(), vec![0, 1, 2]),
+            (false, mul.clone(), vec![0, 1, 3]),
+            (true, equ.clone(), vec![2, 3]),
+        ]);
+        let preds: Vec<&Rc<Predicate>> = sol.clauses[0].predicates().collect();
+        assert_eq!(preds.len(), 2);
+        assert!(Rc::ptr_eq(preds[0], &equ) && Rc::ptr_eq(preds[1], &mul));
+    }
+
+    #[test]
+    fn failure_decoded() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.set_equality(&equ);
+        sol.set_value(true, &one, &[0]);
+        sol.set_value(true, &one, &[1]);
+        sol.add_clause(vec![
+            (false, one.clone(), vec![0]),
+            (false, one.clone(), vec![1]),
+            (true, equ.clone(), vec![0, 1]),
+        ]);
+        assert_eq!(
+            sol.failure_decoded(),
+            Some(Failure {
+                clause: "+equ(x0,x1) -one(x0) -one(x1)".into(),
+                literals: vec![
+                    (true, "equ".into(), vec![0, 1]),
+                    (false, "one".into(), vec![0]),
+                    (false, "one".into(), vec![1]),
+                ],
+            })
+        );
+
+        sol.clauses.clear();
+        sol.add_clause(vec![(true, equ.clone(), vec![0, 0])]);
+        assert_eq!(sol.failure_decoded(), None);
+    }
+
+    #[test]
+    fn format_instance() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set.clone()]);
+        sol.add_clause_terms(vec![
+            (
+                false,
+                mul.clone(),
+                vec![Term::Var(0), Term::Var(1), Term::Const(2)],
+            ),
+            (
+                true,
+                mul.clone(),
+                vec![Term::Var(1), Term::Var(0), Term::Const(2)],
+            ),
+        ]);
+        let cla = &sol.clauses[0];
+        assert_eq!(cla.format_instance(&[0, 1]), "-mul(0,1,2) +mul(1,0,2)");
+        assert_eq!(cla.failure_coordinates(), None);
+
+        sol.set_value(true, &mul, &[1, 2, 2]);
+        sol.set_value(false, &mul, &[2, 1, 2]);
+        sol.evaluate_all();
+        let cla = &sol.clauses[0];
+        let coordinates = cla.failure_coordinates().unwrap();
+        assert_eq!(coordinates, vec![1, 2]);
+        assert_eq!(cla.format_instance(&coordinates), "-mul(1,2,2) +mul(2,1,2)");
+    }
+
+    #[test]
+    fn preprocess() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.set_equality(&equ);
+        sol.add_clause(vec![
+            (false, one.clone(), vec![0]),
+            (false, one.clone(), vec![1]),
+            (true, equ.clone(), vec![0, 1]),
+        ]);
+        sol.add_exist(one.clone());
+        sol.set_value(false, &one, &[0]);
+        assert_eq!(sol.preprocess(), Ok(()));
+        assert_eq!(sol.get_value(&one, &[1]), BOOL_TRUE);
+        assert_eq!(sol.count_all(), 1);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.add_exist(one.clone());
+        sol.set_value(false, &one, &[0]);
+        sol.set_value(false, &one, &[1]);
+        let conflict = sol.preprocess().unwrap_err();
+        assert_eq!(conflict, Conflict::Constraint("exist one".into()));
+        assert_eq!(conflict.to_string(), "the exist one fails");
+        assert_eq!(sol.count_all(), 0);
+        sol.set_value(true, &one, &[0]);
+        assert_eq!(sol.preprocess(), Err(conflict));
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.set_value(true, &one, &[0]);
+        sol.set_value(false, &one, &[0]);
+        assert_eq!(sol.preprocess(), Err(Conflict::Initial));
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.set_value(true, &one, &[1]);
+        sol.learnts.push(Learnt {
+            literals: vec![(false, 1)].into_boxed_slice(),
+        });
+        let conflict = sol.preprocess().unwrap_err();
+        assert_eq!(
+            conflict,
+            Conflict::Learnt(vec![(false, "one".into(), vec![1])])
+        );
+        assert_eq!(conflict.to_string(), "the learnt clause fails at -one[1]");
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.set_equality(&equ);
+        sol.set_value(true, &one, &[0]);
+        sol.set_value(true, &one, &[1]);
+        sol.add_clause(vec![
+            (false, one.clone(), vec![0]),
+            (false, one.clone(), vec![1]),
+            (true, equ.clone(), vec![0, 1]),
+        ]);
+        let conflict = sol.preprocess().unwrap_err();
+        assert_eq!(
+            conflict.to_string(),
+            "the clause +equ(x0,x1) -one(x0) -one(x1) fails at +equ[0, 1] -one[0] -one[1]"
+        );
+    }
+
+    #[test]
+    fn cancellable() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_symmetric(&rel);
+
+        let cancel = AtomicBool::new(true);
+        assert_eq!(sol.search_all_cancellable(&cancel), Default::default());
+        cancel.store(false, Ordering::Relaxed);
+        let stats = sol.search_all_cancellable(&cancel);
+        assert_eq!(stats.solutions, 64);
+        assert_eq!(stats.deadends, 0);
+        assert_eq!(stats.peak_trail, 9);
+        assert_eq!(stats.max_level, 6);
+        assert_eq!(stats.restarts, 0);
+    }
+
+    #[test]
+    fn class_reps() {
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        let classes = sol.class_reps(&handles.mul);
+        assert_eq!(classes.len(), 5);
+        assert_eq!(classes.iter().map(|(_, copies)| copies).sum::<usize>(), 8);
+        for (model, copies) in classes.iter() {
+            assert_eq!(model.automorphisms(&handles.mul) * copies, 2);
+        }
+
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_group(3);
+        let classes = sol.class_reps(&handles.mul);
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].1, 3);
+        assert_eq!(classes[0].0.automorphisms(&handles.mul), 2);
+    }
+
+    #[test]
+    fn contradiction() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_clause(vec![(true, rel.clone(), vec![0, 1])]);
+        sol.set_value(true, &rel, &[0, 0]);
+        sol.set_value(true, &rel, &[0, 0]);
+        assert!(sol.is_consistent());
+        assert_eq!(sol.count_all(), 1);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set]);
+        sol.set_value(true, &equ, &[0, 1]);
+        sol.set_equality(&equ);
+        assert!(!sol.is_consistent());
+        assert_eq!(sol.count_all(), 0);
+        assert_eq!(
+            sol.search_all_cancellable(&AtomicBool::new(false)),
+            Default::default()
+        );
+    }
+
+    #[test]
+    fn histogram_by() {
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        let histogram = sol.histogram_by(&handles.mul);
+        let mut counts: Vec<usize> = histogram.into_values().collect();
+        counts.sort();
+        assert_eq!(counts, vec![1, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn combine() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set.clone()]);
+        let sub = sol.add_variable("sub".into(), vec![set.clone(), set]);
+        for x in 0..3 {
+            for y in 0..3 {
+                sol.set_value(x < y, &rel, &[x, y]);
+                sol.set_value(x == (y + 1) % 3, &sub, &[x, y]);
+            }
+        }
+
+        let buffer = sol.combine(&rel, &sub, &[0, 1], &[1, 2], BOOL_AND);
+        assert_eq!(buffer.len(), 27);
+        let mut pos = 0;
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    let val = x < y && y == (z + 1) % 3;
+                    assert_eq!(buffer.get(pos), if val { BOOL_TRUE } else { BOOL_FALSE });
+                    pos += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn is_total() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set]);
+        sol.set_equality(&equ);
+        sol.set_value(true, &one, &[0]);
+        assert!(sol.is_total(&equ));
+        assert!(!sol.is_total(&one));
+        sol.set_value(false, &one, &[1]);
+        assert!(sol.is_total(&one));
+    }
+
+    #[test]
+    fn solve_maxsat() {
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        let mul = handles.mul;
+        sol.add_soft_clause(vec![(false, mul.clone(), vec![0, 0, 0])], 1);
+        let (cost, model) = sol.solve_maxsat().unwrap();
+        assert_eq!(cost, 1);
+        let idempotents = (0..2)
+            .filter(|&x| model.get_value(&mul, &[x, x, x]) == BOOL_TRUE)
+            .count();
+        assert_eq!(idempotents, 1);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_soft_clause(vec![(true, rel.clone(), vec![0, 1])], 2);
+        sol.add_soft_clause(vec![(false, rel.clone(), vec![0, 0])], 3);
+        let (cost, model) = sol.solve_maxsat().unwrap();
+        assert_eq!(cost, 4);
+        assert_eq!(model.get_value(&rel, &[1, 0]), BOOL_TRUE);
+        assert_eq!(model.get_value(&rel, &[0, 0]), BOOL_FALSE);
+        assert_eq!(model.get_value(&rel, &[1, 1]), BOOL_FALSE);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_clause(vec![(true, rel.clone(), vec![0, 1])]);
+        sol.add_soft_clause(vec![(false, rel.clone(), vec![0, 0])], 3);
+        assert_eq!(sol.solve_maxsat().unwrap().0, 6);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_clause(vec![(true, rel.clone(), vec![0, 1])]);
+        sol.add_clause(vec![(false, rel.clone(), vec![0, 0])]);
+        sol.add_soft_clause(vec![(false, rel.clone(), vec![0, 1])], 1);
+        assert!(sol.solve_maxsat().is_none());
+    }
+
+    #[test]
+    fn conflict_graph_dot() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 1);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_clause(vec![
+            (false, p.clone(), vec![0]),
+            (true, q.clone(), vec![0]),
+        ]);
+        sol.add_clause(vec![
+            (false, q.clone(), vec![0]),
+            (false, p.clone(), vec![0]),
+        ]);
+        assert_eq!(sol.conflict_graph_dot(), None);
+
+        sol.set_value(true, &p, &[0]);
+        while sol.propagate_clauses() == BOOL_UNDEF1 {}
+        let dot = sol.conflict_graph_dot().unwrap();
+        let expected = "digraph conflict {
+  v0 [label=\"+p[0] @0\", shape=ellipse];
+  v0 -> v1;
+  v1 [label=\"+q[0] @0\", shape=ellipse];
+  conflict [shape=octagon];
+  v0 -> conflict;
+  v1 -> conflict;
+}
+";
+        assert_eq!(dot, expected);
+    }
+
+    #[test]
+    fn clause_cells() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        let clause = Clause::from_literals(vec![
+            (false, mul.clone(), vec![0, 1, 2]),
+            (true, mul.clone(), vec![1, 0, 2]),
+            (true, mul, vec![2, 2, 1]),
+        ]);
+
+        let mut coordinates = vec![0; clause.shape.dimension()];
+        for pos in clause.shape.positions() {
+            clause.shape.coordinates(pos, &mut coordinates);
+            let expected: Vec<usize> = clause
+                .literals
+                .iter()
+                .map(|lit| lit.position(&coordinates))
+                .collect();
+            assert_eq!(clause.cell(pos), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn probe() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_clause(vec![
+            (false, p.clone(), vec![0]),
+            (true, q.clone(), vec![0]),
+        ]);
+        sol.add_clause(vec![
+            (false, q.clone(), vec![0]),
+            (false, p.clone(), vec![0]),
+        ]);
+
+        assert_eq!(sol.probe(true, &p, &[0]), (BOOL_FALSE, 1));
+        assert_eq!(sol.probe(false, &p, &[1]), (BOOL_UNDEF2, 0));
+        assert_eq!(sol.get_value(&p, &[0]), BOOL_UNDEF1);
+        assert_eq!(sol.get_value(&q, &[1]), BOOL_UNDEF1);
+        assert!(sol.state.steps.is_empty() && sol.state.levels.is_empty());
+
+        sol.state.begin_speculation();
+        sol.set_value(true, &q, &[1]);
+        assert_eq!(sol.state.speculative_steps().len(), 1);
+        sol.state.commit_speculation();
+        assert_eq!(sol.get_value(&q, &[1]), BOOL_TRUE);
+    }
+
+    #[test]
+    fn signature() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        sol.add_domain("bit".into(), 2);
+        sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set.clone()]);
+        sol.add_variable("one".into(), vec![set]);
+
+        let domains: Vec<(&str, usize)> = sol.domains().collect();
+        assert_eq!(domains, vec![("set", 3), ("bit", 2)]);
+        let predicates: Vec<(&str, Vec<&str>)> = sol
+            .predicates()
+            .map(|(name, doms)| (name, doms.iter().map(|dom| dom.name()).collect()))
+            .collect();
+        assert_eq!(
+            predicates,
+            vec![("mul", vec!["set", "set", "set"]), ("one", vec!["set"])]
+        );
+    }
+
+    #[test]
+    fn checkpoint() {
+        let build = || {
+            let mut sol: Solver = Default::default();
+            sol.define_semigroup(2);
+            sol
+        };
+        let path = std::env::temp_dir().join(format!("relsat-{}.ckpt", std::process::id()));
+
+        let mut sol1 = build();
+        sol1.propagate_all();
+        sol1.state.make_decision();
+        sol1.propagate_all();
+        sol1.save_checkpoint(&path).unwrap();
+
+        let mut sol2 = build();
+        sol2.resume_checkpoint(&path).unwrap();
+        assert_eq!(sol2.state.assignment, sol1.state.assignment);
+        assert_eq!(sol2.state.levels, sol1.state.levels);
+        assert_eq!(sol2.count_all(), sol1.count_all());
+
+        let mut sol3: Solver = Default::default();
+        sol3.add_domain("set".into(), 2);
+        assert!(sol3.resume_checkpoint(&path).is_err());
+
+        // an interrupted and resumed search has the same totals
+        let build = || {
+            let mut sol: Solver = Default::default();
+            sol.define_semigroup(3);
+            sol.set_restart_policy(Some(RestartPolicy { luby_unit: 2 }));
+            sol
+        };
+        let mut sol1 = build();
+        let total = sol1.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!(total, sol1.search_stats());
+        assert!(total.learnt_clauses > 0 && total.restarts > 0);
+
+        let mut sol2 = build();
+        let mut steps = 0;
+        let stats = sol2.search_until(|| {
+            steps += 1;
+            steps > 200
+        });
+        assert!(stats.solutions < total.solutions && stats.learnt_clauses > 0);
+        sol2.save_checkpoint(&path).unwrap();
+
+        let mut sol3 = build();
+        sol3.resume_checkpoint(&path).unwrap();
+        assert_eq!(sol3.learnts.len(), sol2.learnts.len());
+        sol3.search_all_cancellable(&AtomicBool::new(false));
+        // the watches are built again after resuming, which visits all
+        // instances once more
+        let stats = sol3.search_stats();
+        assert!(stats.clause_visits > total.clause_visits);
+        assert_eq!(
+            SearchStats {
+                clause_visits: total.clause_visits,
+                ..stats
+            },
+            total
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn clause_order() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+        let mut literals = vec![
+            (false, mul.clone(), vec![0, 1, 2]),
+            (false, mul.clone(), vec![0, 1, 3]),
+            (true, equ.clone(), vec![2, 3]),
+        ];
+        let clause1 = Clause::from_literals(literals.clone());
+        literals.reverse();
+        let clause2 = Clause::from_literals(literals);
+        assert_eq!(clause1.to_string(), clause2.to_string());
+        assert_eq!(
+            clause1.to_string(),
+            "clause +equ(x2,x3) -mul(x0,x1,x2) -mul(x0,x1,x3) = false"
+        );
+    }
+
+    #[test]
+    fn clause_density() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_clause(vec![(true, p.clone(), vec![0]), (true, q.clone(), vec![1])]);
+        sol.set_value(true, &p, &[0]);
+        sol.evaluate_all();
+        assert_eq!(
+            sol.clause_density(),
+            vec![("+p(x0) +q(x1)".to_string(), 0.5)]
+        );
+    }
+
+    #[test]
+    fn get_cell() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let p = sol.add_variable("p".into(), vec![set]);
+        sol.set_value(true, &p, &[0]);
+        sol.set_value(false, &p, &[1]);
+        assert_eq!(sol.get_cell(&p, &[0]), Cell::True);
+        assert_eq!(sol.get_cell(&p, &[1]), Cell::False);
+        assert_eq!(sol.get_cell(&p, &[2]), Cell::Undef);
+        sol.state.assignment.set(p.position(&[2]), BOOL_MISSING);
+        assert_eq!(sol.get_cell(&p, &[2]), Cell::Missing);
+
+        let cells: Vec<Cell> = [BOOL_TRUE, BOOL_FALSE, BOOL_UNDEF1, BOOL_MISSING]
             .into_iter()
-            .map(|(sign, pred, indices)| Literal::new(&shape, sign, pred, indices))
+            .map(Cell::from)
+            .collect();
+        assert_eq!(cells, [Cell::True, Cell::False, Cell::Undef, Cell::Missing]);
+        let cells: Vec<String> = cells.iter().map(|cell| cell.to_string()).collect();
+        assert_eq!(cells, ["1", "0", "?", "x"]);
+
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 4);
+        let p = sol.add_variable("p".into(), vec![set]);
+        for (idx, val) in [BOOL_TRUE, BOOL_FALSE, BOOL_UNDEF1, BOOL_MISSING]
+            .into_iter()
+            .enumerate()
+        {
+            sol.state.assignment.set(p.position(&[idx]), val);
+        }
+        assert_eq!(
+            sol.model_to_json(),
+            "[{\"name\":\"p\",\"domains\":[\"set\"],\"table\":[1,0,null,\"missing\"]}]"
+        );
+    }
+
+    #[test]
+    fn constant_terms() {
+        let mut sol1: Solver = Default::default();
+        let sg = sol1.define_semigroup(3);
+        // x0 * 1 = x0 by a single clause with a constant argument
+        sol1.add_clause_terms(vec![(
+            true,
+            sg.mul.clone(),
+            vec![Term::Var(0), Term::Const(1), Term::Var(0)],
+        )]);
+        assert_eq!(sol1.clauses.last().unwrap().buffer.len(), 3);
+        assert_eq!(sol1.clauses.last().unwrap().formula(), "+mul(x0,1,x0)");
+
+        let mut sol2: Solver = Default::default();
+        let sg = sol2.define_semigroup(3);
+        for x in 0..3 {
+            sol2.set_value(true, &sg.mul, &[x, 1, x]);
+        }
+        assert_eq!(sol1.count_all(), sol2.count_all());
+
+        let clause = Clause::from_terms(vec![
+            (
+                false,
+                sg.mul.clone(),
+                vec![Term::Var(0), Term::Const(2), Term::Var(1)],
+            ),
+            (
+                true,
+                sg.mul,
+                vec![Term::Const(0), Term::Var(1), Term::Var(0)],
+            ),
+        ]);
+        let mut coordinates = vec![0; clause.shape.dimension()];
+        for pos in clause.shape.positions() {
+            clause.shape.coordinates(pos, &mut coordinates);
+            let expected: Vec<usize> = clause
+                .literals
+                .iter()
+                .map(|lit| lit.position(&coordinates))
+                .collect();
+            assert_eq!(clause.cell(pos), expected.as_slice());
+        }
+
+        // the instance (x0 * 1) * x1 = x0 * (1 * x1) of associativity
+        let magma = |sol: &mut Solver| {
+            let set = sol.add_domain("set".into(), 3);
+            let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+            sol.set_equality(&equ);
+            let mul = sol.add_variable("mul".into(), vec![set.clone(), set.clone(), set]);
+            sol.add_exist(mul.clone());
+            sol.add_clause(vec![
+                (false, mul.clone(), vec![0, 1, 2]),
+                (false, mul.clone(), vec![0, 1, 3]),
+                (true, equ.clone(), vec![2, 3]),
+            ]);
+            (equ, mul)
+        };
+        let mut sol1: Solver = Default::default();
+        let (equ, mul) = magma(&mut sol1);
+        let (var, one) = (Term::Var, Term::Const(1));
+        sol1.add_clause_terms(vec![
+            (false, mul.clone(), vec![var(0), one, var(2)]),
+            (false, mul.clone(), vec![var(2), var(1), var(3)]),
+            (false, mul.clone(), vec![one, var(1), var(4)]),
+            (false, mul.clone(), vec![var(0), var(4), var(5)]),
+            (true, equ.clone(), vec![var(3), var(5)]),
+        ]);
+        assert_eq!(
+            sol1.clauses.last().unwrap().formula(),
+            "+equ(x3,x5) -mul(x0,x4,x5) -mul(x0,1,x2) -mul(x2,x1,x3) -mul(1,x1,x4)"
+        );
+
+        let mut sol2: Solver = Default::default();
+        let (equ, mul) = magma(&mut sol2);
+        let shape = Shape::new(vec![3; 6], 0);
+        let mut coordinates = vec![0; 6];
+        for pos in shape.positions() {
+            shape.coordinates(pos, &mut coordinates);
+            let c = |var: usize| Term::Const(coordinates[var]);
+            sol2.add_clause_terms(vec![
+                (false, mul.clone(), vec![c(0), one, c(2)]),
+                (false, mul.clone(), vec![c(2), c(1), c(3)]),
+                (false, mul.clone(), vec![one, c(1), c(4)]),
+                (false, mul.clone(), vec![c(0), c(4), c(5)]),
+                (true, equ.clone(), vec![c(3), c(5)]),
+            ]);
+        }
+        let count = sol1.count_all();
+        assert!(count > 0);
+        assert_eq!(count, sol2.count_all());
+    }
+
+    #[test]
+    fn clean_evaluation() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        let mut cla = Clause::from_literals(vec![(true, p.clone(), vec![0])]);
+        cla.evaluate(&sol.state);
+        assert_eq!(cla.buffer.get(0), BOOL_UNDEF1);
+
+        // the buffer is not recomputed if only other tables have changed
+        cla.buffer.set(0, BOOL_UNDEF2);
+        sol.set_value(true, &q, &[0]);
+        cla.evaluate(&sol.state);
+        assert_eq!(cla.buffer.get(0), BOOL_UNDEF2);
+
+        sol.set_value(true, &p, &[1]);
+        cla.evaluate(&sol.state);
+        assert_eq!(cla.buffer.get(0), BOOL_UNDEF1);
+        assert_eq!(cla.buffer.get(1), BOOL_TRUE);
+    }
+
+    #[test]
+    fn try_lookup_var() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let empty = sol.add_domain("empty".into(), 0);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        sol.add_variable("e".into(), vec![empty]);
+        let q = sol.add_variable("q".into(), vec![set.clone(), set]);
+
+        let names: Vec<Option<&str>> = (0..7)
+            .map(|bvar| sol.try_lookup_var(bvar).map(|pred| pred.name()))
             .collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("p"),
+                Some("p"),
+                Some("q"),
+                Some("q"),
+                Some("q"),
+                Some("q"),
+                None
+            ]
+        );
+        assert!(Rc::ptr_eq(sol.try_lookup_var(1).unwrap(), &p));
+        assert!(Rc::ptr_eq(sol.try_lookup_var(2).unwrap(), &q));
+    }
+
+    #[test]
+    fn disjoint_union() {
+        let cycle = || {
+            let mut sol: Solver = Default::default();
+            let set = sol.add_domain("set".into(), 2);
+            let edge = sol.add_variable("edge".into(), vec![set.clone(), set]);
+            for x in 0..2 {
+                for y in 0..2 {
+                    sol.set_value(x != y, &edge, &[x, y]);
+                }
+            }
+            (sol, edge)
+        };
+        let (sol1, edge) = cycle();
+        let (sol2, _) = cycle();
+        let sol = sol1.disjoint_union(&sol2, &edge);
+
+        let edge = sol.try_lookup_var(0).unwrap().clone();
+        assert_eq!(edge.domains()[0].size(), 4);
+        assert!(sol.is_total(&edge));
+        let component = |x: usize| x / 2;
+        for x in 0..4 {
+            for y in 0..4 {
+                let connected = x != y && component(x) == component(y);
+                assert_eq!(sol.get_value(&edge, &[x, y]) == BOOL_TRUE, connected);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the predicates of the two solvers differ")]
+    fn disjoint_union_signature() {
+        let mut sol1: Solver = Default::default();
+        let set = sol1.add_domain("set".into(), 2);
+        let edge = sol1.add_variable("edge".into(), vec![set.clone(), set]);
+        let mut sol2: Solver = Default::default();
+        let set = sol2.add_domain("set".into(), 2);
+        sol2.add_variable("edge".into(), vec![set.clone(), set.clone()]);
+        sol2.add_variable("color".into(), vec![set]);
+        sol1.disjoint_union(&sol2, &edge);
+    }
+
+    #[test]
+    fn is_model() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_clause(vec![
+            (false, p.clone(), vec![0]),
+            (true, q.clone(), vec![0]),
+        ]);
+        sol.add_exist(q.clone());
+        assert!(!sol.is_model());
 
-        let cla = Clause::new(shape, domains, literals);
-        self.clauses.push(cla);
-    }
+        sol.set_value(true, &p, &[0]);
+        sol.set_value(true, &q, &[0]);
+        sol.set_value(false, &p, &[1]);
+        assert!(!sol.is_model());
+        sol.set_value(false, &q, &[1]);
+        assert!(sol.is_model());
 
-    pub fn add_exist(&mut self, predicate: Rc<Predicate>) {
-        self.exists.push(Exist::new(predicate));
+        sol.set_value(false, &q, &[0]);
+        assert!(!sol.is_model());
     }
 
-    pub fn set_value(&mut self, sign: bool, predicate: &Predicate, coordinates: &[usize]) {
-        let pos = predicate.shape.position(coordinates.iter());
-        self.state.assign(pos, sign, Reason::Initial);
-    }
+    // Small theories, including one with a parity constraint, on which the
+    // variants of the search are compared.
+    const FIXTURES: [fn(&mut Solver); 4] = [
+        |sol| {
+            sol.define_semigroup(3);
+        },
+        |sol| {
+            sol.define_monoid(3);
+        },
+        |sol| {
+            sol.define_group(3);
+        },
+        |sol| {
+            let set = sol.add_domain("set".into(), 3);
+            let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+            sol.add_parity(&rel, 1, false);
+            sol.add_symmetric(&rel);
+        },
+    ];
 
-    pub fn set_equality(&mut self, predicate: &Predicate) {
-        for i in 0..predicate.shape.length(0) {
-            for j in 0..predicate.shape.length(1) {
-                let pos = predicate.shape.position([i, j].iter());
-                self.state.assign(pos, i == j, Reason::Initial);
-            }
+    // Builds each fixture, checks that `count_all` and `search_all` find as
+    // many models with the given configuration as `count_all` without it,
+    // and returns the configured solvers after the search with the stats.
+    fn compare_fixtures<F>(configure: F) -> Vec<(Solver, SearchStats)>
+    where
+        F: Fn(&mut Solver),
+    {
+        let mut result = vec![];
+        for build in FIXTURES.iter() {
+            let mut sol: Solver = Default::default();
+            build(&mut sol);
+            let count = sol.count_all();
+
+            let mut sol: Solver = Default::default();
+            build(&mut sol);
+            configure(&mut sol);
+            assert_eq!(sol.count_all(), count);
+
+            let mut sol: Solver = Default::default();
+            build(&mut sol);
+            configure(&mut sol);
+            let stats = sol.search_all_cancellable(&AtomicBool::new(false));
+            assert_eq!(stats.solutions, count);
+            result.push((sol, stats));
         }
+        result
     }
 
-    pub fn get_clauses_status(&self) -> Bit2 {
-        let mut res = BOOL_TRUE;
-        for cla in self.clauses.iter() {
-            res = BOOL_AND.of(res, cla.get_status());
+    #[test]
+    fn clause_learning() {
+        for (sol, stats) in compare_fixtures(|_| {}) {
+            assert_eq!(stats.learnt_clauses, sol.learnts.len());
         }
-        res
     }
 
-    pub fn get_exists_status(&self) -> Bit2 {
-        let mut res = BOOL_TRUE;
-        for ext in self.exists.iter() {
-            res = BOOL_AND.of(res, ext.get_status(&self.state));
+    #[test]
+    fn watched_literals() {
+        let dense = compare_fixtures(|sol| sol.set_watched_literals(false));
+        let watched = compare_fixtures(|_| {});
+        for ((_, dense), (_, watched)) in dense.iter().zip(watched.iter()) {
+            assert!(2 * watched.clause_visits < dense.clause_visits);
         }
-        res
+
+        let (dense, watched) = compare_groups(4);
+        assert_eq!(dense.solutions, 16);
+        assert!(4 * watched.clause_visits < dense.clause_visits);
     }
 
-    pub fn get_status(&self) -> Bit2 {
-        BOOL_AND.of(self.get_clauses_status(), self.get_exists_status())
+    // Searches the groups of the given size with dense and with watched
+    // propagation, and returns the stats of both.
+    fn compare_groups(size: usize) -> (SearchStats, SearchStats) {
+        let mut sol: Solver = Default::default();
+        sol.define_group(size);
+        sol.set_watched_literals(false);
+        let dense = sol.search_all();
+        let mut sol: Solver = Default::default();
+        sol.define_group(size);
+        let watched = sol.search_all();
+        assert_eq!(dense.solutions, watched.solutions);
+        (dense, watched)
     }
 
-    pub fn evaluate_all(&mut self) {
-        for cla in self.clauses.iter_mut() {
-            cla.evaluate(&self.state);
-        }
+    #[test]
+    #[ignore = "benchmark, run with --release --ignored"]
+    fn watched_literals_benchmark() {
+        let (dense, watched) = compare_groups(5);
+        assert_eq!(dense.solutions, 30);
+        assert!(10 * watched.clause_visits < dense.clause_visits);
     }
 
-    // Returns BOOL_FALSE if the clause has failed (maybe with propagations),
-    // BOOL_UNDEF1 if some propagations were made and the status is unclear,
-    // BOOL_TRUE if the clause is universally true, and BOOL_UNDEF2 otherwise.
-    pub fn propagate_clauses(&mut self) -> Bit2 {
-        let mut result = BOOL_TRUE;
-        for cla in self.clauses.iter_mut() {
-            cla.evaluate(&self.state);
-            let val = cla.propagate(&mut self.state);
-            result = BOOL_AND.of(result, val);
-        }
+    #[test]
+    fn restart_policy() {
+        let policy = RestartPolicy { luby_unit: 2 };
+        let budgets: Vec<u64> = (0..7).map(|idx| policy.budget(idx)).collect();
+        assert_eq!(budgets, [2, 2, 4, 2, 2, 4, 8]);
 
-        let check = self.get_clauses_status();
-        assert!(result == check || result == BOOL_UNDEF1);
-        result
+        let mut sol1: Solver = Default::default();
+        sol1.define_group(3);
+        let stats1 = sol1.search_all_cancellable(&AtomicBool::new(false));
+        let mut sol2: Solver = Default::default();
+        sol2.define_group(3);
+        sol2.set_restart_policy(Some(RestartPolicy { luby_unit: 1 }));
+        let stats2 = sol2.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!(stats1.solutions, stats2.solutions);
+        assert_eq!(stats1.restarts, 0);
+        assert!(stats2.restarts > 0);
+
+        // with activities the restarts change the order of the decisions
+        let mut sol3: Solver = Default::default();
+        sol3.define_group(3);
+        sol3.set_decision_heuristic(Heuristic::Vsids);
+        let stats3 = sol3.search_all_cancellable(&AtomicBool::new(false));
+        let mut sol4: Solver = Default::default();
+        sol4.define_group(3);
+        sol4.set_decision_heuristic(Heuristic::Vsids);
+        sol4.set_restart_policy(Some(RestartPolicy { luby_unit: 1 }));
+        let stats4 = sol4.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!(stats3.solutions, stats4.solutions);
+        assert!(stats4.restarts > 0);
+        assert_ne!(stats3.deadends, stats4.deadends);
+
+        compare_fixtures(|sol| {
+            sol.set_decision_heuristic(Heuristic::Vsids);
+            sol.set_restart_policy(Some(RestartPolicy { luby_unit: 1 }));
+        });
     }
 
-    pub fn propagate_exists(&mut self) -> Bit2 {
-        let mut result = BOOL_TRUE;
-        for xst in self.exists.iter() {
-            let val = xst.propagate(&mut self.state);
-            result = BOOL_AND.of(result, val);
-        }
+    #[test]
+    fn decision_heuristic() {
+        let mut state = State::default();
+        state.assignment.append(2, BOOL_UNDEF1);
+        state.bump(1);
+        state.decay();
+        state.bump(0);
+        assert_eq!(state.activity[1], 1.0);
+        assert!(state.activity[0] > state.activity[1]);
 
-        let check = self.get_exists_status();
-        assert!(result == check || result == BOOL_UNDEF1);
-        result
+        compare_fixtures(|sol| sol.set_decision_heuristic(Heuristic::Vsids));
     }
 
-    fn get_analysis_failure(&self) -> Option<Vec<usize>> {
-        for cla in self.clauses.iter() {
-            let failure = cla.get_failure();
-            if failure.is_some() {
-                return failure;
+    #[derive(Debug, PartialEq)]
+    enum Json {
+        Null,
+        Number(usize),
+        Text(String),
+        Array(Vec<Json>),
+        Object(Vec<(String, Json)>),
+    }
+
+    // Reads the JSON value at the start of the input, which is written
+    // without whitespace and escapes.
+    fn read_json(input: &mut &[u8]) -> Json {
+        let (&first, rest) = input.split_first().unwrap();
+        match first {
+            b'n' => {
+                *input = &input[4..];
+                Json::Null
+            }
+            b'"' => {
+                let end = rest.iter().position(|&b| b == b'"').unwrap();
+                let text = String::from_utf8(rest[..end].to_vec()).unwrap();
+                *input = &rest[end + 1..];
+                Json::Text(text)
+            }
+            b'[' | b'{' => {
+                *input = rest;
+                let mut items = vec![];
+                let mut fields = vec![];
+                while input[0] != b']' && input[0] != b'}' {
+                    if first == b'{' {
+                        let Json::Text(key) = read_json(input) else {
+                            panic!("invalid key");
+                        };
+                        assert_eq!(input[0], b':');
+                        *input = &input[1..];
+                        fields.push((key, read_json(input)));
+                    } else {
+                        items.push(read_json(input));
+                    }
+                    if input[0] == b',' {
+                        *input = &input[1..];
+                    }
+                }
+                *input = &input[1..];
+                if first == b'{' {
+                    Json::Object(fields)
+                } else {
+                    Json::Array(items)
+                }
+            }
+            _ => {
+                let end = input.iter().position(|b| !b.is_ascii_digit()).unwrap();
+                let num = std::str::from_utf8(&input[..end]).unwrap().parse().unwrap();
+                *input = &input[end..];
+                Json::Number(num)
             }
         }
-        None
     }
 
-    fn get_analysis_step(&self, bvar: usize) -> Option<usize> {
-        let last = *self.state.levels.last().unwrap();
-        self.state
-            .steps
-            .iter()
-            .skip(last)
-            .position(|s| s.bvar == bvar)
-            .map(|p| p + last)
-    }
+    #[test]
+    fn model_to_json() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let one = sol.add_domain("one".into(), 1);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set.clone()]);
+        let pnt = sol.add_variable("pnt".into(), vec![one]);
+        sol.add_clause(vec![
+            (false, rel.clone(), vec![0, 1]),
+            (true, rel.clone(), vec![1, 0]),
+        ]);
+        sol.set_value(true, &rel, &[0, 1]);
+        sol.set_value(false, &rel, &[0, 0]);
+        assert!(sol.next_model());
+        assert_eq!(sol.get_status(), BOOL_TRUE);
 
-    fn analyze(&self) {
-        println!("*** ANALYSIS ***");
-        let failure = self.get_analysis_failure().unwrap();
+        let json = sol.model_to_json();
+        let mut input = json.as_bytes();
+        let Json::Array(entries) = read_json(&mut input) else {
+            panic!("not an array");
+        };
+        assert!(input.is_empty());
+        assert_eq!(entries.len(), 2);
 
-        let mut before: Vec<usize> = Default::default();
-        let mut after: Vec<usize> = Default::default();
-        for &bvar in failure.iter() {
-            let step = self.get_analysis_step(bvar);
-            println!("{} {} {:?}", bvar, self.format_var(bvar), step);
-            match step {
-                None => {
-                    match before.binary_search(&bvar) {
-                        Ok(_) => {}
-                        Err(pos) => before.insert(pos, bvar),
-                    };
-                }
-                Some(step) => {
-                    match after.binary_search(&step) {
-                        Ok(_) => {}
-                        Err(pos) => after.insert(pos, step),
+        for (entry, pred) in entries.iter().zip([&rel, &pnt]) {
+            let Json::Object(fields) = entry else {
+                panic!("not an object");
+            };
+            assert_eq!(fields[0], ("name".into(), Json::Text(pred.name.clone())));
+            let domains = pred.domains.iter().map(|dom| Json::Text(dom.name.clone()));
+            assert_eq!(
+                fields[1],
+                ("domains".into(), Json::Array(domains.collect()))
+            );
+            assert_eq!(fields[2].0, "table");
+
+            let mut coordinates = vec![0; pred.arity()];
+            for pos in pred.shape.positions() {
+                pred.shape.coordinates(pos, &mut coordinates);
+                let mut cell = &fields[2].1;
+                for &coordinate in coordinates.iter() {
+                    let Json::Array(items) = cell else {
+                        panic!("not an array");
                     };
+                    cell = &items[coordinate];
                 }
-            };
+                let expected = match sol.get_value(pred, &coordinates) {
+                    BOOL_TRUE => Json::Number(1),
+                    BOOL_FALSE => Json::Number(0),
+                    _ => Json::Null,
+                };
+                assert_eq!(*cell, expected);
+            }
         }
-        assert!(!after.is_empty());
-        println!("before: {:?}, after: {:?}", before, after);
+    }
 
-        while after.len() >= 2 {
-            let last = after.pop().unwrap();
-            match &self.state.steps[last].reason {
-                Reason::Clause(bvars) => {
-                    for &bvar in bvars.iter() {
-                        let step = self.get_analysis_step(bvar);
-                        println!("{} {} {:?}", bvar, self.format_var(bvar), step);
-                        match step {
-                            None => {
-                                match before.binary_search(&bvar) {
-                                    Ok(_) => {}
-                                    Err(pos) => before.insert(pos, bvar),
-                                };
-                            }
-                            Some(step) => {
-                                match after.binary_search(&step) {
-                                    Ok(_) => {}
-                                    Err(pos) => after.insert(pos, step),
-                                };
-                            }
+    #[test]
+    fn export_dimacs() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_clause(vec![
+            (false, rel.clone(), vec![0, 1]),
+            (true, rel.clone(), vec![1, 0]),
+        ]);
+        sol.add_exist(rel.clone());
+        sol.set_value(false, &rel, &[0, 0]);
+
+        let expected = "p cnf 4 7\n\
+            -1 1 0\n-2 3 0\n-3 2 0\n-4 4 0\n\
+            1 2 0\n3 4 0\n\
+            -1 0\n";
+        assert_eq!(sol.export_dimacs(), expected);
+    }
+
+    #[test]
+    fn export_dimacs_parity() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_parity(&rel, 1, false);
+        let dimacs = sol.export_dimacs();
+        assert!(dimacs.starts_with("p cnf 12 18\n"));
+
+        // the models of the formula restricted to the cells are the models
+        // of the solver, and each has a single value of the auxiliaries
+        let clauses: Vec<Vec<i32>> = dimacs
+            .lines()
+            .skip(1)
+            .map(|line| line.split(' ').map(|lit| lit.parse().unwrap()).collect())
+            .collect();
+        let mut models = 0;
+        for bits in 0..1 << 12 {
+            let satisfied = clauses.iter().all(|cla| {
+                cla.iter()
+                    .any(|&lit| lit != 0 && ((bits >> (lit.abs() - 1)) & 1 == 1) == (lit > 0))
+            });
+            if satisfied {
+                models += 1;
+                let model: Vec<i32> = (1..=12)
+                    .map(|var| {
+                        if (bits >> (var - 1)) & 1 == 1 {
+                            var
+                        } else {
+                            -var
                         }
-                    }
-                }
-                _ => {
-                    panic!();
-                }
-            };
+                    })
+                    .collect();
+                let mut other: Solver = Default::default();
+                let set = other.add_domain("set".into(), 3);
+                let rel = other.add_variable("rel".into(), vec![set.clone(), set]);
+                other.add_parity(&rel, 1, false);
+                other.import_dimacs_model(&model).unwrap();
+                assert!(other.is_model());
+            }
         }
+        assert_eq!(models, sol.count_all());
+    }
 
-        assert_eq!(after.len(), 1);
-        let bvar = self.state.steps[after.pop().unwrap()].bvar;
-        assert!(!before.contains(&bvar));
-        before.push(bvar);
-        println!("literals: {:?}", before);
+    #[test]
+    fn import_dimacs_model() {
+        let build = |sol: &mut Solver| {
+            let set = sol.add_domain("set".into(), 2);
+            let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+            sol.add_clause(vec![
+                (false, rel.clone(), vec![0, 1]),
+                (true, rel.clone(), vec![1, 0]),
+            ]);
+            sol.set_value(false, &rel, &[0, 0]);
+        };
 
-        print!("learned clause:");
-        for &bvar in before.iter() {
-            print!(" {}", self.format_var(bvar));
-        }
-        println!();
+        let mut sol: Solver = Default::default();
+        build(&mut sol);
+        assert_eq!(sol.import_dimacs_model(&[-1, 2, 3, 4, 0]), Ok(()));
+        assert_eq!(sol.get_status(), BOOL_TRUE);
 
-        println!("*** END OF ANALYSIS ***");
+        let mut sol: Solver = Default::default();
+        build(&mut sol);
+        assert_eq!(sol.import_dimacs_model(&[-1, 2, -3, 4, 0]), Ok(()));
+        assert_eq!(sol.get_status(), BOOL_FALSE);
+
+        let mut sol: Solver = Default::default();
+        build(&mut sol);
+        let range = ImportError::Range {
+            index: -5,
+            variables: 4,
+        };
+        assert_eq!(sol.import_dimacs_model(&[2, -5]), Err(range));
+        let conflict = ImportError::Conflict { index: 1 };
+        assert_eq!(sol.import_dimacs_model(&[2, 1]), Err(conflict));
+        assert_eq!(sol.state.steps.len(), 1);
+        assert_eq!(
+            sol.get_value(&sol.predicates[0].clone(), &[0, 1]),
+            BOOL_UNDEF1
+        );
     }
 
-    pub fn search_all(&mut self) {
-        let mut num_solutions: usize = 0;
-        let mut num_learnings: usize = 0;
-        let mut num_deadends: usize = 0;
+    #[test]
+    fn trail() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_clause(vec![
+            (false, rel.clone(), vec![0, 1]),
+            (true, rel.clone(), vec![1, 0]),
+        ]);
+        sol.set_value(false, &rel, &[0, 0]);
+        sol.propagate_all();
+        assert!(sol.state.make_decision());
+        sol.propagate_all();
 
-        loop {
-            let mut used_exists = false;
-            let mut value;
-            loop {
-                value = self.propagate_clauses();
-                if value == BOOL_UNDEF1 {
-                    continue;
-                } else if value == BOOL_FALSE {
-                    break;
-                }
+        let trail: Vec<(String, String, usize)> = sol
+            .trail()
+            .map(|(var, reason, level)| (var, sol.format_reason(reason), level))
+            .collect();
+        assert_eq!(
+            trail,
+            [
+                ("-rel[0, 0]".into(), "initial".into(), 0),
+                ("+rel[0, 1]".into(), "decision".into(), 1),
+                ("+rel[1, 0]".into(), "+rel[0, 1]".into(), 1),
+            ]
+        );
+    }
 
-                used_exists = true;
-                value = BOOL_AND.of(value, self.propagate_exists());
-                if value == BOOL_UNDEF1 {
-                    continue;
-                } else {
-                    break;
-                }
-            }
+    #[test]
+    fn backjump() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+        sol.add_symmetric(&rel);
 
-            assert!(value != BOOL_UNDEF1 && value == self.get_status());
-            if value == BOOL_FALSE && !used_exists {
-                num_learnings += 1;
-                self.evaluate_all();
-                if true {
-                    println!("*** LEARNING ***");
-                    self.print();
-                    println!("*** END OF LEARNING ***");
-                }
-                self.analyze();
-                if true || !self.state.next_decision() {
-                    break;
-                }
-            } else if value == BOOL_FALSE && used_exists {
-                num_deadends += 1;
-                if true {
-                    println!("*** EXISTS ***");
-                    self.evaluate_all();
-                    self.print();
-                    println!("*** END OF EXISTS ***");
-                }
-                if !self.state.next_decision() {
-                    break;
-                }
-            } else if value == BOOL_TRUE {
-                num_solutions += 1;
-                if false {
-                    println!("*** SOLUTION ***");
-                    for pred in self.predicates.iter() {
-                        println!("{}", pred);
-                        self.state.print_table(&pred.shape);
-                    }
-                    println!("*** END OF SOLUTION ***");
-                }
-                if !self.state.next_decision() {
-                    break;
-                }
-            } else {
-                assert_eq!(value, BOOL_UNDEF2);
-                let ret = self.state.make_decision();
-                assert!(ret);
-            }
-        }
+        let mut fresh: Solver = Default::default();
+        let set = fresh.add_domain("set".into(), 2);
+        let rel = fresh.add_variable("rel".into(), vec![set.clone(), set]);
+        fresh.add_symmetric(&rel);
 
-        println!("Total solutions: {}", num_solutions);
-        println!("Total learnings: {}", num_learnings);
-        println!("Total deadends: {}", num_deadends);
-    }
+        fresh.propagate_all();
+        assert!(fresh.state.make_decision());
+        fresh.propagate_all();
 
-    fn lookup_var(&self, bvar: usize) -> &Predicate {
-        for rvar in self.predicates.iter() {
-            if rvar.shape.positions().contains(&bvar) {
-                return rvar;
-            }
+        sol.propagate_all();
+        while sol.state.make_decision() {
+            sol.propagate_all();
         }
-        panic!();
+        assert_eq!(sol.state.levels.len(), 3);
+        sol.state.backjump(1);
+        assert_eq!(sol.state.levels.len(), 1);
+        assert_eq!(sol.state.assignment, fresh.state.assignment);
+        assert_eq!(sol.state.steps.len(), fresh.state.steps.len());
+
+        // after the last branch the solver is back at level zero
+        while sol.state.next_decision() {}
+        assert!(sol.state.steps.is_empty() && sol.state.levels.is_empty());
+        assert_eq!(sol.count_all(), 8);
     }
 
-    fn format_var(&self, bvar: usize) -> String {
-        let bval = self.state.assignment.get(bvar);
-        assert!(bval == BOOL_FALSE || bval == BOOL_TRUE);
+    #[test]
+    fn solutions() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 1);
+        let p = sol.add_variable("p".into(), vec![set.clone()]);
+        let q = sol.add_variable("q".into(), vec![set]);
+        sol.add_clause(vec![(true, p, vec![0]), (true, q, vec![0])]);
 
-        let rvar = self.lookup_var(bvar);
-        let mut coordinates = vec![0; rvar.shape.dimension()];
-        rvar.shape.coordinates(bvar, &mut coordinates);
+        // the first model is reported as soon as the clause is satisfied
+        let models: Vec<_> = sol.solutions().collect();
+        let p0 = |val| ("p".to_string(), vec![0], val);
+        let q0 = |val| ("q".to_string(), vec![0], val);
+        assert_eq!(models, vec![vec![p0(true)], vec![p0(false), q0(true)]]);
 
-        format!(
-            "{}{}{:?}",
-            if bval == BOOL_TRUE { '+' } else { '-' },
-            rvar.name,
-            coordinates,
-        )
+        let mut sol: Solver = Default::default();
+        sol.define_semigroup(2);
+        assert_eq!(sol.solutions().take(3).count(), 3);
+        assert_eq!(sol.solutions().count(), 5);
     }
 
-    fn format_reason(&self, reason: &Reason) -> String {
-        match reason {
-            Reason::Initial => "initial".into(),
-            Reason::Decision => "decision".into(),
-            Reason::Clause(vars) => vars
-                .iter()
-                .map(|&bvar| self.format_var(bvar))
-                .collect::<Vec<String>>()
-                .join(" "),
-            Reason::Exists => "exists".into(),
-        }
+    #[test]
+    fn for_each_solution() {
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        let commutative = |sol: &Solver| {
+            (0..2).all(|x| {
+                (0..2).all(|y| {
+                    (0..2).all(|z| {
+                        sol.get_value(&handles.mul, &[x, y, z])
+                            == sol.get_value(&handles.mul, &[y, x, z])
+                    })
+                })
+            })
+        };
+
+        let mut visited = 0;
+        sol.for_each_solution(|sol| {
+            visited += 1;
+            commutative(sol)
+        });
+        assert!(!commutative(&sol));
+
+        let mut rest = 0;
+        sol.for_each_solution(|_| {
+            rest += 1;
+            true
+        });
+        assert_eq!(visited + rest, 8);
     }
 
-    pub fn print(&self) {
-        for dom in self.domains.iter() {
-            println!("{}", dom);
-        }
-        for pred in self.predicates.iter() {
-            println!("{}", pred);
-            self.state.print_table(&pred.shape);
-        }
-        for step in self.state.steps.iter() {
-            println!(
-                "step {} from {}",
-                self.format_var(step.bvar),
-                self.format_reason(&step.reason)
-            );
+    #[test]
+    fn symmetry_break() {
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        let witness = sol.add_symmetry_break(&handles.set);
+        assert!(Rc::ptr_eq(&witness, &handles.mul));
+        let stats = sol.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!(stats.solutions, 5);
+
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_group(3);
+        sol.set_symmetry_witness(handles.mul);
+        let stats = sol.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!(stats.solutions, 1);
+
+        // the other enumerations report the same classes
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(2);
+        sol.add_symmetry_break(&handles.set);
+        assert_eq!(sol.count_all(), 5);
+
+        // the lex-leader clauses prune the search
+        let mut sol1: Solver = Default::default();
+        sol1.define_semigroup(3);
+        let stats1 = sol1.search_all_cancellable(&AtomicBool::new(false));
+        let mut sol2: Solver = Default::default();
+        let handles = sol2.define_semigroup(3);
+        sol2.add_symmetry_break(&handles.set);
+        assert_eq!(sol2.count_models(), 24);
+        let mut sol2: Solver = Default::default();
+        let handles = sol2.define_semigroup(3);
+        sol2.add_symmetry_break(&handles.set);
+        let stats2 = sol2.search_all_cancellable(&AtomicBool::new(false));
+        assert_eq!((stats1.solutions, stats2.solutions), (113, 24));
+        assert!(2 * stats2.decisions < stats1.decisions);
+
+        // the lex-leader clauses and their cells are private and are not
+        // counted as learnt, in both propagation modes
+        assert_eq!(sol2.domains().count(), 1);
+        assert_eq!(sol2.predicates().count(), 2);
+        assert!(!sol2.lex_clauses.is_empty());
+        assert_eq!(stats2.learnt_clauses, sol2.learnts.len());
+        for watched in [true, false] {
+            let mut sol: Solver = Default::default();
+            let handles = sol.define_semigroup(3);
+            sol.set_watched_literals(watched);
+            sol.add_symmetry_break(&handles.set);
+            assert!(sol.learnts.is_empty());
+            assert_eq!(sol.count_all(), 24);
         }
-        for cla in self.clauses.iter() {
-            println!("{}", cla);
-            if let Some(failure) = cla.get_failure() {
-                // duh, this is negated
-                let failure: Vec<String> = failure
-                    .into_iter()
-                    .map(|bvar| self.format_var(bvar))
-                    .collect();
-                println!("failure {}", failure.join(" "));
-            }
+        let mut sol: Solver = Default::default();
+        let handles = sol.define_semigroup(3);
+        sol.add_symmetry_break(&handles.set);
+        let lines = sol.export_dimacs().lines().count();
+        let mut other: Solver = Default::default();
+        other.define_semigroup(3);
+        let other_lines = other.export_dimacs().lines().count();
+        assert_eq!(lines, other_lines + sol.lex_clauses.len());
+
+        // the unconstrained cells of the witness are decided too
+        for (size, count) in [(1, 2), (2, 10), (3, 104)] {
+            let mut sol: Solver = Default::default();
+            let set = sol.add_domain("set".into(), size);
+            let rel = sol.add_variable("rel".into(), vec![set.clone(), set]);
+            sol.set_symmetry_witness(rel);
+            assert_eq!(sol.count_all(), count);
         }
-        for ext in self.exists.iter() {
-            // println!("exist {}", ext);
-            println!(
-                "{} = {}",
-                ext,
-                BOOL_FORMAT2[ext.get_status(&self.state).idx()]
-            );
-            if let Some(failure) = ext.get_failure(&self.state) {
-                println!("failure {}", self.format_var(failure));
+    }
+
+    #[test]
+    fn set_congruence() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let two = sol.add_domain("two".into(), 2);
+        let equ = sol.add_variable("equ".into(), vec![set.clone(), set.clone()]);
+        let one = sol.add_variable("one".into(), vec![set.clone()]);
+        let rel = sol.add_variable("rel".into(), vec![two, set]);
+        sol.set_congruence(&equ);
+        assert_eq!(sol.clauses.len(), 2);
+        assert_eq!(sol.clauses[1].literals.len(), 3);
+
+        // under the full relation both predicates must be constant
+        for x in 0..3 {
+            for y in 0..3 {
+                sol.set_value(true, &equ, &[x, y]);
             }
         }
-        if false {
-            println!("steps = {:?}", self.state.steps);
-            println!("levels = {:?}", self.state.levels);
-        }
-        println!(
-            "clauses status = {}",
-            BOOL_FORMAT2[self.get_clauses_status().idx()]
-        );
-        println!(
-            "exists status = {}",
-            BOOL_FORMAT2[self.get_exists_status().idx()]
-        );
+        sol.set_value(true, &rel, &[0, 0]);
+        sol.set_value(false, &one, &[0]);
+        assert_eq!(sol.count_all(), 2);
     }
 }
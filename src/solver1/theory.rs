@@ -15,8 +15,11 @@
 * along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::ops::Range;
 use std::rc::Rc;
 
+use super::solver;
+
 #[derive(Debug)]
 pub struct Domain {
     name: String,
@@ -238,6 +241,73 @@ impl std::fmt::Display for Theory {
     }
 }
 
+/// Creates a solver with the domains, predicates and clauses of the theory,
+/// where each domain gets the size listed for its name.
+pub fn lower(theory: &Theory, sizes: &[(&str, usize)]) -> solver::Solver {
+    let mut sol: solver::Solver = Default::default();
+
+    let domains: Vec<Rc<solver::Domain>> = theory
+        .domains
+        .iter()
+        .map(|dom| {
+            let size = sizes
+                .iter()
+                .find(|(name, _)| *name == dom.name)
+                .unwrap_or_else(|| panic!("no size for domain {}", dom.name))
+                .1;
+            sol.add_domain(dom.name.clone(), size)
+        })
+        .collect();
+    let lower_domain = |dom: &Rc<Domain>| {
+        let idx = theory
+            .domains
+            .iter()
+            .position(|other| Rc::ptr_eq(other, dom))
+            .unwrap();
+        domains[idx].clone()
+    };
+
+    let predicates: Vec<Rc<solver::Predicate>> = theory
+        .predicates
+        .iter()
+        .map(|prd| {
+            let domains = prd.domains.iter().map(lower_domain).collect();
+            sol.add_variable(prd.name.clone(), domains)
+        })
+        .collect();
+
+    for cla in theory.clauses.iter() {
+        let literals = cla
+            .literals
+            .iter()
+            .map(|lit| {
+                let idx = theory
+                    .predicates
+                    .iter()
+                    .position(|prd| Rc::ptr_eq(prd, &lit.predicate))
+                    .unwrap();
+                (lit.sign, predicates[idx].clone(), lit.variables.to_vec())
+            })
+            .collect();
+        sol.add_clause(literals);
+    }
+
+    sol
+}
+
+/// Counts the models of the theory for each size of the given domain in the
+/// range, which must be the only domain of the theory. Every size is solved
+/// by a fresh solver, and panics if a count does not fit into `usize`.
+pub fn search_sizes(theory: &Theory, domain: &str, range: Range<usize>) -> Vec<(usize, usize)> {
+    range
+        .map(|size| {
+            let mut sol = lower(theory, &[(domain, size)]);
+            let count = usize::try_from(sol.count_models()).expect("too many models");
+            (size, count)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,4 +341,48 @@ mod tests {
 
         println!("{}", thy);
     }
+
+    fn equivalence() -> Theory {
+        let mut thy = Theory::new();
+        let set = Rc::new(Domain::new("set".into()));
+        thy.add_domain(set.clone());
+        let equ = Rc::new(Predicate::new("equ".into(), vec![set.clone(), set]));
+        thy.add_predicate(equ.clone());
+        thy.add_clause(Rc::new(Clause::new(vec![Literal::new(
+            true,
+            equ.clone(),
+            vec![0, 0],
+        )])));
+        thy.add_clause(Rc::new(Clause::new(vec![
+            Literal::new(false, equ.clone(), vec![0, 1]),
+            Literal::new(true, equ.clone(), vec![1, 0]),
+        ])));
+        thy.add_clause(Rc::new(Clause::new(vec![
+            Literal::new(false, equ.clone(), vec![0, 1]),
+            Literal::new(false, equ.clone(), vec![1, 2]),
+            Literal::new(true, equ, vec![0, 2]),
+        ])));
+        thy
+    }
+
+    #[test]
+    fn lower() {
+        let thy = equivalence();
+
+        // the number of equivalence relations are the Bell numbers
+        let mut sol = super::lower(&thy, &[("set", 3)]);
+        assert_eq!(sol.predicates().count(), 1);
+        assert_eq!(sol.count_all(), 5);
+        let mut sol = super::lower(&thy, &[("other", 1), ("set", 4)]);
+        assert_eq!(sol.count_all(), 15);
+    }
+
+    #[test]
+    fn search_sizes() {
+        let thy = equivalence();
+        assert_eq!(
+            super::search_sizes(&thy, "set", 1..5),
+            vec![(1, 1), (2, 2), (3, 5), (4, 15)]
+        );
+    }
 }
@@ -22,22 +22,60 @@
 pub enum Token<'a> {
     Literal(&'a str),
     Integer(usize),
+    /// An integer with a leading sign, only produced by a tokenizer created
+    /// with `Tokenizer::new_with_signed`.
+    Signed(i64),
     Operator(char),
     String(&'a str),
+    /// A string with escape sequences, which is stored unescaped.
+    OwnedString(String),
     Error(&'a str),
 }
 
+/// Replaces the escape sequences `\"`, `\\`, `\n` and `\t` in the string,
+/// or returns `None` if some other character is escaped.
+fn unescape(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '"' => result.push('"'),
+            '\\' => result.push('\\'),
+            'n' => result.push('\n'),
+            't' => result.push('\t'),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
 /// A tokenizer that breaks down an input string into tokens separated by
 /// whitespace.
 pub struct Tokenizer<'a> {
     /// byte position
     index: usize,
 
+    /// byte position of the last token
+    start: usize,
+
     /// input string
     input: &'a str,
 
     /// operator characters
     opers: &'a str,
+
+    /// whether a sign directly before digits starts a signed integer
+    signed: bool,
+
+    /// whether the last token can be the left operand of an operator
+    operand: bool,
+
+    /// the character starting a comment that lasts until the end of line
+    comment: Option<char>,
 }
 
 impl<'a> Tokenizer<'a> {
@@ -46,10 +84,67 @@ impl<'a> Tokenizer<'a> {
     pub fn new(input: &'a str, opers: &'a str) -> Self {
         Self {
             index: 0,
+            start: 0,
             input,
             opers,
+            signed: false,
+            operand: false,
+            comment: None,
+        }
+    }
+
+    /// Makes the tokenizer skip everything from the given character to the
+    /// end of the line, except inside strings.
+    pub fn with_comments(self, comment: char) -> Self {
+        Self {
+            comment: Some(comment),
+            ..self
         }
     }
+
+    /// Same as `new`, but a `+` or `-` sign directly followed by digits
+    /// yields a `Signed` integer, unless it follows an operand (a literal,
+    /// number, string or closing parenthesis) where it is an operator.
+    pub fn new_with_signed(input: &'a str, opers: &'a str) -> Self {
+        Self {
+            signed: true,
+            ..Self::new(input, opers)
+        }
+    }
+
+    /// Returns the byte position where the last token started, or the end
+    /// of the input if there are no more tokens.
+    pub fn offset(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the line and column, both starting from one, where the last
+    /// token started. The column counts characters, not bytes.
+    pub fn position(&self) -> (usize, usize) {
+        let prefix = &self.input[..self.start];
+        let line = prefix.matches('\n').count() + 1;
+        let begin = prefix.rfind('\n').map_or(0, |pos| pos + 1);
+        (line, prefix[begin..].chars().count() + 1)
+    }
+
+    /// Returns an iterator that yields the tokens together with their
+    /// starting byte positions.
+    pub fn spanned(self) -> Spanned<'a> {
+        Spanned(self)
+    }
+}
+
+/// An iterator of tokens with their byte positions created by
+/// `Tokenizer::spanned`.
+pub struct Spanned<'a>(Tokenizer<'a>);
+
+impl<'a> Iterator for Spanned<'a> {
+    type Item = (usize, Token<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.0.next()?;
+        Some((self.0.offset(), token))
+    }
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
@@ -58,11 +153,16 @@ impl<'a> Iterator for Tokenizer<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         let mut iter = self.input[self.index..].char_indices();
 
-        // eat whitespace
+        // eat whitespace and comments
         let mut pos1 = self.index;
         let mut head = ' ';
+        let mut comment = false;
         for (n, c) in &mut iter {
-            if !c.is_whitespace() {
+            if comment {
+                comment = c != '\n';
+            } else if Some(c) == self.comment {
+                comment = true;
+            } else if !c.is_whitespace() {
                 pos1 = self.index + n;
                 head = c;
                 break;
@@ -72,8 +172,10 @@ impl<'a> Iterator for Tokenizer<'a> {
         // end of string
         if head == ' ' {
             self.index = self.input.len();
+            self.start = self.index;
             return None;
         }
+        self.start = pos1;
 
         // handle cases
         let mut pos2 = self.input.len();
@@ -96,9 +198,31 @@ impl<'a> Iterator for Tokenizer<'a> {
                 Ok(num) => Token::Integer(num),
                 Err(_) => Token::Error(&self.input[pos1..pos2]),
             }
+        } else if self.signed
+            && !self.operand
+            && (head == '+' || head == '-')
+            && iter.clone().next().is_some_and(|(_, c)| c.is_ascii_digit())
+        {
+            for (n, c) in iter {
+                if !c.is_ascii_digit() {
+                    pos2 = self.index + n;
+                    break;
+                }
+            }
+            match self.input[pos1..pos2].parse::<i64>() {
+                Ok(num) => Token::Signed(num),
+                Err(_) => Token::Error(&self.input[pos1..pos2]),
+            }
         } else if head == '"' {
+            let mut escapes = false;
+            let mut escaped = false;
             for (n, c) in iter {
-                if c == '"' {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escapes = true;
+                    escaped = true;
+                } else if c == '"' {
                     pos2 = self.index + n;
                     break;
                 }
@@ -107,7 +231,14 @@ impl<'a> Iterator for Tokenizer<'a> {
                 Token::Error(&self.input[pos1..])
             } else {
                 pos2 += 1;
-                Token::String(&self.input[(pos1 + 1)..(pos2 - 1)])
+                let text = &self.input[(pos1 + 1)..(pos2 - 1)];
+                if !escapes {
+                    Token::String(text)
+                } else if let Some(text) = unescape(text) {
+                    Token::OwnedString(text)
+                } else {
+                    Token::Error(&self.input[pos1..pos2])
+                }
             }
         } else {
             pos2 = pos1 + head.len_utf8();
@@ -119,6 +250,10 @@ impl<'a> Iterator for Tokenizer<'a> {
         };
 
         self.index = pos2;
+        self.operand = !matches!(
+            token,
+            Token::Operator(c) if c != ')' && c != ']'
+        );
         Some(token)
     }
 }
@@ -146,4 +281,99 @@ mod tests {
         assert_eq!(tokens.next(), Some(Token::Error("\"y")));
         assert_eq!(tokens.next(), None);
     }
+
+    #[test]
+    fn spanned() {
+        let tokens: Vec<(usize, Token)> =
+            Tokenizer::new("ab (12) \"é\"+", "()+").spanned().collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, Token::Literal("ab")),
+                (3, Token::Operator('(')),
+                (4, Token::Integer(12)),
+                (6, Token::Operator(')')),
+                (8, Token::String("é")),
+                (12, Token::Operator('+')),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let input = r#""a\"b" "c\\d" "e\nf" "g\th" "plain" "bad\q" "end\"#;
+        let tokens: Vec<Token> = Tokenizer::new(input, "").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::OwnedString("a\"b".into()),
+                Token::OwnedString("c\\d".into()),
+                Token::OwnedString("e\nf".into()),
+                Token::OwnedString("g\th".into()),
+                Token::String("plain"),
+                Token::Error(r#""bad\q""#),
+                Token::Error(r#""end\"#),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let input = "ab # cd\n+# \"x\"\n\"#\" 12# last";
+        let tokens: Vec<Token> = Tokenizer::new(input, "+").with_comments('#').collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Literal("ab"),
+                Token::Operator('+'),
+                Token::String("#"),
+                Token::Integer(12),
+            ]
+        );
+
+        let tokens: Vec<Token> = Tokenizer::new("a # b", "").collect();
+        assert_eq!(
+            tokens,
+            vec![Token::Literal("a"), Token::Error("#"), Token::Literal("b")]
+        );
+    }
+
+    #[test]
+    fn position() {
+        let mut tokens = Tokenizer::new("ab\n  (é 12\n\n)", "()");
+        let mut positions = vec![];
+        while tokens.next().is_some() {
+            positions.push(tokens.position());
+        }
+        assert_eq!(positions, vec![(1, 1), (2, 3), (2, 4), (2, 6), (4, 1)]);
+        assert_eq!(tokens.position(), (4, 2));
+    }
+
+    #[test]
+    fn signed() {
+        let tokens: Vec<Token> =
+            Tokenizer::new_with_signed("-5 + 5 3-4 (+12) x -0", "()+-").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Signed(-5),
+                Token::Operator('+'),
+                Token::Integer(5),
+                Token::Integer(3),
+                Token::Operator('-'),
+                Token::Integer(4),
+                Token::Operator('('),
+                Token::Signed(12),
+                Token::Operator(')'),
+                Token::Literal("x"),
+                Token::Operator('-'),
+                Token::Integer(0),
+            ]
+        );
+
+        let tokens: Vec<Token> = Tokenizer::new("-5", "-").collect();
+        assert_eq!(tokens, vec![Token::Operator('-'), Token::Integer(5)]);
+        let tokens: Vec<Token> = Tokenizer::new_with_signed("-99999999999999999999", "-").collect();
+        assert_eq!(tokens, vec![Token::Error("-99999999999999999999")]);
+    }
 }
@@ -19,7 +19,7 @@ use std::rc::Rc;
 
 use super::{Clause, Coord, Literal, State, UniversalFormula};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EvalStep {
     Loop(u32),
     Atom(u32),
@@ -32,7 +32,59 @@ pub struct Evaluator {
 }
 
 impl Evaluator {
-    pub fn watch(&self, state: &mut State, lit: &Literal) -> Option<Clause> {
+    /// Creates the program that watches the given atom of the formula. The
+    /// watched atom comes first, then all atoms whose variables are already
+    /// bound are checked. If there is no such atom, then the one with the
+    /// fewest free variables is chosen and loops are emitted for those.
+    pub fn compile(formula: &Rc<UniversalFormula>, watched_atom: usize) -> Evaluator {
+        let mut atoms: Vec<usize> = (0..formula.atom_count())
+            .filter(|&idx| idx != watched_atom)
+            .collect();
+        let mut bound = vec![false; formula.arity()];
+        let mut program = vec![EvalStep::Atom(watched_atom as u32)];
+        for &var in formula.disjunction(watched_atom).variables() {
+            bound[var] = true;
+        }
+
+        let free_vars = |bound: &[bool], atom: usize| {
+            let mut vars: Vec<usize> = Vec::new();
+            for &var in formula.disjunction(atom).variables() {
+                if !bound[var] && !vars.contains(&var) {
+                    vars.push(var);
+                }
+            }
+            vars
+        };
+
+        while !atoms.is_empty() {
+            let (pos, vars) = atoms
+                .iter()
+                .map(|&atom| free_vars(&bound, atom))
+                .enumerate()
+                .min_by_key(|(_, vars)| vars.len())
+                .unwrap();
+            for var in vars {
+                bound[var] = true;
+                program.push(EvalStep::Loop(var as u32));
+            }
+            program.push(EvalStep::Atom(atoms.remove(pos) as u32));
+        }
+
+        Evaluator {
+            formula: formula.clone(),
+            program: program.into_boxed_slice(),
+        }
+    }
+
+    /// Returns the index of the atom watched by this evaluator.
+    pub fn watched(&self) -> usize {
+        match self.program.first() {
+            Some(&EvalStep::Atom(atom)) => atom as usize,
+            _ => unreachable!("the program of an evaluator starts with the watched atom"),
+        }
+    }
+
+    pub fn watch(&self, state: &mut State, lit: &Literal) -> Option<Clause<'_>> {
         if let Some(&EvalStep::Atom(atom)) = self.program.first() {
             let atom = self.formula.disjunction(atom as usize);
             debug_assert_eq!(atom.negated(), lit.negated());
@@ -42,7 +94,7 @@ impl Evaluator {
             let mut coords = vec![Coord(usize::MAX); self.formula.arity()];
             for (&var, &coord) in atom.variables().iter().zip(lit.coords()) {
                 debug_assert_ne!(coord, Coord(usize::MAX));
-                if coords[var] != Coord(usize::MAX) {
+                if coords[var] != Coord(usize::MAX) && coords[var] != coord {
                     return None;
                 }
                 coords[var] = coord;
@@ -77,8 +129,8 @@ impl Evaluator {
                 debug_assert_eq!(coords[var as usize], Coord(usize::MAX));
                 for coord in 0..size {
                     coords[var as usize] = Coord(coord);
+                    // keep the coordinates of the falsified instance
                     if self.propagate(state, coords, step + 1) {
-                        coords[var as usize] = Coord(usize::MAX);
                         return true;
                     }
                 }
@@ -88,6 +140,26 @@ impl Evaluator {
         }
     }
 
+    /// Returns true if, under the current state, some instance of the clause
+    /// extending the given coordinates has all of its literals false except
+    /// possibly the watched first one, that is the instance is unit or
+    /// conflicting. The coordinates of the variables not bound by the watched
+    /// literal must be `Coord(usize::MAX)`. The state is not modified.
+    ///
+    /// ```ignore
+    /// let mut coords = vec![Coord(usize::MAX); evaluator.formula.arity()];
+    /// coords[0] = Coord(1);
+    /// if evaluator.is_unit(&state, &coords) {
+    ///     println!("the watched literal is forced");
+    /// }
+    /// ```
+    pub fn is_unit(&self, state: &State, coords: &[Coord]) -> bool {
+        debug_assert!(matches!(self.program.first(), Some(&EvalStep::Atom(_))));
+        debug_assert_eq!(coords.len(), self.formula.arity());
+        let mut coords = coords.to_vec();
+        self.conflicting(state, &mut coords, 1)
+    }
+
     fn conflicting(&self, state: &State, coords: &mut [Coord], step: usize) -> bool {
         match self.program.get(step) {
             None => true,
@@ -115,3 +187,47 @@ impl Evaluator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Domain, Predicate};
+    use super::*;
+
+    #[test]
+    fn compile() {
+        let set = Rc::new(Domain::new("set".into(), 3));
+        let equ = Rc::new(Predicate::new(
+            "equ".into(),
+            vec![set.clone(), set.clone()],
+            0,
+        ));
+        let mul = Rc::new(Predicate::new("mul".into(), vec![set.clone(); 3], 9));
+
+        // the manual program of the symmetry of equ in Solver::test
+        let disjunction = vec![(true, equ.clone(), vec![0, 1]), (false, equ, vec![1, 0])];
+        let formula = Rc::new(UniversalFormula::new(disjunction.into_iter(), 0));
+        let eval = Evaluator::compile(&formula, 0);
+        assert_eq!(&*eval.program, &[EvalStep::Atom(0), EvalStep::Atom(1)]);
+
+        let disjunction = vec![
+            (true, mul.clone(), vec![0, 1, 3]),
+            (true, mul.clone(), vec![3, 2, 4]),
+            (true, mul.clone(), vec![1, 2, 5]),
+            (false, mul, vec![0, 5, 4]),
+        ];
+        let formula = Rc::new(UniversalFormula::new(disjunction.into_iter(), 0));
+        let eval = Evaluator::compile(&formula, 3);
+        assert_eq!(
+            &*eval.program,
+            &[
+                EvalStep::Atom(3),
+                EvalStep::Loop(1),
+                EvalStep::Loop(3),
+                EvalStep::Atom(0),
+                EvalStep::Loop(2),
+                EvalStep::Atom(1),
+                EvalStep::Atom(2),
+            ]
+        );
+    }
+}
@@ -131,6 +131,24 @@ impl UniversalFormula {
         &self.domains[var]
     }
 
+    /// Returns the distinct predicates of the atomic formulas in the order
+    /// of their first occurrence.
+    pub fn predicates(&self) -> impl Iterator<Item = &Rc<Predicate>> + '_ {
+        self.disjunction
+            .iter()
+            .enumerate()
+            .filter(|(idx, atom)| {
+                self.disjunction[..*idx]
+                    .iter()
+                    .all(|other| !other.predicate().ptr_eq(atom.predicate()))
+            })
+            .map(|(_, atom)| atom.predicate())
+    }
+
+    pub fn atom_count(&self) -> usize {
+        self.disjunction.len()
+    }
+
     pub fn disjunction(&self, pos: usize) -> &AtomicFormula {
         &self.disjunction[pos]
     }
@@ -186,7 +204,7 @@ impl<'a> Clause<'a> {
         ClauseIdx(self.formula.cla_start + cla_offset)
     }
 
-    pub fn literals(&self) -> Vec<Literal> {
+    pub fn literals(&self) -> Vec<Literal<'_>> {
         self.formula
             .disjunction
             .iter()
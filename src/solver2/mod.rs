@@ -24,8 +24,8 @@ mod solver;
 
 use boolean::{Bool, FALSE, TRUE, UNDEF};
 use domain::{get_coords, get_offset, Coord, Domain};
-use eval1::{EvalStep, Evaluator};
-use formula::{Clause, ClauseIdx, UniversalFormula};
+use eval1::Evaluator;
+use formula::{AtomicFormula, Clause, ClauseIdx, UniversalFormula};
 use predicate::{Literal, LiteralIdx, Predicate};
 use solver::State;
 
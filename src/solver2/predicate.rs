@@ -52,6 +52,10 @@ impl Predicate {
         &self.domains[pos]
     }
 
+    pub fn var_start(&self) -> usize {
+        self.var_start
+    }
+
     pub fn var_count(&self) -> usize {
         self.var_count
     }
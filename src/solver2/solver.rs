@@ -16,8 +16,8 @@
 */
 
 use super::{
-    Bool, Clause, ClauseIdx, Coord, Domain, EvalStep, Evaluator, Literal, LiteralIdx, Predicate,
-    UniversalFormula, FALSE, TRUE, UNDEF,
+    AtomicFormula, Bool, Clause, ClauseIdx, Coord, Domain, Evaluator, Literal, LiteralIdx,
+    Predicate, UniversalFormula, FALSE, TRUE, UNDEF,
 };
 
 use std::rc::Rc;
@@ -25,6 +25,8 @@ use std::rc::Rc;
 #[derive(Debug, Default)]
 pub struct State {
     values: Vec<Bool>,
+    trail: Vec<LiteralIdx>,
+    head: usize,
 }
 
 impl State {
@@ -41,11 +43,31 @@ impl State {
         val ^ lit.negated()
     }
 
+    /// Returns the number of variables that have no value yet.
+    pub fn count_undef(&self) -> usize {
+        self.values.iter().filter(|val| val.is_undef()).count()
+    }
+
+    /// Returns true if every variable has a value.
+    pub fn is_complete(&self) -> bool {
+        self.values.iter().all(|val| !val.is_undef())
+    }
+
     /// Sets the given literal to true and enqueues it for unit propagation.
     pub fn enqueue(&mut self, lit: LiteralIdx) {
         let var = lit.variable();
         assert!(self.values[var].is_undef());
         self.values[var] = if lit.negated() { FALSE } else { TRUE };
+        self.trail.push(lit);
+    }
+
+    /// Returns the next enqueued literal that was not yet propagated.
+    pub fn dequeue(&mut self) -> Option<LiteralIdx> {
+        let lit = self.trail.get(self.head).cloned();
+        if lit.is_some() {
+            self.head += 1;
+        }
+        lit
     }
 }
 
@@ -55,13 +77,16 @@ pub struct Solver {
     domains: Vec<Rc<Domain>>,
     predicates: Vec<Rc<Predicate>>,
     formulas: Vec<Rc<UniversalFormula>>,
+    evaluators: Vec<Evaluator>,
+    /// The evaluators whose watched atom matches the variable.
+    watches: Vec<Vec<usize>>,
     cla_count: usize,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct DomainIdx(usize);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct PredicateIdx(usize);
 
 impl Solver {
@@ -80,23 +105,114 @@ impl Solver {
         let pred = Rc::new(Predicate::new(name, domains, self.state.get_variables()));
         self.state
             .set_variables(self.state.get_variables() + pred.var_count());
+        self.watches.resize(self.state.get_variables(), Vec::new());
         self.predicates.push(pred);
         idx
     }
 
+    /// Returns the index of the predicate with the given name, if any.
+    pub fn predicate_by_name(&self, name: &str) -> Option<PredicateIdx> {
+        self.predicates
+            .iter()
+            .position(|pred| pred.name() == name)
+            .map(PredicateIdx)
+    }
+
     pub fn add_formula(&mut self, disjunction: Vec<(bool, PredicateIdx, Vec<usize>)>) {
+        self.add_formula_iter(disjunction);
+    }
+
+    /// Same as `add_formula`, but the atomic formulas (each with a negated
+    /// flag, predicate and variables) can come from any iterator, which is
+    /// convenient for generated theories.
+    pub fn add_formula_iter<ITER>(&mut self, disjunction: ITER)
+    where
+        ITER: IntoIterator<Item = (bool, PredicateIdx, Vec<usize>)>,
+        ITER::IntoIter: ExactSizeIterator,
+    {
         let disjunction = disjunction
             .into_iter()
             .map(|(neg, pred, vars)| (neg, self.predicates[pred.0].clone(), vars));
         let formula = Rc::new(UniversalFormula::new(disjunction, self.cla_count));
         self.cla_count += formula.cla_count();
+        for atom in 0..formula.atom_count() {
+            self.add_watches(formula.disjunction(atom), self.evaluators.len());
+            self.evaluators.push(Evaluator::compile(&formula, atom));
+        }
         self.formulas.push(formula);
     }
 
-    fn get_literal(&self, idx: LiteralIdx) -> Literal {
+    /// Registers the evaluator for all variables of the predicate of the
+    /// atom whose coordinates agree at the repeated variables of the atom.
+    fn add_watches(&mut self, atom: &AtomicFormula, eval: usize) {
+        let pred = atom.predicate();
+        let vars = atom.variables();
+        let mut coords = vec![Coord(0); pred.arity()];
+        for offset in 0..pred.var_count() {
+            pred.get_coords(offset, &mut coords);
+            let matching = (0..vars.len())
+                .all(|i| (0..i).all(|j| vars[i] != vars[j] || coords[i] == coords[j]));
+            if matching {
+                self.watches[pred.var_start() + offset].push(eval);
+            }
+        }
+    }
+
+    /// Returns the indices of the evaluators that need to run when the given
+    /// literal becomes false.
+    fn watchers(&self, lit: LiteralIdx) -> impl Iterator<Item = usize> + '_ {
+        self.watches[lit.variable()]
+            .iter()
+            .cloned()
+            .filter(move |&eval| {
+                let eval = &self.evaluators[eval];
+                eval.formula.disjunction(eval.watched()).negated() == lit.negated()
+            })
+    }
+
+    /// Sets the value of the predicate at the given coordinates, just like
+    /// `set_value` of the first solver.
+    pub fn set_value(&mut self, sign: bool, pred: PredicateIdx, coordinates: &[usize]) {
+        let pred = &self.predicates[pred.0];
+        let coords = coordinates.iter().map(|&c| Coord(c)).collect();
+        let lit = Literal::new(!sign, pred, coords);
+        self.state.enqueue(lit.idx());
+    }
+
+    /// Sets the binary predicate to the equality relation.
+    pub fn set_equality(&mut self, pred: PredicateIdx) {
+        let predicate = &self.predicates[pred.0];
+        assert_eq!(predicate.arity(), 2);
+        assert!(predicate.domain(0).ptr_eq(predicate.domain(1)));
+        let size = predicate.domain(0).size();
+        for i in 0..size {
+            for j in 0..size {
+                self.set_value(i == j, pred, &[i, j]);
+            }
+        }
+    }
+
+    /// Propagates all enqueued literals by running the evaluators watching
+    /// their negation, which enqueue the implied literals in turn. Returns
+    /// the first clause whose literals are all false.
+    pub fn propagate(&mut self) -> Option<ClauseIdx> {
+        while let Some(idx) = self.state.dequeue() {
+            let lit = Self::get_literal(&self.predicates, !idx);
+            let watchers: Vec<usize> = self.watchers(!idx).collect();
+            for eval in watchers {
+                let eval = &self.evaluators[eval];
+                if let Some(cla) = eval.watch(&mut self.state, &lit) {
+                    return Some(cla.idx());
+                }
+            }
+        }
+        None
+    }
+
+    fn get_literal(predicates: &[Rc<Predicate>], idx: LiteralIdx) -> Literal<'_> {
         let negated = idx.negated();
         let mut offset = idx.variable();
-        for predicate in self.predicates.iter() {
+        for predicate in predicates.iter() {
             if offset < predicate.var_count() {
                 let mut coords = vec![Coord(0); predicate.arity()];
                 predicate.get_coords(offset, &mut coords);
@@ -109,7 +225,7 @@ impl Solver {
         panic!();
     }
 
-    fn get_clause(&self, idx: ClauseIdx) -> Clause {
+    fn get_clause(&self, idx: ClauseIdx) -> Clause<'_> {
         let mut offset = idx.0;
         for formula in self.formulas.iter() {
             if offset < formula.cla_count() {
@@ -139,10 +255,7 @@ impl Solver {
     }
 
     pub fn test(&mut self) {
-        let watcher = Evaluator {
-            formula: self.formulas[1].clone(),
-            program: vec![EvalStep::Atom(0), EvalStep::Atom(1)].into(),
-        };
+        let watcher = Evaluator::compile(&self.formulas[1], 0);
 
         let lit1 = Literal::new(true, &self.predicates[0], vec![Coord(1), Coord(2)]);
         self.state.enqueue(!lit1.idx());
@@ -154,3 +267,113 @@ impl Solver {
         println!("{}", self.state.get_value(lit2.idx()));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_undef() {
+        let mut state: State = Default::default();
+        assert!(state.is_complete());
+        state.set_variables(3);
+        assert_eq!(state.count_undef(), 3);
+        state.enqueue(LiteralIdx::new(false, 0));
+        state.enqueue(LiteralIdx::new(true, 2));
+        assert_eq!(state.count_undef(), 1);
+        assert!(!state.is_complete());
+        state.enqueue(LiteralIdx::new(true, 1));
+        assert_eq!(state.count_undef(), 0);
+        assert!(state.is_complete());
+    }
+
+    fn associative(sol: &mut Solver) -> PredicateIdx {
+        let set = sol.add_domain("set".into(), 2);
+        let mul = sol.add_predicate("mul".into(), vec![set, set, set]);
+        sol.add_formula(vec![
+            (true, mul, vec![0, 1, 3]),
+            (true, mul, vec![3, 2, 4]),
+            (true, mul, vec![1, 2, 5]),
+            (false, mul, vec![0, 5, 4]),
+        ]);
+        mul
+    }
+
+    #[test]
+    fn propagate() {
+        let mut sol: Solver = Default::default();
+        let mul = associative(&mut sol);
+        let lit = Literal::new(
+            false,
+            &sol.predicates[mul.0],
+            vec![Coord(0), Coord(1), Coord(1)],
+        );
+        let lit = lit.idx();
+
+        // (0*0)*0 = 1*0 = 1 and 0*(0*0) = 0*1 must be 1
+        sol.set_value(true, mul, &[0, 0, 1]);
+        sol.set_value(true, mul, &[1, 0, 1]);
+        assert!(sol.state.get_value(lit).is_undef());
+        assert_eq!(sol.propagate(), None);
+        assert!(sol.state.get_value(lit).is_true());
+
+        let mut sol: Solver = Default::default();
+        let mul = associative(&mut sol);
+        sol.set_value(false, mul, &[0, 1, 1]);
+        sol.set_value(true, mul, &[0, 0, 1]);
+        sol.set_value(true, mul, &[1, 0, 1]);
+        let cla = sol.propagate().unwrap();
+        assert_eq!(
+            sol.get_clause(cla).to_string(),
+            "-mul[0,0,1] | -mul[1,0,1] | -mul[0,0,1] | +mul[0,1,1]"
+        );
+    }
+
+    #[test]
+    fn predicate_by_name() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_predicate("equ".into(), vec![set, set]);
+        let one = sol.add_predicate("one".into(), vec![set]);
+        assert_eq!(sol.predicate_by_name("equ"), Some(equ));
+        assert_eq!(sol.predicate_by_name("one"), Some(one));
+        assert_eq!(sol.predicate_by_name("mul"), None);
+    }
+
+    #[test]
+    fn set_equality() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let equ = sol.add_predicate("equ".into(), vec![set, set]);
+        sol.set_equality(equ);
+        for i in 0..3 {
+            for j in 0..3 {
+                let coords = vec![Coord(i), Coord(j)];
+                let lit = Literal::new(false, &sol.predicates[equ.0], coords).idx();
+                assert_eq!(sol.state.get_value(lit).is_true(), i == j);
+            }
+        }
+        assert!(sol.state.is_complete());
+    }
+
+    #[test]
+    fn watchers() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let equ = sol.add_predicate("equ".into(), vec![set, set]);
+        let one = sol.add_predicate("one".into(), vec![set]);
+        sol.add_formula(vec![(true, equ, vec![0, 1]), (false, equ, vec![1, 0])]);
+        sol.add_formula(vec![(true, one, vec![0]), (false, equ, vec![0, 0])]);
+
+        let lit = |neg: bool, pred: PredicateIdx, coords: &[usize]| {
+            let coords = coords.iter().map(|&c| Coord(c)).collect();
+            Literal::new(neg, &sol.predicates[pred.0], coords).idx()
+        };
+        let watchers = |lit: LiteralIdx| sol.watchers(lit).collect::<Vec<_>>();
+        assert_eq!(watchers(lit(true, equ, &[1, 2])), vec![0]);
+        assert_eq!(watchers(lit(false, equ, &[1, 2])), vec![1]);
+        assert_eq!(watchers(lit(false, equ, &[1, 1])), vec![1, 3]);
+        assert_eq!(watchers(lit(true, one, &[2])), vec![2]);
+        assert_eq!(watchers(lit(false, one, &[2])), Vec::<usize>::new());
+    }
+}
@@ -15,6 +15,7 @@
 * along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+use super::bitops::*;
 use super::buffer::Buffer2;
 use super::shape::Shape;
 
@@ -25,19 +26,78 @@ struct Axis<const LEN: usize> {
     strides: [(usize, usize); LEN],
 }
 
+/// Computes the disjunction of the conjunction of the input tables into
+/// the output table, like a tensor contraction. All shapes have the same
+/// lengths, and the output has stride zero along the contracted axes.
 #[derive(Debug, Clone)]
-struct Conj<const LEN: usize> {
+pub(super) struct Conj<const LEN: usize> {
     output: Shape,
     inputs: [Shape; LEN],
 }
 
 impl<const LEN: usize> Conj<LEN> {
-    fn new(output: Shape, inputs: [Shape; LEN]) -> Self {
+    pub(super) fn new(output: Shape, inputs: [Shape; LEN]) -> Self {
         for input in inputs.iter() {
             debug_assert!(output.equals(input));
         }
         Self { output, inputs }
     }
 
-    fn apply(&self, _buffer: &mut Buffer2) {}
+    /// Overwrites the output cells in the buffer. Each output cell becomes
+    /// the disjunction over the contracted axes of the conjunction of the
+    /// corresponding input cells, so the inputs must not overlap the output.
+    pub(super) fn apply(&self, buffer: &mut Buffer2) {
+        for pos in self.output.positions() {
+            buffer.set(pos, BOOL_FALSE);
+        }
+
+        let mut inputs = self.inputs.clone().map(|input| input.positions());
+        for out in self.output.positions() {
+            let mut val = BOOL_TRUE;
+            for input in inputs.iter_mut() {
+                val = BOOL_AND.of(val, buffer.get(input.next().unwrap()));
+            }
+            buffer.set(out, BOOL_OR.of(buffer.get(out), val));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composition() {
+        // rel1 at 0..9, rel2 at 9..18 and the composition at 18..27
+        let rel1 = [(0, 1), (1, 1), (2, 0)];
+        let rel2 = [(0, 2), (1, 0)];
+        let mut buffer = Buffer2::new(27, BOOL_UNDEF1);
+        for x in 0..3 {
+            for y in 0..3 {
+                let val = |rel: &[(usize, usize)]| {
+                    if rel.contains(&(x, y)) {
+                        BOOL_TRUE
+                    } else {
+                        BOOL_FALSE
+                    }
+                };
+                buffer.set(x + 3 * y, val(&rel1));
+                buffer.set(9 + x + 3 * y, val(&rel2));
+            }
+        }
+
+        let lengths = || [3, 3, 3].into_iter();
+        let input1 = Shape::new([3, 3].into_iter(), 0).polymer(lengths(), &[0, 1]);
+        let input2 = Shape::new([3, 3].into_iter(), 9).polymer(lengths(), &[1, 2]);
+        let output = Shape::new([3, 3].into_iter(), 18).polymer(lengths(), &[0, 2]);
+        Conj::new(output, [input1, input2]).apply(&mut buffer);
+
+        for x in 0..3 {
+            for z in 0..3 {
+                let related = (0..3).any(|y| rel1.contains(&(x, y)) && rel2.contains(&(y, z)));
+                let val = if related { BOOL_TRUE } else { BOOL_FALSE };
+                assert_eq!(buffer.get(18 + x + 3 * z), val);
+            }
+        }
+    }
 }
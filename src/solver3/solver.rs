@@ -17,6 +17,7 @@
 
 use super::bitops::*;
 use super::buffer::Buffer2;
+use super::contraction::Conj;
 use super::shape::Shape;
 
 #[derive(Debug)]
@@ -41,6 +42,29 @@ pub struct Rel(usize);
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Var(pub usize);
 
+/// The reason why three relations cannot be composed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ComposeError {
+    /// The relation is not binary.
+    Arity(Rel),
+    /// The domains of the relations do not line up.
+    Domains,
+    /// The output relation is also one of the inputs.
+    Aliased(Rel),
+}
+
+impl std::fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ComposeError::Arity(rel) => write!(f, "relation {} is not binary", rel.0),
+            ComposeError::Domains => write!(f, "the domains do not match"),
+            ComposeError::Aliased(rel) => {
+                write!(f, "relation {} is both an input and the output", rel.0)
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Literal {
     sign: bool,
@@ -54,6 +78,22 @@ struct Clause {
     domains: Box<[Dom]>,
 }
 
+impl Clause {
+    /// Returns the distinct relations of the literals in the order of their
+    /// first occurrence.
+    fn relations(&self) -> impl Iterator<Item = Rel> + '_ {
+        self.literals
+            .iter()
+            .enumerate()
+            .filter(|(idx, lit)| {
+                self.literals[..*idx]
+                    .iter()
+                    .all(|other| other.relation != lit.relation)
+            })
+            .map(|(_, lit)| lit.relation)
+    }
+}
+
 #[derive(Debug)]
 struct Step {
     pos: usize,
@@ -139,6 +179,18 @@ impl Solver {
         self.assign(pos, sign, vec![]);
     }
 
+    /// Sets the binary relation to the equality relation.
+    pub fn set_equality(&mut self, rel: Rel) {
+        let domains = &self.relations[rel.0].domains;
+        assert!(domains.len() == 2 && domains[0] == domains[1]);
+        let size = self.domains[domains[0].0].size;
+        for i in 0..size {
+            for j in 0..size {
+                self.set_value(i == j, rel, &[i, j]);
+            }
+        }
+    }
+
     pub fn print(&self) {
         for dom in self.domains.iter() {
             println!("domain {} = {}", dom.name, dom.size);
@@ -173,6 +225,182 @@ impl Solver {
         }
     }
 
+    /// Prints a binary relation as a matrix whose rows are indexed by the
+    /// first coordinate and columns by the second one. Relations of other
+    /// arities are printed one tuple per line as in `print_relation`.
+    pub fn print_relation_grid(&self, rel: Rel) {
+        if self.relations[rel.0].domains.len() == 2 {
+            print!("{}", self.relation_grid(rel));
+        } else {
+            self.print_relation(rel);
+        }
+    }
+
+    // Returns the lines of the matrix of a binary relation.
+    fn relation_grid(&self, rel: Rel) -> String {
+        let rel = &self.relations[rel.0];
+        let shape = &rel.shape;
+        let width = shape.length(0).max(shape.length(1)).to_string().len();
+
+        let mut grid = format!("relation {}\n{:width$}", rel.name, "");
+        for col in 0..shape.length(1) {
+            grid.push_str(&format!(" {:width$}", col));
+        }
+        grid.push('\n');
+        for row in 0..shape.length(0) {
+            grid.push_str(&format!("{:width$}", row));
+            for col in 0..shape.length(1) {
+                let pos = shape.position([row, col].into_iter());
+                let val = BOOL_FORMAT1[self.assignment.get(pos).idx()];
+                grid.push_str(&format!(" {:width$}", val));
+            }
+            grid.push('\n');
+        }
+        grid
+    }
+
+    /// Overwrites the table of `out` with the composition of `a` and `b`,
+    /// so `out(x,z)` is the disjunction of `a(x,y) & b(y,z)` over all `y`.
+    /// The relations must be binary, and `out` must have the domain of `x`
+    /// from `a` and the domain of `z` from `b`, while the second domain of
+    /// `a` must be the first one of `b`. The output cannot be one of the
+    /// inputs.
+    pub fn compose(&mut self, out: Rel, a: Rel, b: Rel) -> Result<(), ComposeError> {
+        if out == a || out == b {
+            return Err(ComposeError::Aliased(out));
+        }
+        for rel in [out, a, b] {
+            if self.relations[rel.0].domains.len() != 2 {
+                return Err(ComposeError::Arity(rel));
+            }
+        }
+        let (out, a, b) = (
+            &self.relations[out.0],
+            &self.relations[a.0],
+            &self.relations[b.0],
+        );
+        if a.domains[1] != b.domains[0]
+            || out.domains[0] != a.domains[0]
+            || out.domains[1] != b.domains[1]
+        {
+            return Err(ComposeError::Domains);
+        }
+
+        let lengths = || [a.shape.length(0), a.shape.length(1), b.shape.length(1)].into_iter();
+        let conj = Conj::new(
+            out.shape.polymer(lengths(), &[0, 2]),
+            [
+                a.shape.polymer(lengths(), &[0, 1]),
+                b.shape.polymer(lengths(), &[1, 2]),
+            ],
+        );
+        conj.apply(&mut self.assignment);
+        Ok(())
+    }
+
+    /// Performs unit propagation on all instances of the clauses until no
+    /// more values can be derived. Returns `BOOL_FALSE` if some instance is
+    /// falsified, `BOOL_TRUE` if all of them are satisfied, and `BOOL_UNDEF2`
+    /// otherwise.
+    pub fn propagate(&mut self) -> Bit2 {
+        loop {
+            let mut result = BOOL_TRUE;
+            for idx in 0..self.clauses.len() {
+                result = BOOL_AND.of(result, self.propagate_clause(idx));
+                if result == BOOL_FALSE {
+                    return result;
+                }
+            }
+            if result != BOOL_UNDEF1 {
+                return result;
+            }
+        }
+    }
+
+    /// Evaluates every instance of the given clause and assigns the last
+    /// undefined literal of the unit instances. Returns `BOOL_UNDEF1` if some
+    /// value was assigned, otherwise the conjunction of the instances.
+    fn propagate_clause(&mut self, idx: usize) -> Bit2 {
+        let cla = &self.clauses[idx];
+        let lengths: Vec<usize> = cla
+            .domains
+            .iter()
+            .map(|dom| self.domains.get(dom.0).map_or(1, |dom| dom.size))
+            .collect();
+        if lengths.contains(&0) {
+            return BOOL_TRUE;
+        }
+
+        let mut result = BOOL_TRUE;
+        let mut units = vec![];
+        let mut cell = vec![0; cla.literals.len()];
+        let mut cor = vec![0; lengths.len()];
+        'outer: loop {
+            let mut val = BOOL_FALSE;
+            for (lit, pos) in cla.literals.iter().zip(cell.iter_mut()) {
+                let shape = &self.relations[lit.relation.0].shape;
+                *pos = shape.position(lit.variables.iter().map(|var| cor[var.0]));
+                let lit_val = self.assignment.get(*pos);
+                val = BOOL_OR.of(
+                    val,
+                    if lit.sign {
+                        lit_val
+                    } else {
+                        BOOL_NOT.of(lit_val)
+                    },
+                );
+            }
+
+            if val == BOOL_FALSE {
+                return val;
+            } else if val == BOOL_UNDEF1 {
+                let mut unit = (0, false);
+                let mut reason = vec![];
+                for (lit, &pos) in cla.literals.iter().zip(cell.iter()) {
+                    if self.assignment.get(pos) == BOOL_UNDEF1 {
+                        unit = (pos, lit.sign);
+                    } else {
+                        reason.push(pos);
+                    }
+                }
+                units.push((unit, reason));
+            }
+            result = BOOL_AND.of(result, val);
+
+            for (i, c) in cor.iter_mut().enumerate() {
+                *c += 1;
+                if *c >= lengths[i] {
+                    *c = 0;
+                } else {
+                    continue 'outer;
+                }
+            }
+            break;
+        }
+
+        for ((pos, sign), reason) in units {
+            // an earlier unit could have assigned it already
+            if self.assignment.get(pos) == BOOL_UNDEF1 {
+                self.assign(pos, sign, reason);
+            }
+        }
+        result
+    }
+
+    /// Returns the number of assignment steps made so far, which can be
+    /// passed later to `undo_to` to backtrack to this point.
+    pub fn mark(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Clears all the assignments made after the given mark was taken.
+    pub fn undo_to(&mut self, mark: usize) {
+        assert!(mark <= self.steps.len());
+        for step in self.steps.drain(mark..) {
+            self.assignment.set(step.pos, BOOL_UNDEF1);
+        }
+    }
+
     fn assign(&mut self, pos: usize, sign: bool, reason: Vec<usize>) {
         assert!(self.assignment.get(pos) == BOOL_UNDEF1);
         self.assignment
@@ -253,3 +481,132 @@ where
         write!(f, ")")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let two = sol.add_domain("two".into(), 2);
+        let equ = sol.add_relation("equ".into(), vec![set, set]);
+        let fun = sol.add_relation("fun".into(), vec![set, two]);
+        let out = sol.add_relation("out".into(), vec![set, two]);
+        for x in 0..3 {
+            for y in 0..3 {
+                sol.set_value(x == y, equ, &[x, y]);
+            }
+            for y in 0..2 {
+                sol.set_value(x % 2 == y, fun, &[x, y]);
+            }
+        }
+
+        assert_eq!(sol.compose(out, equ, fun), Ok(()));
+        for x in 0..3 {
+            for y in 0..2 {
+                let pos1 = sol.relations[fun.0].shape.position([x, y].into_iter());
+                let pos2 = sol.relations[out.0].shape.position([x, y].into_iter());
+                assert_eq!(sol.assignment.get(pos1), sol.assignment.get(pos2));
+            }
+        }
+
+        assert_eq!(sol.compose(out, fun, equ), Err(ComposeError::Domains));
+        let one = sol.add_relation("one".into(), vec![set]);
+        assert_eq!(sol.compose(out, equ, one), Err(ComposeError::Arity(one)));
+        assert_eq!(sol.compose(equ, equ, equ), Err(ComposeError::Aliased(equ)));
+        assert_eq!(sol.compose(out, equ, out), Err(ComposeError::Aliased(out)));
+    }
+
+    #[test]
+    fn set_equality() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 2);
+        let equ = sol.add_relation("equ".into(), vec![set, set]);
+        sol.set_equality(equ);
+        assert_eq!(
+            sol.relation_grid(equ),
+            "relation equ\n  0 1\n0 1 0\n1 0 1\n"
+        );
+        assert_eq!(sol.mark(), 4);
+    }
+
+    #[test]
+    fn relation_grid() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let two = sol.add_domain("two".into(), 2);
+        let rel = sol.add_relation("rel".into(), vec![set, two]);
+        sol.set_value(true, rel, &[0, 1]);
+        sol.set_value(false, rel, &[2, 0]);
+        assert_eq!(
+            sol.relation_grid(rel),
+            "relation rel\n  0 1\n0 ? 1\n1 ? ?\n2 0 ?\n"
+        );
+    }
+
+    #[test]
+    fn propagate() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 3);
+        let rel = sol.add_relation("rel".into(), vec![set, set]);
+        let one = sol.add_relation("one".into(), vec![set]);
+
+        // rel is reflexive and transitive, and one(x) & rel(x,y) -> one(y)
+        sol.add_clause(vec![(true, rel, vec![0, 0])]);
+        sol.add_clause(vec![
+            (false, rel, vec![0, 1]),
+            (false, rel, vec![1, 2]),
+            (true, rel, vec![0, 2]),
+        ]);
+        sol.add_clause(vec![
+            (false, one, vec![0]),
+            (false, rel, vec![0, 1]),
+            (true, one, vec![1]),
+        ]);
+        sol.set_value(true, rel, &[0, 1]);
+        sol.set_value(true, rel, &[1, 2]);
+        sol.set_value(true, one, &[0]);
+        assert_eq!(sol.propagate(), BOOL_UNDEF2);
+
+        let get = |rel: Rel, cor: &[usize]| {
+            let pos = sol.relations[rel.0].shape.position(cor.iter().cloned());
+            sol.assignment.get(pos)
+        };
+        for x in 0..3 {
+            assert_eq!(get(rel, &[x, x]), BOOL_TRUE);
+            assert_eq!(get(one, &[x]), BOOL_TRUE);
+        }
+        assert_eq!(get(rel, &[0, 2]), BOOL_TRUE);
+        assert_eq!(get(rel, &[2, 0]), BOOL_UNDEF1);
+        let pos = |cor: [usize; 2]| sol.relations[rel.0].shape.position(cor.into_iter());
+        let step = sol.steps.iter().find(|step| step.pos == pos([0, 2]));
+        assert_eq!(step.unwrap().reason, vec![pos([0, 1]), pos([1, 2])]);
+
+        sol.add_clause(vec![(false, one, vec![0])]);
+        assert_eq!(sol.propagate(), BOOL_FALSE);
+    }
+
+    #[test]
+    fn undo_to() {
+        let mut sol: Solver = Default::default();
+        let set = sol.add_domain("set".into(), 4);
+        let rel = sol.add_relation("rel".into(), vec![set]);
+        sol.set_value(true, rel, &[0]);
+        sol.set_value(false, rel, &[1]);
+        let before = sol.assignment.clone();
+
+        let mark = sol.mark();
+        assert_eq!(mark, 2);
+        sol.set_value(true, rel, &[2]);
+        sol.set_value(false, rel, &[3]);
+        assert_ne!(sol.assignment, before);
+
+        sol.undo_to(mark);
+        assert_eq!(sol.mark(), mark);
+        assert_eq!(sol.assignment, before);
+        sol.undo_to(0);
+        assert!((0..4).all(|pos| sol.assignment.get(pos) == BOOL_UNDEF1));
+    }
+}